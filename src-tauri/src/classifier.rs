@@ -0,0 +1,231 @@
+use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use crate::duplicates::normalize_description;
+use crate::parsers::{DEFAULT_CATEGORY, TRANSFER_CATEGORY};
+use crate::utils::rebase_category;
+
+// Below this posterior a suggestion is not worth showing
+pub const MIN_CONFIDENCE: f64 = 0.4;
+
+// Multinomial naive Bayes over description words and an amount bucket, with
+// Laplace smoothing. Counts rather than probabilities are kept so a
+// correction can be undone and relearned without retraining from scratch.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct CategoryModel {
+    documents: HashMap<String, u32>, // Per category
+    features: HashMap<String, HashMap<String, u32>>, // Category -> feature -> count
+    totals: HashMap<String, u32>, // Features counted per category
+}
+
+impl CategoryModel {
+    pub fn learn(&mut self, description: &str, amount: f64, category: &str) {
+        if !is_trainable(category) {
+            return;
+        }
+        *self.documents.entry(category.to_string()).or_insert(0) += 1;
+        let counts = self.features.entry(category.to_string()).or_default();
+        let total = self.totals.entry(category.to_string()).or_insert(0);
+        for feature in features(description, amount) {
+            *counts.entry(feature).or_insert(0) += 1;
+            *total += 1;
+        }
+    }
+
+    // Takes back an earlier `learn` call with the same arguments
+    pub fn forget(&mut self, description: &str, amount: f64, category: &str) {
+        if !is_trainable(category) {
+            return;
+        }
+        let documents = match self.documents.get_mut(category) {
+            Some(documents) if *documents > 0 => documents,
+            _ => return,
+        };
+        *documents -= 1;
+        if *documents == 0 {
+            self.documents.remove(category);
+            self.features.remove(category);
+            self.totals.remove(category);
+            return;
+        }
+
+        if let Some(counts) = self.features.get_mut(category) {
+            let total = self.totals.entry(category.to_string()).or_insert(0);
+            for feature in features(description, amount) {
+                if let Some(count) = counts.get_mut(&feature) {
+                    *count -= 1;
+                    *total = total.saturating_sub(1);
+                    if *count == 0 {
+                        counts.remove(&feature);
+                    }
+                }
+            }
+        }
+    }
+
+    // Files what was learned under `from` and its sub-categories under `to`
+    // when a category is renamed, moved or merged into another
+    pub fn rebase(&mut self, from: &str, to: &str) {
+        let categories: Vec<String> = self.documents.keys().cloned().collect();
+        for category in categories {
+            let moved = match rebase_category(&category, from, to) {
+                Some(moved) if moved != category => moved,
+                _ => continue,
+            };
+            if let Some(documents) = self.documents.remove(&category) {
+                *self.documents.entry(moved.clone()).or_insert(0) += documents;
+            }
+            if let Some(total) = self.totals.remove(&category) {
+                *self.totals.entry(moved.clone()).or_insert(0) += total;
+            }
+            if let Some(counts) = self.features.remove(&category) {
+                let merged = self.features.entry(moved).or_default();
+                for (feature, count) in counts {
+                    *merged.entry(feature).or_insert(0) += count;
+                }
+            }
+        }
+    }
+
+    // Most likely category with its posterior probability
+    pub fn suggest(&self, description: &str, amount: f64) -> Option<(String, f64)> {
+        let document_count: u32 = self.documents.values().sum();
+        if document_count == 0 {
+            return None;
+        }
+        let vocabulary: HashSet<&String> = self.features.values().flat_map(|counts| counts.keys()).collect();
+        let vocabulary_size = vocabulary.len().max(1) as f64;
+        let features = features(description, amount);
+
+        let scores: Vec<(&String, f64)> = self.documents
+            .iter()
+            .map(|(category, documents)| {
+                let counts = self.features.get(category);
+                let total = self.totals.get(category).copied().unwrap_or(0) as f64;
+                let mut score = (*documents as f64 / document_count as f64).ln();
+                for feature in &features {
+                    let count = counts.and_then(|counts| counts.get(feature)).copied().unwrap_or(0) as f64;
+                    score += ((count + 1.0) / (total + vocabulary_size)).ln();
+                }
+                (category, score)
+            })
+            .collect();
+
+        // Normalising the log scores gives the posterior of each category
+        let best = scores.iter().map(|(_, score)| *score).fold(f64::NEG_INFINITY, f64::max);
+        let sum: f64 = scores.iter().map(|(_, score)| (score - best).exp()).sum();
+        scores
+            .iter()
+            .find(|(_, score)| *score == best)
+            .map(|(category, _)| (category.to_string(), 1.0 / sum))
+    }
+}
+
+// Uncategorised rows and transfers say nothing about spending habits
+pub fn is_trainable(category: &str) -> bool {
+    let category = category.trim();
+    !category.is_empty() && category != DEFAULT_CATEGORY && category != TRANSFER_CATEGORY
+}
+
+// Normalised description words plus the sign and order of magnitude of the
+// amount, so a 9.99 subscription and a 900 rent payment stay apart
+fn features(description: &str, amount: f64) -> Vec<String> {
+    let mut features: Vec<String> = normalize_description(description)
+        .split_whitespace()
+        .filter(|word| word.chars().count() >= 2)
+        .map(str::to_string)
+        .collect();
+    let magnitude = amount.abs().max(1.0).log10().floor() as i32;
+    let sign = if amount < 0.0 { "-" } else { "+" };
+    features.push(format!("#{}{}", sign, magnitude));
+    features
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trained() -> CategoryModel {
+        let mut model = CategoryModel::default();
+        model.learn("CB CARREFOUR MARKET PARIS", -54.2, "Courses");
+        model.learn("CB CARREFOUR CITY", -12.8, "Courses");
+        model.learn("CB LIDL", -31.0, "Courses");
+        model.learn("NAVIGO SEPTEMBRE", -86.4, "Transport");
+        model.learn("SNCF VOYAGEURS", -45.0, "Transport");
+        model
+    }
+
+    fn snapshot(model: &CategoryModel) -> serde_json::Value {
+        serde_json::to_value(model).unwrap()
+    }
+
+    #[test]
+    fn suggests_the_category_of_similar_descriptions() {
+        let model = trained();
+        let (category, confidence) = model.suggest("CB CARREFOUR EXPRESS", -23.5).unwrap();
+        assert_eq!(category, "Courses");
+        assert!((MIN_CONFIDENCE..=1.0).contains(&confidence));
+        assert_eq!(model.suggest("SNCF INTERNET", -60.0).unwrap().0, "Transport");
+        assert!(CategoryModel::default().suggest("CB CARREFOUR", -10.0).is_none());
+    }
+
+    #[test]
+    fn gives_a_low_confidence_when_categories_tie() {
+        let mut model = CategoryModel::default();
+        for category in ["Courses", "Loisirs", "Santé"] {
+            model.learn("CB PAYPAL", -20.0, category);
+        }
+        let (_, confidence) = model.suggest("CB PAYPAL", -20.0).unwrap();
+        assert!((confidence - 1.0 / 3.0).abs() < 1e-9);
+        assert!(confidence < MIN_CONFIDENCE);
+    }
+
+    #[test]
+    fn does_not_learn_uncategorised_rows_or_transfers() {
+        let mut model = CategoryModel::default();
+        model.learn("CB CARREFOUR", -10.0, DEFAULT_CATEGORY);
+        model.learn("VIR LIVRET A", -100.0, TRANSFER_CATEGORY);
+        model.learn("CB FNAC", -10.0, "  ");
+        assert!(model.suggest("CB CARREFOUR", -10.0).is_none());
+        assert!(!is_trainable(TRANSFER_CATEGORY));
+        assert!(is_trainable("Courses"));
+    }
+
+    #[test]
+    fn forgetting_undoes_learning() {
+        let mut model = trained();
+        let before = snapshot(&model);
+        model.learn("CB FNAC", -129.99, "Loisirs");
+        model.learn("CB LECLERC", -60.0, "Courses");
+        model.forget("CB LECLERC", -60.0, "Courses");
+        model.forget("CB FNAC", -129.99, "Loisirs");
+        assert_eq!(snapshot(&model), before);
+
+        // Taking back what was never learned changes nothing
+        model.forget("CB FNAC", -129.99, "Loisirs");
+        model.forget("CB FNAC", -129.99, DEFAULT_CATEGORY);
+        assert_eq!(snapshot(&model), before);
+
+        // Forgetting a category's last example removes the category
+        let mut model = CategoryModel::default();
+        model.learn("CB FNAC", -129.99, "Loisirs");
+        model.forget("CB FNAC", -129.99, "Loisirs");
+        assert_eq!(snapshot(&model), snapshot(&CategoryModel::default()));
+    }
+
+    #[test]
+    fn rebases_categories_and_their_children() {
+        let mut model = CategoryModel::default();
+        model.learn("CB CARREFOUR", -50.0, "Alimentation > Courses");
+        model.learn("RESTAURANT LE ZINC", -35.0, "Alimentation");
+        model.learn("CB BOULANGERIE", -4.0, "Maison");
+
+        model.rebase("Alimentation", "Maison");
+        assert_eq!(model.suggest("CB CARREFOUR", -50.0).unwrap().0, "Maison > Courses");
+        assert_eq!(model.suggest("RESTAURANT LE ZINC", -35.0).unwrap().0, "Maison");
+        // A merge adds the counts up, so the merged examples can still be forgotten
+        model.forget("RESTAURANT LE ZINC", -35.0, "Maison");
+        model.forget("CB BOULANGERIE", -4.0, "Maison");
+        model.forget("CB CARREFOUR", -50.0, "Maison > Courses");
+        assert_eq!(snapshot(&model), snapshot(&CategoryModel::default()));
+    }
+}
@@ -0,0 +1,68 @@
+use tauri::{command, State};
+use crate::{AppState, models::{Account, BalancePoint}};
+use anyhow::Result;
+
+#[command]
+pub async fn get_accounts(state: State<'_, AppState>) -> Result<Vec<Account>, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.get_accounts().await
+                .map_err(|e| format!("Erreur lors de la récupération des comptes: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn create_account(account: Account, state: State<'_, AppState>) -> Result<Account, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.create_account(&account).await
+                .map_err(|e| format!("Erreur lors de la création du compte: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn update_account(account: Account, state: State<'_, AppState>) -> Result<Account, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.update_account(&account).await
+                .map_err(|e| format!("Erreur lors de la mise à jour du compte: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn delete_account(id: String, state: State<'_, AppState>) -> Result<(), String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.delete_account(&id).await
+                .map_err(|e| format!("Erreur lors de la suppression du compte: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn get_account_balance_history(id: String, days: i32, state: State<'_, AppState>) -> Result<Vec<BalancePoint>, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.get_account_balance_history(&id, days).await
+                .map_err(|e| format!("Erreur lors de la récupération de l'historique du compte: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
@@ -1,5 +1,5 @@
 use tauri::{command, State};
-use crate::{AppState, models::{FinancialMetrics, BalancePoint}};
+use crate::{AppState, models::{FinancialMetrics, BalancePoint, CategorySpending, PayeeSpending}};
 use anyhow::Result;
 
 #[command]
@@ -27,3 +27,29 @@ pub async fn get_balance_history(days: i32, state: State<'_, AppState>) -> Resul
         None => Err("Application verrouillée".to_string())
     }
 }
+
+#[command]
+pub async fn get_spending_by_category(days: i32, depth: Option<usize>, state: State<'_, AppState>) -> Result<Vec<CategorySpending>, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.get_spending_by_category(days, depth).await
+                .map_err(|e| format!("Erreur lors du calcul des dépenses par catégorie: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn get_spending_by_payee(days: i32, state: State<'_, AppState>) -> Result<Vec<PayeeSpending>, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.get_spending_by_payee(days).await
+                .map_err(|e| format!("Erreur lors du calcul des dépenses par bénéficiaire: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
@@ -1,17 +1,22 @@
 use tauri::{command, State};
-use crate::{AppState, database::DatabaseManager};
+use crate::{AppState, database::DatabaseManager, models::UnlockResult};
 use anyhow::Result;
 
 #[command]
-pub async fn unlock_app(password: String, state: State<'_, AppState>) -> Result<bool, String> {
+pub async fn unlock_app(password: String, state: State<'_, AppState>) -> Result<UnlockResult, String> {
     let mut is_locked = state.is_locked.lock().unwrap();
     let mut db_guard = state.db.lock().unwrap();
     
     match DatabaseManager::new(&password).await {
         Ok(db_manager) => {
+            let mut result = UnlockResult { purged_count: 0, warnings: Vec::new() };
+            match db_manager.purge_trash().await {
+                Ok(purged_count) => result.purged_count = purged_count,
+                Err(e) => result.warnings.push(format!("Erreur lors du vidage de la corbeille: {}", e)),
+            }
             *db_guard = Some(db_manager);
             *is_locked = false;
-            Ok(true)
+            Ok(result)
         }
         Err(e) => {
             *is_locked = true;
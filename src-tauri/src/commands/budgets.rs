@@ -1,5 +1,5 @@
 use tauri::{command, State};
-use crate::{AppState, models::Budget};
+use crate::{AppState, models::{Budget, MergedBudget}};
 use anyhow::Result;
 
 #[command]
@@ -8,21 +8,47 @@ pub async fn get_budgets(state: State<'_, AppState>) -> Result<Vec<Budget>, Stri
     
     match db_guard.as_ref() {
         Some(db) => {
-            // Implementation for get_budgets
-            Ok(vec![])
+            db.get_budgets().await
+                .map_err(|e| format!("Erreur lors de la récupération des budgets: {}", e))
         }
         None => Err("Application verrouillée".to_string())
     }
 }
 
 #[command]
-pub async fn set_budget(budget: Budget, state: State<'_, AppState>) -> Result<(), String> {
+pub async fn set_budget(budget: Budget, state: State<'_, AppState>) -> Result<Budget, String> {
     let db_guard = state.db.lock().unwrap();
     
     match db_guard.as_ref() {
         Some(db) => {
-            // Implementation for set_budget
-            Ok(())
+            db.set_budget(&budget).await
+                .map_err(|e| format!("Erreur lors de l'enregistrement du budget: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn delete_budget(id: String, state: State<'_, AppState>) -> Result<(), String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.delete_budget(&id).await
+                .map_err(|e| format!("Erreur lors de la suppression du budget: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn get_merged_budgets(state: State<'_, AppState>) -> Result<Vec<MergedBudget>, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.get_merged_budgets().await
+                .map_err(|e| format!("Erreur lors de la récupération des budgets fusionnés: {}", e))
         }
         None => Err("Application verrouillée".to_string())
     }
@@ -0,0 +1,107 @@
+use tauri::{command, State};
+use crate::{AppState, models::{Category, CategorySuggestion}};
+use anyhow::Result;
+
+#[command]
+pub async fn get_categories(state: State<'_, AppState>) -> Result<Vec<Category>, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.get_categories().await
+                .map_err(|e| format!("Erreur lors de la récupération des catégories: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn create_category(category: Category, state: State<'_, AppState>) -> Result<Category, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.create_category(&category).await
+                .map_err(|e| format!("Erreur lors de la création de la catégorie: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn update_category(category: Category, state: State<'_, AppState>) -> Result<Category, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.update_category(&category).await
+                .map_err(|e| format!("Erreur lors de la mise à jour de la catégorie: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn merge_category(source_id: String, target_id: String, state: State<'_, AppState>) -> Result<Category, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.merge_category(&source_id, &target_id).await
+                .map_err(|e| format!("Erreur lors de la fusion des catégories: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn delete_category(id: String, state: State<'_, AppState>) -> Result<(), String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.delete_category(&id).await
+                .map_err(|e| format!("Erreur lors de la suppression de la catégorie: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn suggest_category(description: String, amount: f64, state: State<'_, AppState>) -> Result<Option<CategorySuggestion>, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.suggest_category(&description, amount).await
+                .map_err(|e| format!("Erreur lors de la suggestion de catégorie: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn get_category_suggestions(state: State<'_, AppState>) -> Result<Vec<CategorySuggestion>, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.get_category_suggestions().await
+                .map_err(|e| format!("Erreur lors de la suggestion de catégories: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn retrain_category_model(state: State<'_, AppState>) -> Result<usize, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.retrain_category_model().await
+                .map_err(|e| format!("Erreur lors de l'apprentissage des catégories: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
@@ -1,22 +1,541 @@
+use std::collections::{HashMap, HashSet};
 use tauri::{command, State};
-use crate::{AppState, models::ImportResult};
-use anyhow::Result;
+use crate::{AppState, database::{AccountDirectory, DatabaseManager}};
+use crate::models::{ColumnMapping, DuplicateLevel, DuplicateReview, FileFormat, ImportBatch, ImportCandidate, ImportPreview, ImportProfile, ImportResult, Transaction, TransactionSplit};
+use crate::parsers::{self, ParseOutput, ParsedTransaction};
+use crate::rules::renamed_payee;
+use crate::security::SecurityManager;
+use crate::utils::{decode_text, detect_file_format};
+use anyhow::{Result, anyhow};
+
+const MIN_DETECTION_CONFIDENCE: f32 = 0.5;
 
 #[command]
-pub async fn import_file(file_path: String, file_type: String, state: State<'_, AppState>) -> Result<ImportResult, String> {
+pub async fn import_file(
+    file_path: String,
+    file_type: String,
+    mapping: Option<ColumnMapping>,
+    profile_id: Option<String>,
+    encoding: Option<String>,
+    excluded_lines: Option<Vec<usize>>,
+    force: Option<bool>,
+    account: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<ImportResult, String> {
     let db_guard = state.db.lock().unwrap();
     
     match db_guard.as_ref() {
         Some(db) => {
-            // Implementation for file import with duplicate detection
-            Ok(ImportResult {
-                success: true,
-                imported_count: 0,
-                duplicate_count: 0,
-                error_count: 0,
-                errors: vec![],
-            })
+            let security = SecurityManager::new();
+            let bytes = std::fs::read(&file_path)
+                .map_err(|e| format!("Erreur lors de la lecture du fichier: {}", e))?;
+            let file_hash = security.create_file_hash(&bytes)
+                .map_err(|e| format!("Erreur lors de la lecture du fichier: {}", e))?;
+            
+            // The same file was already imported: warn instead of importing it twice
+            if !force.unwrap_or(false) {
+                let existing = db.find_import_batch_by_hash(&file_hash).await
+                    .map_err(|e| format!("Erreur lors de l'import: {}", e))?;
+                if let Some(batch) = existing {
+                    return Ok(already_imported(&batch));
+                }
+            }
+            
+            let profile = load_profile(db, profile_id.as_deref()).await?;
+            let mut parsed_file = parse_file(&bytes, &file_type, mapping, profile.as_ref(), encoding.as_deref())
+                .map_err(|e| format!("Erreur lors de la lecture du fichier: {}", e))?;
+            
+            // Rows the user deselected in the preview table
+            if let Some(excluded_lines) = excluded_lines {
+                parsed_file.output.transactions.retain(|t| !excluded_lines.contains(&t.line));
+            }
+            
+            let file_name = file_name(&file_path);
+            import_parsed(db, parsed_file, &file_name, &file_hash, account.as_deref()).await
+                .map_err(|e| format!("Erreur lors de l'import: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+// Runs the whole import pipeline without writing anything, so the import page
+// can show a review table before calling import_file. `account` is the account
+// picked on the import page, used for rows whose own account is not recognised.
+#[command]
+pub async fn preview_import(
+    file_path: String,
+    file_type: String,
+    mapping: Option<ColumnMapping>,
+    profile_id: Option<String>,
+    encoding: Option<String>,
+    account: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<ImportPreview, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            let bytes = std::fs::read(&file_path)
+                .map_err(|e| format!("Erreur lors de la lecture du fichier: {}", e))?;
+            let profile = load_profile(db, profile_id.as_deref()).await?;
+            let suggested_profile = if profile.is_none() && mapping.is_none() {
+                suggest_profile(db, &bytes).await
+                    .map_err(|e| format!("Erreur lors de la prévisualisation: {}", e))?
+            } else {
+                None
+            };
+            let parsed_file = parse_file(&bytes, &file_type, mapping, profile.as_ref(), encoding.as_deref())
+                .map_err(|e| format!("Erreur lors de la lecture du fichier: {}", e))?;
+            
+            let mut preview = preview_parsed(db, &bytes, parsed_file, account.as_deref()).await
+                .map_err(|e| format!("Erreur lors de la prévisualisation: {}", e))?;
+            preview.suggested_profile = suggested_profile;
+            Ok(preview)
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn get_import_batches(state: State<'_, AppState>) -> Result<Vec<ImportBatch>, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.get_import_batches().await
+                .map_err(|e| format!("Erreur lors de la récupération des imports: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn rollback_import_batch(batch_id: String, state: State<'_, AppState>) -> Result<u64, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.rollback_import_batch(&batch_id).await
+                .map_err(|e| format!("Erreur lors de l'annulation de l'import: {}", e))
         }
         None => Err("Application verrouillée".to_string())
     }
 }
+
+#[command]
+pub async fn get_duplicate_reviews(state: State<'_, AppState>) -> Result<Vec<DuplicateReview>, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.get_duplicate_reviews().await
+                .map_err(|e| format!("Erreur lors de la récupération des doublons: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+// Keeps the reviewed row as a new transaction, or discards it as a duplicate
+#[command]
+pub async fn resolve_duplicate_review(review_id: String, keep: bool, state: State<'_, AppState>) -> Result<(), String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.resolve_duplicate_review(&review_id, keep).await
+                .map_err(|e| format!("Erreur lors de la résolution du doublon: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+async fn load_profile(db: &DatabaseManager, profile_id: Option<&str>) -> Result<Option<ImportProfile>, String> {
+    match profile_id {
+        Some(profile_id) => db.get_import_profile(profile_id).await
+            .map(Some)
+            .map_err(|e| format!("Erreur lors du chargement du profil: {}", e)),
+        None => Ok(None),
+    }
+}
+
+// Suggests the saved profile whose header fingerprint matches the file. Each
+// profile is tried with its own delimiter and skipped rows.
+async fn suggest_profile(db: &DatabaseManager, bytes: &[u8]) -> Result<Option<ImportProfile>> {
+    let (content, _) = decode_text(bytes, None)?;
+    
+    let profiles = db.get_import_profiles().await?;
+    Ok(profiles.into_iter().find(|profile| {
+        profile.header_fingerprint.is_some()
+            && parsers::csv::header_fingerprint(&content, &profile.mapping) == profile.header_fingerprint
+    }))
+}
+
+struct ParsedFile {
+    format: FileFormat,
+    encoding: String,
+    output: ParseOutput,
+}
+
+fn parse_file(
+    bytes: &[u8],
+    file_type: &str,
+    mapping: Option<ColumnMapping>,
+    profile: Option<&ImportProfile>,
+    encoding: Option<&str>,
+) -> Result<ParsedFile> {
+    let detection = detect_file_format(bytes);
+    
+    let format = if file_type.eq_ignore_ascii_case("auto") {
+        if detection.format == FileFormat::Unknown || detection.confidence < MIN_DETECTION_CONFIDENCE {
+            return Err(anyhow!("Format de fichier non reconnu, veuillez le préciser"));
+        }
+        detection.format
+    } else {
+        FileFormat::from_name(file_type)
+            .ok_or_else(|| anyhow!("Format de fichier non supporté: {}", file_type))?
+    };
+    
+    // An explicit encoding wins over the profile's, which wins over detection
+    let encoding = encoding.or_else(|| profile.and_then(|p| p.encoding.as_deref()));
+    let (content, encoding) = decode_text(bytes, encoding)?;
+    let content = content.as_str();
+    
+    // QIF dates carry no day/month order: a mapping or profile settles it,
+    // otherwise the parser infers it from the file
+    let date_order = mapping.as_ref().or_else(|| profile.map(|p| &p.mapping)).map(|m| m.date_order);
+    
+    let mut parsed = match format {
+        FileFormat::Csv => {
+            // An explicit mapping wins over the profile; without either, the
+            // sniffed delimiter and header row are used
+            let mapping = mapping
+                .or_else(|| profile.map(|p| p.mapping.clone()))
+                .unwrap_or_else(|| ColumnMapping {
+                    delimiter: detection.delimiter.unwrap_or(','),
+                    has_header: detection.has_header,
+                    ..Default::default()
+                });
+            parsers::csv::parse(content, &mapping)
+        }
+        FileFormat::Ofx => parsers::ofx::parse(content),
+        FileFormat::Qif => parsers::qif::parse(content, date_order),
+        FileFormat::Mt940 => parsers::mt940::parse(content),
+        FileFormat::Camt053 => parsers::camt::parse(content),
+        FileFormat::Unknown => Err(anyhow!("Format de fichier non supporté: {}", file_type)),
+    }?;
+    
+    if let Some(account) = profile.and_then(|p| p.account.as_ref()) {
+        for transaction in &mut parsed.transactions {
+            transaction.account = account.clone();
+        }
+    }
+    
+    Ok(ParsedFile {
+        format,
+        encoding,
+        output: parsed,
+    })
+}
+
+fn file_name(file_path: &str) -> String {
+    std::path::Path::new(file_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| file_path.to_string())
+}
+
+fn already_imported_warning(batch: &ImportBatch) -> String {
+    format!(
+        "Ce fichier a déjà été importé le {} ({} transactions, lot {})",
+        batch.created_at, batch.imported_count, batch.id
+    )
+}
+
+fn already_imported(batch: &ImportBatch) -> ImportResult {
+    ImportResult {
+        success: false,
+        imported_count: 0,
+        duplicate_count: 0,
+        review_count: 0,
+        transfer_count: 0,
+        rule_count: 0,
+        error_count: 0,
+        errors: vec![],
+        warnings: vec![already_imported_warning(batch)],
+        batch_id: Some(batch.id.clone()),
+        encoding: None,
+        account_id: None,
+        ledger_balance: None,
+    }
+}
+
+// Resolves the account of each row: statements name theirs by IBAN or account
+// number, CSV files by name. Rows whose account is not recognised go to the
+// account picked on the import page, or are skipped and reported once per
+// unknown account rather than once per row.
+struct RowAccounts {
+    directory: AccountDirectory,
+    selected: Option<String>,
+    unknown: Vec<(String, usize)>,
+}
+
+impl RowAccounts {
+    fn new(directory: AccountDirectory, selected: Option<&str>) -> Result<Self> {
+        let selected = selected.map(|account| directory.resolve(account)).transpose()?;
+        Ok(RowAccounts {
+            directory,
+            selected,
+            unknown: Vec::new(),
+        })
+    }
+
+    fn resolve(&mut self, account: &str) -> Option<String> {
+        if let Ok(account) = self.directory.resolve(account) {
+            return Some(account);
+        }
+        if let Some(selected) = &self.selected {
+            return Some(selected.clone());
+        }
+        match self.unknown.iter_mut().find(|(unknown, _)| unknown == account) {
+            Some((_, count)) => *count += 1,
+            None => self.unknown.push((account.to_string(), 1)),
+        }
+        None
+    }
+
+    fn unknown_errors(&self) -> Vec<String> {
+        self.unknown
+            .iter()
+            .map(|(account, count)| format!(
+                "Compte inconnu: {} ({} ligne(s) ignorée(s)); renseignez son identifiant bancaire ou choisissez un compte",
+                account, count
+            ))
+            .collect()
+    }
+}
+
+fn to_transaction(parsed: ParsedTransaction, security: &SecurityManager) -> Transaction {
+    Transaction {
+        id: security.generate_secure_id(),
+        description: parsed.description,
+        amount: parsed.amount,
+        date: parsed.date,
+        category: parsed.category,
+        account: parsed.account,
+        external_id: parsed.external_id,
+        transfer_id: None,
+        // QIF split lines (S/E/$) become stored splits
+        splits: parsed.splits
+            .into_iter()
+            .map(|split| TransactionSplit {
+                id: String::new(),
+                amount: split.amount,
+                category: split.category,
+                memo: if split.memo.is_empty() { None } else { Some(split.memo) },
+            })
+            .collect(),
+        tags: Vec::new(),
+        rule_id: None,
+        payee_id: None,
+    }
+}
+
+async fn import_parsed(
+    db: &DatabaseManager,
+    parsed_file: ParsedFile,
+    file_name: &str,
+    file_hash: &str,
+    selected_account: Option<&str>,
+) -> Result<ImportResult> {
+    let batch_id = db.create_import_batch(file_name, file_hash, parsed_file.format).await?;
+    match import_rows(db, parsed_file, &batch_id, selected_account).await {
+        Ok(mut result) => {
+            // The batch is complete by now; a pairing failure leaves its rows
+            // unpaired rather than undoing the import
+            if result.imported_count > 0 {
+                match db.pair_transfers(&batch_id).await {
+                    Ok(transfer_count) => result.transfer_count = transfer_count,
+                    Err(e) => {
+                        result.errors.push(format!("Rapprochement des virements impossible: {}", e));
+                        result.error_count += 1;
+                        result.success = false;
+                    }
+                }
+            }
+            Ok(result)
+        }
+        Err(e) => {
+            // Nothing of a failed import is kept, so the file can be imported again
+            if let Err(cleanup) = db.abandon_import_batch(&batch_id).await {
+                return Err(anyhow!("{} (annulation de l'import impossible: {})", e, cleanup));
+            }
+            Err(e)
+        }
+    }
+}
+
+// Imports the rows into the batch and finishes it; the caller pairs transfers
+// and undoes the batch if this fails
+async fn import_rows(
+    db: &DatabaseManager,
+    parsed_file: ParsedFile,
+    batch_id: &str,
+    selected_account: Option<&str>,
+) -> Result<ImportResult> {
+    let security = SecurityManager::new();
+    let parsed = parsed_file.output;
+    let mut errors = parsed.errors;
+    let statement = parsed.statement.unwrap_or_default();
+    let mut imported_count = 0;
+    let mut duplicate_count = 0;
+    let mut review_count = 0;
+    let mut rule_count = 0;
+    let rules = db.rule_engine().await?;
+    let mut accounts = RowAccounts::new(db.account_directory().await?, selected_account)?;
+    let mut payees = db.get_payees().await?;
+    // Each stored row stands for one bank movement, so it absorbs one row at most
+    let mut matched = HashSet::new();
+    
+    for parsed_transaction in parsed.transactions {
+        let line = parsed_transaction.line;
+        let mut transaction = to_transaction(parsed_transaction, &security);
+        transaction.account = match accounts.resolve(&transaction.account) {
+            Some(account) => account,
+            None => continue,
+        };
+        // Rules run before duplicate detection: stored rows went through them too
+        let rule = rules.apply(&mut transaction);
+        
+        // Certain duplicates are counted rather than aborting the whole import;
+        // probable ones wait for the user to decide
+        let duplicate = db.detect_duplicate(&transaction, Some(batch_id), &matched).await?;
+        matched.extend(duplicate.transaction_id.clone());
+        // Rows that may still be stored, including those queued for review,
+        // get the payee their rule renames them to
+        if duplicate.level != DuplicateLevel::Certain {
+            if let Some(payee) = rule.and_then(renamed_payee) {
+                transaction.payee_id = Some(db.rule_payee(payee, &mut payees).await?);
+            }
+        }
+        match duplicate.level {
+            DuplicateLevel::Certain => {
+                duplicate_count += 1;
+                continue;
+            }
+            DuplicateLevel::Probable => {
+                match db.queue_duplicate_review(&transaction, batch_id, &duplicate).await {
+                    Ok(()) => review_count += 1,
+                    Err(e) => errors.push(format!("Ligne {}: {}", line, e)),
+                }
+                continue;
+            }
+            DuplicateLevel::NotDuplicate => {}
+        }
+        
+        match db.add_imported_transaction(&transaction, batch_id, &mut payees).await {
+            Ok(()) => {
+                imported_count += 1;
+                if transaction.rule_id.is_some() {
+                    rule_count += 1;
+                }
+            }
+            Err(e) => errors.push(format!("Ligne {}: {}", line, e)),
+        }
+    }
+    
+    errors.extend(accounts.unknown_errors());
+    db.finish_import_batch(batch_id, imported_count).await?;
+    
+    Ok(ImportResult {
+        success: errors.is_empty(),
+        imported_count,
+        duplicate_count,
+        review_count,
+        transfer_count: 0,
+        rule_count,
+        error_count: errors.len() as i32,
+        errors,
+        warnings: vec![],
+        batch_id: if imported_count > 0 || review_count > 0 { Some(batch_id.to_string()) } else { None },
+        encoding: Some(parsed_file.encoding),
+        account_id: statement.account_id,
+        ledger_balance: statement.ledger_balance,
+    })
+}
+
+async fn preview_parsed(
+    db: &DatabaseManager,
+    bytes: &[u8],
+    parsed_file: ParsedFile,
+    selected_account: Option<&str>,
+) -> Result<ImportPreview> {
+    let parsed = parsed_file.output;
+    let security = SecurityManager::new();
+    let mut warnings = Vec::new();
+    if let Some(batch) = db.find_import_batch_by_hash(&security.create_file_hash(bytes)?).await? {
+        warnings.push(already_imported_warning(&batch));
+    }
+    let statement = parsed.statement.unwrap_or_default();
+    let mut errors = parsed.errors;
+    let mut candidates = Vec::new();
+    let mut duplicates = Vec::new();
+    let mut probable_duplicates = Vec::new();
+    // An external ID repeated inside the file is rejected once the first copy is
+    // inserted; identical rows without one are genuine repeated purchases
+    let mut seen_in_file: HashMap<String, usize> = HashMap::new();
+    // As on import, a stored row absorbs one row of the file at most
+    let mut matched = HashSet::new();
+    let rules = db.rule_engine().await?;
+    let mut accounts = RowAccounts::new(db.account_directory().await?, selected_account)?;
+    
+    for parsed_transaction in parsed.transactions {
+        let line = parsed_transaction.line;
+        let mut transaction = to_transaction(parsed_transaction, &security);
+        transaction.account = match accounts.resolve(&transaction.account) {
+            Some(account) => account,
+            None => continue,
+        };
+        rules.apply(&mut transaction);
+        let duplicate = db.detect_duplicate(&transaction, None, &matched).await?;
+        matched.extend(duplicate.transaction_id.clone());
+        
+        let duplicate_of_line = match &transaction.external_id {
+            Some(external_id) => {
+                let key = format!("{}|{}", transaction.account, external_id);
+                let earlier = seen_in_file.get(&key).copied();
+                seen_in_file.entry(key).or_insert(line);
+                earlier
+            }
+            None => None,
+        };
+        
+        let level = duplicate.level;
+        let candidate = ImportCandidate {
+            line,
+            transaction,
+            duplicate,
+            duplicate_of_line,
+        };
+        if level == DuplicateLevel::Certain || candidate.duplicate_of_line.is_some() {
+            duplicates.push(candidate);
+        } else if level == DuplicateLevel::Probable {
+            probable_duplicates.push(candidate);
+        } else {
+            candidates.push(candidate);
+        }
+    }
+    errors.extend(accounts.unknown_errors());
+    
+    Ok(ImportPreview {
+        format: parsed_file.format,
+        encoding: parsed_file.encoding,
+        candidates,
+        duplicates,
+        probable_duplicates,
+        errors,
+        warnings,
+        suggested_profile: None,
+        account_id: statement.account_id,
+        ledger_balance: statement.ledger_balance,
+    })
+}
@@ -3,3 +3,8 @@ pub mod transactions;
 pub mod budgets;
 pub mod analytics;
 pub mod import;
+pub mod profiles;
+pub mod accounts;
+pub mod categories;
+pub mod rules;
+pub mod payees;
@@ -0,0 +1,55 @@
+use tauri::{command, State};
+use crate::{AppState, models::Payee};
+use anyhow::Result;
+
+#[command]
+pub async fn get_payees(state: State<'_, AppState>) -> Result<Vec<Payee>, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.get_payees().await
+                .map_err(|e| format!("Erreur lors de la récupération des bénéficiaires: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn update_payee(payee: Payee, state: State<'_, AppState>) -> Result<Payee, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.update_payee(&payee).await
+                .map_err(|e| format!("Erreur lors de la mise à jour du bénéficiaire: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn merge_payees(source_id: String, target_id: String, state: State<'_, AppState>) -> Result<Payee, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.merge_payees(&source_id, &target_id).await
+                .map_err(|e| format!("Erreur lors de la fusion des bénéficiaires: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn delete_payee(id: String, state: State<'_, AppState>) -> Result<(), String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.delete_payee(&id).await
+                .map_err(|e| format!("Erreur lors de la suppression du bénéficiaire: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
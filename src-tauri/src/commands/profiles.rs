@@ -0,0 +1,64 @@
+use tauri::{command, State};
+use crate::{AppState, models::ImportProfile};
+use crate::parsers;
+use crate::security::SecurityManager;
+use crate::utils::decode_text;
+use anyhow::Result;
+
+#[command]
+pub async fn get_import_profiles(state: State<'_, AppState>) -> Result<Vec<ImportProfile>, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.get_import_profiles().await
+                .map_err(|e| format!("Erreur lors de la récupération des profils: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+// Saves a profile; when a sample file is given, its header row becomes the
+// fingerprint used to suggest the profile for later files
+#[command]
+pub async fn save_import_profile(
+    mut profile: ImportProfile,
+    sample_file_path: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<ImportProfile, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            if profile.id.is_empty() {
+                profile.id = SecurityManager::new().generate_secure_id();
+            }
+            
+            if let Some(sample_file_path) = sample_file_path {
+                let bytes = std::fs::read(&sample_file_path)
+                    .map_err(|e| format!("Erreur lors de la lecture du fichier: {}", e))?;
+                let (content, _) = decode_text(&bytes, profile.encoding.as_deref())
+                    .map_err(|e| format!("Erreur lors de la lecture du fichier: {}", e))?;
+                profile.header_fingerprint = parsers::csv::header_fingerprint(&content, &profile.mapping);
+            }
+            
+            db.save_import_profile(&profile).await
+                .map_err(|e| format!("Erreur lors de l'enregistrement du profil: {}", e))?;
+            Ok(profile)
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn delete_import_profile(id: String, state: State<'_, AppState>) -> Result<(), String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.delete_import_profile(&id).await
+                .map_err(|e| format!("Erreur lors de la suppression du profil: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
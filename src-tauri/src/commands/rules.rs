@@ -0,0 +1,56 @@
+use tauri::{command, State};
+use crate::{AppState, models::{Rule, RuleApplication}};
+use anyhow::Result;
+
+#[command]
+pub async fn get_rules(state: State<'_, AppState>) -> Result<Vec<Rule>, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.get_rules().await
+                .map_err(|e| format!("Erreur lors de la récupération des règles: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn save_rule(rule: Rule, state: State<'_, AppState>) -> Result<Rule, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.save_rule(&rule).await
+                .map_err(|e| format!("Erreur lors de l'enregistrement de la règle: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn delete_rule(id: String, state: State<'_, AppState>) -> Result<(), String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.delete_rule(&id).await
+                .map_err(|e| format!("Erreur lors de la suppression de la règle: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+// Without transaction IDs every stored transaction is processed
+#[command]
+pub async fn apply_rules(transaction_ids: Option<Vec<String>>, state: State<'_, AppState>) -> Result<Vec<RuleApplication>, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.apply_rules(transaction_ids).await
+                .map_err(|e| format!("Erreur lors de l'application des règles: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
@@ -1,20 +1,33 @@
 use tauri::{command, State};
-use crate::{AppState, models::Transaction};
+use crate::{AppState, models::{SearchResult, Transaction, TransactionPage, TransactionQuery, TransferRequest, TrashedTransaction}};
 use anyhow::Result;
 
 #[command]
-pub async fn get_transactions(limit: Option<i32>, state: State<'_, AppState>) -> Result<Vec<Transaction>, String> {
+pub async fn get_transactions(query: Option<TransactionQuery>, state: State<'_, AppState>) -> Result<TransactionPage, String> {
     let db_guard = state.db.lock().unwrap();
     
     match db_guard.as_ref() {
         Some(db) => {
-            db.get_transactions(limit).await
+            db.get_transactions(&query.unwrap_or_default()).await
                 .map_err(|e| format!("Erreur lors de la récupération des transactions: {}", e))
         }
         None => Err("Application verrouillée".to_string())
     }
 }
 
+#[command]
+pub async fn search_transactions(query: String, limit: Option<i32>, state: State<'_, AppState>) -> Result<Vec<SearchResult>, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.search_transactions(&query, limit).await
+                .map_err(|e| format!("Erreur lors de la recherche: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
 #[command]
 pub async fn add_transaction(transaction: Transaction, state: State<'_, AppState>) -> Result<(), String> {
     let db_guard = state.db.lock().unwrap();
@@ -34,8 +47,8 @@ pub async fn update_transaction(transaction: Transaction, state: State<'_, AppSt
     
     match db_guard.as_ref() {
         Some(db) => {
-            // Implementation for update_transaction
-            Ok(())
+            db.update_transaction(&transaction).await
+                .map_err(|e| format!("Erreur lors de la mise à jour de la transaction: {}", e))
         }
         None => Err("Application verrouillée".to_string())
     }
@@ -47,8 +60,112 @@ pub async fn delete_transaction(id: String, state: State<'_, AppState>) -> Resul
     
     match db_guard.as_ref() {
         Some(db) => {
-            // Implementation for delete_transaction
-            Ok(())
+            db.delete_transaction(&id).await
+                .map_err(|e| format!("Erreur lors de la suppression de la transaction: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn get_trash(state: State<'_, AppState>) -> Result<Vec<TrashedTransaction>, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.get_trash().await
+                .map_err(|e| format!("Erreur lors de la récupération de la corbeille: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn restore_transaction(id: String, state: State<'_, AppState>) -> Result<(), String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.restore_transaction(&id).await
+                .map_err(|e| format!("Erreur lors de la restauration de la transaction: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn purge_trash(state: State<'_, AppState>) -> Result<u64, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.purge_trash().await
+                .map_err(|e| format!("Erreur lors du vidage de la corbeille: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn get_trash_retention(state: State<'_, AppState>) -> Result<u32, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.get_trash_retention().await
+                .map_err(|e| format!("Erreur lors de la lecture des paramètres: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn set_trash_retention(days: u32, state: State<'_, AppState>) -> Result<(), String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.set_trash_retention(days).await
+                .map_err(|e| format!("Erreur lors de l'enregistrement des paramètres: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn create_transfer(transfer: TransferRequest, state: State<'_, AppState>) -> Result<String, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.create_transfer(&transfer).await
+                .map_err(|e| format!("Erreur lors de la création du virement: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn link_transfer(first_id: String, second_id: String, state: State<'_, AppState>) -> Result<String, String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.link_transfer(&first_id, &second_id).await
+                .map_err(|e| format!("Erreur lors du rapprochement du virement: {}", e))
+        }
+        None => Err("Application verrouillée".to_string())
+    }
+}
+
+#[command]
+pub async fn unlink_transfer(transfer_id: String, state: State<'_, AppState>) -> Result<(), String> {
+    let db_guard = state.db.lock().unwrap();
+    
+    match db_guard.as_ref() {
+        Some(db) => {
+            db.unlink_transfer(&transfer_id).await
+                .map_err(|e| format!("Erreur lors de la dissociation du virement: {}", e))
         }
         None => Err("Application verrouillée".to_string())
     }
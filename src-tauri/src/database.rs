@@ -1,15 +1,41 @@
-use sqlx::{SqlitePool, Row, sqlite::SqliteConnectOptions};
+use sqlx::{QueryBuilder, Sqlite, SqliteConnection, SqlitePool, Row, sqlite::SqliteConnectOptions};
 use chrono::{DateTime, Utc};
 use uuid::Uuid;
 use anyhow::{Result, anyhow};
 use crate::models::*;
+use crate::classifier::{self, CategoryModel};
+use crate::parsers::{CATEGORY_PATH_SEPARATOR, DEFAULT_CATEGORY, TRANSFER_CATEGORY};
 use crate::security::SecurityManager;
+use crate::duplicates;
+use crate::payees;
+use crate::rules::{self, RuleEngine};
+use crate::search;
+use crate::transfers;
+use crate::utils::{budget_period, category_rollup, is_in_category, normalize_bank_identifier, parse_date, rebase_category, BUDGET_PERIODS};
 use std::str::FromStr;
+use serde::{Serialize, de::DeserializeOwned};
+use std::collections::{HashMap, HashSet};
+use base64::{Engine as _, engine::general_purpose};
+
+const IMPORT_PROFILE_PREFIX: &str = "import_profile:";
+const TRASH_RETENTION_KEY: &str = "trash_retention_days";
+const CATEGORY_MODEL_KEY: &str = "category_model";
+const MERGED_BUDGETS_KEY: &str = "merged_budgets";
+const SEARCH_INDEX_VERSION_KEY: &str = "search_index_version";
+const INDEX_KEY_LABEL: &str = "blind-index";
+const INDEX_KEY_VERSION_KEY: &str = "index_key_version";
+const INDEX_KEY_VERSION: u32 = 1; // 1: derived index key instead of the encryption key
+const SEARCH_INDEX_VERSION: u32 = 2; // Raised when what gets indexed changes; 2 added split memos
+const DEFAULT_TRASH_RETENTION_DAYS: u32 = 30;
+const CATEGORY_INDEX_DOMAIN: &str = "category";
+const SEARCH_INDEX_DOMAIN: &str = "search";
+const MAX_PAGE_SIZE: i32 = 1000;
 
 pub struct DatabaseManager {
     pool: SqlitePool,
     security: SecurityManager,
     encryption_key: [u8; 32],
+    index_key: [u8; 32], // Keys the blind indexes, derived from encryption_key
 }
 
 impl DatabaseManager {
@@ -31,14 +57,27 @@ impl DatabaseManager {
         sqlx::query("SELECT 1").fetch_one(&pool).await
             .map_err(|_| anyhow!("Mot de passe incorrect ou base de données corrompue"))?;
         
+        Self::open(pool, security, encryption_key).await
+    }
+
+    async fn open(pool: SqlitePool, security: SecurityManager, encryption_key: [u8; 32]) -> Result<Self> {
         // Initialize database schema
         Self::initialize_schema(&pool).await?;
         
-        Ok(DatabaseManager { 
+        let index_key = security.derive_subkey(&encryption_key, INDEX_KEY_LABEL)?;
+        let db = DatabaseManager { 
             pool, 
             security,
             encryption_key,
-        })
+            index_key,
+        };
+        db.rebuild_blind_indexes().await?;
+        db.backfill_category_index().await?;
+        db.enforce_unique_budgets().await?;
+        db.backfill_search_index().await?;
+        db.backfill_payees().await?;
+        
+        Ok(db)
     }
 
     async fn initialize_schema(pool: &SqlitePool) -> Result<()> {
@@ -60,7 +99,7 @@ impl DatabaseManager {
                 id TEXT PRIMARY KEY,
                 category_encrypted TEXT NOT NULL,
                 amount REAL NOT NULL,
-                spent REAL DEFAULT 0.0,
+                spent REAL DEFAULT 0.0, -- Unused: spending is computed from transactions
                 period TEXT NOT NULL DEFAULT 'monthly',
                 created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
                 updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
@@ -87,249 +126,2949 @@ impl DatabaseManager {
             )
         "#).execute(pool).await?;
 
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS import_batches (
+                id TEXT PRIMARY KEY,
+                file_name_encrypted TEXT NOT NULL,
+                file_hash TEXT NOT NULL,
+                format TEXT NOT NULL,
+                imported_count INTEGER NOT NULL DEFAULT 0,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )
+        "#).execute(pool).await?;
+
+        // Application settings, stored as encrypted JSON documents
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS settings (
+                key TEXT PRIMARY KEY,
+                value_encrypted TEXT NOT NULL,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )
+        "#).execute(pool).await?;
+
+        // Imported rows that look like, but are not certainly, a stored transaction
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS duplicate_reviews (
+                id TEXT PRIMARY KEY,
+                batch_id TEXT REFERENCES import_batches(id),
+                transaction_encrypted TEXT NOT NULL,
+                existing_id TEXT NOT NULL,
+                score REAL NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )
+        "#).execute(pool).await?;
+
+        // Parts of a transaction spread over several categories, encrypted like the parent
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS transaction_splits (
+                id TEXT PRIMARY KEY,
+                transaction_id TEXT NOT NULL,
+                position INTEGER NOT NULL,
+                amount REAL NOT NULL,
+                category_encrypted TEXT NOT NULL,
+                category_index TEXT NOT NULL,
+                memo_encrypted TEXT
+            )
+        "#).execute(pool).await?;
+
+        // Blind index of description words and prefixes for search_transactions
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS search_tokens (
+                transaction_id TEXT NOT NULL,
+                token_hash TEXT NOT NULL,
+                exact INTEGER NOT NULL DEFAULT 0, -- Whole word rather than prefix
+                PRIMARY KEY (transaction_id, token_hash)
+            )
+        "#).execute(pool).await?;
+
+        // Conditions and actions are stored as one encrypted JSON document since
+        // they name merchants and accounts
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS rules (
+                id TEXT PRIMARY KEY,
+                priority INTEGER NOT NULL DEFAULT 0,
+                rule_encrypted TEXT NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )
+        "#).execute(pool).await?;
+
+        // Merchants behind the bank descriptions; aliases are the normalised
+        // descriptions that map to each payee
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS payees (
+                id TEXT PRIMARY KEY,
+                name_encrypted TEXT NOT NULL,
+                aliases_encrypted TEXT NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )
+        "#).execute(pool).await?;
+
+        // Columns added after the first release are appended to existing databases
+        Self::add_column_if_missing(pool, "transactions", "external_id", "TEXT").await?;
+        Self::add_column_if_missing(pool, "transactions", "batch_id", "TEXT REFERENCES import_batches(id)").await?;
+        Self::add_column_if_missing(pool, "transactions", "deleted_at", "DATETIME").await?;
+        Self::add_column_if_missing(pool, "transactions", "category_index", "TEXT").await?;
+        Self::add_column_if_missing(pool, "transactions", "transfer_id", "TEXT").await?;
+        Self::add_column_if_missing(pool, "transactions", "tags_encrypted", "TEXT").await?;
+        Self::add_column_if_missing(pool, "transactions", "rule_id", "TEXT").await?;
+        Self::add_column_if_missing(pool, "transactions", "payee_id", "TEXT").await?;
+        Self::add_column_if_missing(pool, "transactions", "previous_category_encrypted", "TEXT").await?;
+        Self::add_column_if_missing(pool, "accounts", "opening_balance", "REAL DEFAULT 0.0").await?;
+        Self::add_column_if_missing(pool, "accounts", "opening_date", "TEXT").await?;
+        Self::add_column_if_missing(pool, "accounts", "bank_identifier_encrypted", "TEXT").await?;
+        Self::add_column_if_missing(pool, "categories", "parent_id", "TEXT").await?;
+        Self::add_column_if_missing(pool, "budgets", "category_index", "TEXT").await?;
+        Self::add_column_if_missing(pool, "budgets", "start_day", "INTEGER NOT NULL DEFAULT 1").await?;
+
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_transactions_date ON transactions(date)").execute(pool).await?;
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_transactions_hash ON transactions(hash)").execute(pool).await?;
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_transactions_amount ON transactions(amount)").execute(pool).await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_transactions_external_id ON transactions(account, external_id)").execute(pool).await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_transactions_batch ON transactions(batch_id)").execute(pool).await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_transactions_deleted ON transactions(deleted_at)").execute(pool).await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_transactions_category_index ON transactions(category_index)").execute(pool).await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_transactions_transfer ON transactions(transfer_id)").execute(pool).await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_transactions_account ON transactions(account, date)").execute(pool).await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_transactions_payee ON transactions(payee_id)").execute(pool).await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_import_batches_hash ON import_batches(file_hash)").execute(pool).await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_transaction_splits_parent ON transaction_splits(transaction_id)").execute(pool).await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_transaction_splits_category ON transaction_splits(category_index)").execute(pool).await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_search_tokens_hash ON search_tokens(token_hash)").execute(pool).await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_duplicate_reviews_batch ON duplicate_reviews(batch_id)").execute(pool).await?;
 
         Ok(())
     }
 
-    pub async fn add_transaction(&self, transaction: &Transaction) -> Result<()> {
-        let encrypted_description = self.security.encrypt(&transaction.description, &self.encryption_key)?;
-        let encrypted_category = self.security.encrypt(&transaction.category, &self.encryption_key)?;
-        
-        // Create hash for duplicate detection
-        let hash_input = format!("{}{}{}{}", 
-            transaction.description, transaction.amount, transaction.date, transaction.account);
-        let hash = self.security.create_hash(&hash_input)?;
+    async fn add_column_if_missing(pool: &SqlitePool, table: &str, column: &str, definition: &str) -> Result<()> {
+        let columns = sqlx::query(&format!("PRAGMA table_info({})", table))
+            .fetch_all(pool).await?;
         
-        // Check for duplicates
-        let existing = sqlx::query!(
-            "SELECT COUNT(*) as count FROM transactions WHERE hash = ?",
-            hash
-        ).fetch_one(&self.pool).await?;
+        let exists = columns.iter().any(|row| row.get::<String, _>("name") == column);
+        if !exists {
+            sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+                .execute(pool).await?;
+        }
         
-        if existing.count > 0 {
-            return Err(anyhow!("Transaction en double détectée"));
+        Ok(())
+    }
+
+    // Blind indexes were once keyed with the encryption key itself; they are
+    // recomputed with the index key once. Search tokens are dropped and
+    // rebuilt by backfill_search_index.
+    async fn rebuild_blind_indexes(&self) -> Result<()> {
+        if self.get_setting::<u32>(INDEX_KEY_VERSION_KEY).await? == Some(INDEX_KEY_VERSION) {
+            return Ok(());
         }
         
-        sqlx::query!(
-            "INSERT INTO transactions (id, description_encrypted, amount, date, category_encrypted, account, hash) 
-             VALUES (?, ?, ?, ?, ?, ?, ?)",
-            transaction.id,
-            encrypted_description,
-            transaction.amount,
-            transaction.date,
-            encrypted_category,
-            transaction.account,
-            hash
-        ).execute(&self.pool).await?;
+        let mut tx = self.pool.begin().await?;
+        let transactions = sqlx::query!("SELECT id, category_encrypted FROM transactions")
+            .fetch_all(&mut *tx).await?;
+        for row in transactions {
+            let category = self.security.decrypt(&row.category_encrypted, &self.encryption_key)?;
+            let category_index = self.category_index(&category)?;
+            sqlx::query!("UPDATE transactions SET category_index = ? WHERE id = ?", category_index, row.id)
+                .execute(&mut *tx).await?;
+        }
+        let splits = sqlx::query!("SELECT id, category_encrypted FROM transaction_splits")
+            .fetch_all(&mut *tx).await?;
+        for row in splits {
+            let category = self.security.decrypt(&row.category_encrypted, &self.encryption_key)?;
+            let category_index = self.category_index(&category)?;
+            sqlx::query!("UPDATE transaction_splits SET category_index = ? WHERE id = ?", category_index, row.id)
+                .execute(&mut *tx).await?;
+        }
+        let budgets = sqlx::query!("SELECT id, category_encrypted FROM budgets")
+            .fetch_all(&mut *tx).await?;
+        for row in budgets {
+            let category = self.security.decrypt(&row.category_encrypted, &self.encryption_key)?;
+            let category_index = self.category_index(&category)?;
+            sqlx::query!("UPDATE budgets SET category_index = ? WHERE id = ?", category_index, row.id)
+                .execute(&mut *tx).await?;
+        }
+        sqlx::query!("DELETE FROM search_tokens").execute(&mut *tx).await?;
+        self.set_setting_with(&mut tx, INDEX_KEY_VERSION_KEY, &INDEX_KEY_VERSION).await?;
         
+        tx.commit().await?;
         Ok(())
     }
 
-    pub async fn get_transactions(&self, limit: Option<i32>) -> Result<Vec<Transaction>> {
-        let limit = limit.unwrap_or(100);
-        
+    // Rows written before category_index existed get their blind index here,
+    // since computing it needs the key that initialize_schema doesn't have
+    async fn backfill_category_index(&self) -> Result<()> {
         let rows = sqlx::query!(
-            "SELECT id, description_encrypted, amount, date, category_encrypted, account 
-             FROM transactions ORDER BY date DESC LIMIT ?",
-            limit
+            "SELECT id, category_encrypted FROM transactions WHERE category_index IS NULL"
         ).fetch_all(&self.pool).await?;
-
-        let mut transactions = Vec::new();
+        
         for row in rows {
-            let description = self.security.decrypt(&row.description_encrypted, &self.encryption_key)?;
             let category = self.security.decrypt(&row.category_encrypted, &self.encryption_key)?;
-            
-            transactions.push(Transaction {
-                id: row.id,
-                description,
-                amount: row.amount,
-                date: row.date,
-                category,
-                account: row.account,
-            });
+            let category_index = self.category_index(&category)?;
+            sqlx::query!(
+                "UPDATE transactions SET category_index = ? WHERE id = ?",
+                category_index,
+                row.id
+            ).execute(&self.pool).await?;
         }
         
-        Ok(transactions)
+        Ok(())
     }
 
-    pub async fn get_balance_history(&self, days: i32) -> Result<Vec<BalancePoint>> {
+    // Descriptions that normalise to nothing keep a NULL payee and are looked
+    // at again on the next unlock
+    async fn backfill_payees(&self) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
         let rows = sqlx::query!(
-            "SELECT date, amount FROM transactions 
-             WHERE date >= date('now', '-{} days') 
-             ORDER BY date ASC",
-            days
-        ).fetch_all(&self.pool).await?;
+            "SELECT id, description_encrypted FROM transactions WHERE payee_id IS NULL"
+        ).fetch_all(&mut *tx).await?;
+        let mut known = self.load_payees(&mut tx).await?;
+        
+        for row in rows {
+            let description = self.security.decrypt(&row.description_encrypted, &self.encryption_key)?;
+            if let Some(payee_id) = self.assign_payee_from(&mut tx, &description, &mut known).await? {
+                sqlx::query!(
+                    "UPDATE transactions SET payee_id = ? WHERE id = ?",
+                    payee_id,
+                    row.id
+                ).execute(&mut *tx).await?;
+            }
+        }
+        
+        tx.commit().await?;
+        Ok(())
+    }
 
-        let mut balance = 0.0;
-        let mut history = Vec::new();
-        let mut current_date = String::new();
-        let mut daily_total = 0.0;
+    // One budget per category and period, enforced by a unique index. Runs
+    // once, before the index exists: the category index is backfilled, and
+    // where an older database holds several budgets for the same pair the
+    // largest amount is kept. The budgets merged away are kept in the
+    // settings so the user can see what changed.
+    async fn enforce_unique_budgets(&self) -> Result<()> {
+        let indexed = sqlx::query!(
+            "SELECT name FROM sqlite_master WHERE type = 'index' AND name = 'idx_budgets_category_period'"
+        ).fetch_optional(&self.pool).await?;
+        if indexed.is_some() {
+            return Ok(());
+        }
         
+        let mut tx = self.pool.begin().await?;
+        let rows = sqlx::query!(
+            "SELECT id, category_encrypted FROM budgets WHERE category_index IS NULL"
+        ).fetch_all(&mut *tx).await?;
         for row in rows {
-            if row.date != current_date {
-                if !current_date.is_empty() {
-                    balance += daily_total;
-                    history.push(BalancePoint {
-                        date: current_date.clone(),
-                        balance,
+            let category = self.security.decrypt(&row.category_encrypted, &self.encryption_key)?;
+            let category_index = self.category_index(&category)?;
+            sqlx::query!(
+                "UPDATE budgets SET category_index = ? WHERE id = ?",
+                category_index,
+                row.id
+            ).execute(&mut *tx).await?;
+        }
+        
+        let budgets = sqlx::query!(
+            "SELECT id, category_encrypted, category_index, period, amount, start_day FROM budgets 
+             ORDER BY category_index, period, amount DESC, updated_at DESC"
+        ).fetch_all(&mut *tx).await?;
+        let mut merged: Vec<MergedBudget> = self.get_setting_with(&mut tx, MERGED_BUDGETS_KEY).await?.unwrap_or_default();
+        let merged_count = merged.len();
+        let mut kept: Option<(Option<String>, String, String)> = None;
+        for budget in budgets {
+            match &kept {
+                Some((category_index, period, kept_id))
+                    if *category_index == budget.category_index && *period == budget.period =>
+                {
+                    merged.push(MergedBudget {
+                        budget: Budget {
+                            id: budget.id.clone(),
+                            category: self.security.decrypt(&budget.category_encrypted, &self.encryption_key)?,
+                            amount: budget.amount,
+                            spent: 0.0,
+                            period: budget.period,
+                            start_day: budget.start_day as u32,
+                            period_start: None,
+                            period_end: None,
+                        },
+                        merged_into: kept_id.clone(),
+                        merged_at: Utc::now().to_rfc3339(),
                     });
+                    sqlx::query!("DELETE FROM budgets WHERE id = ?", budget.id)
+                        .execute(&mut *tx).await?;
                 }
-                current_date = row.date;
-                daily_total = 0.0;
+                _ => kept = Some((budget.category_index, budget.period, budget.id)),
             }
-            daily_total += row.amount;
         }
-        
-        // Add the last day
-        if !current_date.is_empty() {
-            balance += daily_total;
-            history.push(BalancePoint {
-                date: current_date,
-                balance,
-            });
+        if merged.len() > merged_count {
+            self.set_setting_with(&mut tx, MERGED_BUDGETS_KEY, &merged).await?;
         }
         
-        Ok(history)
+        sqlx::query("CREATE UNIQUE INDEX idx_budgets_category_period ON budgets(category_index, period)")
+            .execute(&mut *tx).await?;
+        tx.commit().await?;
+        
+        Ok(())
     }
 
-    pub async fn get_financial_metrics(&self) -> Result<FinancialMetrics> {
-        // Calculate burn rate (average daily expenses over last 30 days)
-        let burn_rate_row = sqlx::query!(
-            "SELECT AVG(daily_expense) as burn_rate FROM (
-                SELECT DATE(date) as day, SUM(ABS(amount)) as daily_expense 
-                FROM transactions 
-                WHERE amount < 0 AND date >= date('now', '-30 days')
-                GROUP BY DATE(date)
-            )"
-        )
-        .fetch_one(&self.pool)
-        .await?;
-
-        let burn_rate = burn_rate_row.burn_rate.unwrap_or(0.0);
-
-        // Calculate current balance
-        let balance_row = sqlx::query!(
-            "SELECT SUM(amount) as balance FROM transactions"
-        )
-        .fetch_one(&self.pool)
-        .await?;
-
-        let balance = balance_row.balance.unwrap_or(0.0);
-
-        // Calculate runway (days until balance reaches zero)
-        let runway = if burn_rate > 0.0 { balance / burn_rate } else { 999.0 };
-
-        // Calculate ITT (Income Tension Index)
-        let income_row = sqlx::query!(
-            "SELECT SUM(amount) as income FROM transactions 
-             WHERE amount > 0 AND date >= date('now', '-30 days')"
-        )
-        .fetch_one(&self.pool)
-        .await?;
-
-        let expense_row = sqlx::query!(
-            "SELECT SUM(ABS(amount)) as expenses FROM transactions 
-             WHERE amount < 0 AND date >= date('now', '-30 days')"
-        )
-        .fetch_one(&self.pool)
-        .await?;
-
-        let income = income_row.income.unwrap_or(0.0);
-        let expenses = expense_row.expenses.unwrap_or(0.0);
-        let itt = if expenses > 0.0 { income / expenses } else { 999.0 };
-
-        // Calculate volatility (standard deviation of daily balances)
-        let volatility = self.calculate_volatility().await?;
-
-        // Calculate max drawdown
-        let drawdown = self.calculate_max_drawdown().await?;
-
-        Ok(FinancialMetrics {
-            balance,
-            burn_rate,
-            runway,
-            itt,
-            volatility,
-            drawdown,
-        })
+    // Budgets removed when duplicates were merged, see enforce_unique_budgets
+    pub async fn get_merged_budgets(&self) -> Result<Vec<MergedBudget>> {
+        Ok(self.get_setting(MERGED_BUDGETS_KEY).await?.unwrap_or_default())
     }
 
-    async fn calculate_volatility(&self) -> Result<f64> {
-        let balances = self.get_balance_history(30).await?;
-        if balances.len() < 2 {
-            return Ok(0.0);
+    // Indexes the transactions written before search existed, and rebuilds
+    // the whole index when what gets indexed changes
+    async fn backfill_search_index(&self) -> Result<()> {
+        let current = self.get_setting::<u32>(SEARCH_INDEX_VERSION_KEY).await? == Some(SEARCH_INDEX_VERSION);
+        let rows = if current {
+            sqlx::query!(
+                "SELECT id, description_encrypted FROM transactions 
+                 WHERE id NOT IN (SELECT DISTINCT transaction_id FROM search_tokens)"
+            ).fetch_all(&self.pool).await?
+                .into_iter()
+                .map(|row| (row.id, row.description_encrypted))
+                .collect::<Vec<_>>()
+        } else {
+            sqlx::query!("SELECT id, description_encrypted FROM transactions")
+                .fetch_all(&self.pool).await?
+                .into_iter()
+                .map(|row| (row.id, row.description_encrypted))
+                .collect()
+        };
+        let ids: Vec<String> = rows.iter().map(|(id, _)| id.clone()).collect();
+        let mut splits = self.load_splits(&ids).await?;
+        
+        let mut tx = self.pool.begin().await?;
+        for (id, description_encrypted) in rows {
+            let description = self.security.decrypt(&description_encrypted, &self.encryption_key)?;
+            let splits = splits.remove(&id).unwrap_or_default();
+            self.index_transaction(&mut tx, &id, &description, &splits).await?;
         }
-
-        let mean = balances.iter().map(|b| b.balance).sum::<f64>() / balances.len() as f64;
-        let variance = balances.iter()
-            .map(|b| (b.balance - mean).powi(2))
-            .sum::<f64>() / balances.len() as f64;
+        if !current {
+            self.set_setting_with(&mut tx, SEARCH_INDEX_VERSION_KEY, &SEARCH_INDEX_VERSION).await?;
+        }
+        tx.commit().await?;
         
-        Ok(variance.sqrt())
+        Ok(())
     }
 
-    async fn calculate_max_drawdown(&self) -> Result<f64> {
-        let balances = self.get_balance_history(90).await?;
-        if balances.is_empty() {
-            return Ok(0.0);
+    // Replaces the search tokens of a transaction; called whenever its
+    // description or splits are written. Split memos are indexed under the
+    // parent, so a search finds the transaction by either.
+    async fn index_transaction(&self, conn: &mut SqliteConnection, transaction_id: &str, description: &str, splits: &[TransactionSplit]) -> Result<()> {
+        sqlx::query!("DELETE FROM search_tokens WHERE transaction_id = ?", transaction_id)
+            .execute(&mut *conn).await?;
+        
+        // One token per term, so a query word matches a transaction once
+        let mut terms = search::index_terms(description);
+        for memo in splits.iter().filter_map(|split| split.memo.as_deref()) {
+            for (term, exact) in search::index_terms(memo) {
+                *terms.entry(term).or_insert(false) |= exact;
+            }
+        }
+        for (term, exact) in terms {
+            let token_hash = self.security.blind_index(SEARCH_INDEX_DOMAIN, &term, &self.index_key)?;
+            sqlx::query!(
+                "INSERT INTO search_tokens (transaction_id, token_hash, exact) VALUES (?, ?, ?)",
+                transaction_id,
+                token_hash,
+                exact
+            ).execute(&mut *conn).await?;
         }
+        
+        Ok(())
+    }
 
-        let mut max_balance = balances[0].balance;
-        let mut max_drawdown = 0.0;
+    // Categories are encrypted with a random nonce, so filtering on them goes
+    // through this keyed hash of the trimmed, lowercased name
+    fn category_index(&self, category: &str) -> Result<String> {
+        self.security.blind_index(CATEGORY_INDEX_DOMAIN, &category.trim().to_lowercase(), &self.index_key)
+    }
 
-        for point in &balances {
-            if point.balance > max_balance {
-                max_balance = point.balance;
-            }
-            let drawdown = max_balance - point.balance;
-            if drawdown > max_drawdown {
-                max_drawdown = drawdown;
-            }
+    // Tags are stored as one encrypted JSON array, NULL when there are none
+    fn encrypt_tags(&self, tags: &[String]) -> Result<Option<String>> {
+        if tags.is_empty() {
+            return Ok(None);
         }
+        Ok(Some(self.security.encrypt(&serde_json::to_string(tags)?, &self.encryption_key)?))
+    }
 
-        Ok(-max_drawdown) // Return as negative value
+    fn decrypt_tags(&self, encrypted_tags: Option<String>) -> Result<Vec<String>> {
+        match encrypted_tags {
+            Some(encrypted_tags) => Ok(serde_json::from_str(&self.security.decrypt(&encrypted_tags, &self.encryption_key)?)?),
+            None => Ok(Vec::new()),
+        }
     }
 
-    pub async fn get_budgets(&self) -> Result<Vec<Budget>> {
-        let rows = sqlx::query!(
-            "SELECT id, category_encrypted, amount, spent, period FROM budgets"
-        ).fetch_all(&self.pool).await?;
+    pub async fn add_transaction(&self, transaction: &Transaction) -> Result<()> {
+        self.insert_transaction(transaction, None).await
+    }
 
-        let mut budgets = Vec::new();
-        for row in rows {
-            let category = self.security.decrypt(&row.category_encrypted, &self.encryption_key)?;
-            
-            budgets.push(Budget {
-                id: row.id,
-                category,
-                amount: row.amount,
-                spent: row.spent,
-                period: row.period,
-            });
+    // The import resolves the account of each row and loads the payees once
+    // for all of them; the category model learns the rows once per import,
+    // in finish_import_batch
+    pub async fn add_imported_transaction(&self, transaction: &Transaction, batch_id: &str, payees: &mut Vec<Payee>) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        self.insert_transaction_with(&mut tx, transaction, Some(batch_id), payees).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // Inserts one transaction and teaches its category to the model.
+    // Accounts are stored by ID; an account name is resolved.
+    async fn insert_transaction(&self, transaction: &Transaction, batch_id: Option<&str>) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let transaction = &Transaction {
+            account: self.resolve_account_with(&mut tx, &transaction.account).await?,
+            ..transaction.clone()
+        };
+        let mut payees = self.load_payees(&mut tx).await?;
+        self.insert_transaction_with(&mut tx, transaction, batch_id, &mut payees).await?;
+        if transaction.transfer_id.is_none() {
+            self.relearn_category(&mut tx, &[], &[TrainingExample::of(transaction)]).await?;
         }
-        
-        Ok(budgets)
+        tx.commit().await?;
+        Ok(())
     }
 
-    pub async fn set_budget(&self, budget: &Budget) -> Result<()> {
-        let encrypted_category = self.security.encrypt(&budget.category, &self.encryption_key)?;
+    // `transaction.account` must already be resolved to an account ID.
+    // Payees created for the row are added to `payees`.
+    async fn insert_transaction_with(
+        &self,
+        conn: &mut SqliteConnection,
+        transaction: &Transaction,
+        batch_id: Option<&str>,
+        payees: &mut Vec<Payee>,
+    ) -> Result<()> {
+        let encrypted_description = self.security.encrypt(&transaction.description, &self.encryption_key)?;
+        let encrypted_category = self.security.encrypt(&transaction.category, &self.encryption_key)?;
+        
+        // Stored dates must be ISO-8601 for the date('now', ...) comparisons in analytics
+        let transaction = &Transaction {
+            date: parse_date(&transaction.date)?,
+            ..transaction.clone()
+        };
+        let hash = self.transaction_hash(transaction)?;
+        validate_splits(transaction)?;
+        
+        // Only the bank's own identifier is conclusive here: two identical
+        // coffees on the same day are two purchases. A trashed row does not
+        // block its re-import.
+        if let Some(external_id) = &transaction.external_id {
+            let existing = sqlx::query!(
+                "SELECT id FROM transactions WHERE account = ? AND external_id = ? AND deleted_at IS NULL",
+                transaction.account,
+                external_id
+            ).fetch_optional(&mut *conn).await?;
+            if existing.is_some() {
+                return Err(anyhow!("Transaction en double détectée"));
+            }
+        }
+        
+        let category_index = self.category_index(&transaction.category)?;
+        let encrypted_tags = self.encrypt_tags(&transaction.tags)?;
+        let payee_id = match &transaction.payee_id {
+            Some(payee_id) => Some(payee_id.clone()),
+            None => self.assign_payee_from(&mut *conn, &transaction.description, payees).await?,
+        };
         
         sqlx::query!(
-            "INSERT OR REPLACE INTO budgets (id, category_encrypted, amount, spent, period, updated_at) 
-             VALUES (?, ?, ?, ?, ?, CURRENT_TIMESTAMP)",
-            budget.id,
+            "INSERT INTO transactions (id, description_encrypted, amount, date, category_encrypted, category_index, account, hash, external_id, batch_id, transfer_id, tags_encrypted, rule_id, payee_id) 
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            transaction.id,
+            encrypted_description,
+            transaction.amount,
+            transaction.date,
             encrypted_category,
-            budget.amount,
-            budget.spent,
-            budget.period
-        ).execute(&self.pool).await?;
+            category_index,
+            transaction.account,
+            hash,
+            transaction.external_id,
+            batch_id,
+            transaction.transfer_id,
+            encrypted_tags,
+            transaction.rule_id,
+            payee_id
+        ).execute(&mut *conn).await?;
+        self.write_splits(&mut *conn, &transaction.id, &transaction.splits).await?;
+        self.index_transaction(&mut *conn, &transaction.id, &transaction.description, &transaction.splits).await?;
         
         Ok(())
     }
 
+    // Rewrites a transaction, all or nothing. Transfer links are kept, so a
+    // linked leg keeps its amount and account; use link_transfer and
+    // unlink_transfer to change them.
+    pub async fn update_transaction(&self, transaction: &Transaction) -> Result<()> {
+        let transaction = &Transaction {
+            date: parse_date(&transaction.date)?,
+            ..transaction.clone()
+        };
+        let encrypted_description = self.security.encrypt(&transaction.description, &self.encryption_key)?;
+        let encrypted_category = self.security.encrypt(&transaction.category, &self.encryption_key)?;
+        let category_index = self.category_index(&transaction.category)?;
+        let encrypted_tags = self.encrypt_tags(&transaction.tags)?;
+        validate_splits(transaction)?;
+        
+        let mut tx = self.pool.begin().await?;
+        
+        let account = self.resolve_account_with(&mut tx, &transaction.account).await?;
+        let transaction = &Transaction {
+            account,
+            ..transaction.clone()
+        };
+        let hash = self.transaction_hash(transaction)?;
+        
+        let old = sqlx::query!(
+            "SELECT description_encrypted, amount, date, category_encrypted, account, transfer_id, payee_id FROM transactions WHERE id = ? AND deleted_at IS NULL",
+            transaction.id
+        ).fetch_optional(&mut *tx).await?
+            .ok_or_else(|| anyhow!("Transaction introuvable: {}", transaction.id))?;
+        let old_description = self.security.decrypt(&old.description_encrypted, &self.encryption_key)?;
+        let old_category = self.security.decrypt(&old.category_encrypted, &self.encryption_key)?;
+        
+        // A linked leg must keep cancelling its partner out from another account
+        if old.transfer_id.is_some() && ((transaction.amount - old.amount).abs() >= 0.005 || transaction.account != old.account) {
+            return Err(anyhow!("Le montant et le compte d'une opération de virement ne peuvent pas être modifiés; détachez d'abord le virement"));
+        }
+        if let Some(external_id) = &transaction.external_id {
+            let existing = sqlx::query!(
+                "SELECT id FROM transactions WHERE account = ? AND external_id = ? AND id != ? AND deleted_at IS NULL",
+                transaction.account,
+                external_id,
+                transaction.id
+            ).fetch_optional(&mut *tx).await?;
+            if existing.is_some() {
+                return Err(anyhow!("Transaction en double détectée"));
+            }
+        }
+        
+        // A new description brings its payee along unless one was picked explicitly
+        let payee_id = match &transaction.payee_id {
+            Some(payee_id) if transaction.payee_id != old.payee_id || transaction.description == old_description => {
+                Some(payee_id.clone())
+            }
+            _ => self.assign_payee(&mut tx, &transaction.description).await?,
+        };
+        
+        sqlx::query!(
+            "UPDATE transactions SET description_encrypted = ?, amount = ?, date = ?, category_encrypted = ?, 
+             category_index = ?, account = ?, hash = ?, external_id = ?, tags_encrypted = ?, rule_id = ?, payee_id = ? WHERE id = ?",
+            encrypted_description,
+            transaction.amount,
+            transaction.date,
+            encrypted_category,
+            category_index,
+            transaction.account,
+            hash,
+            transaction.external_id,
+            encrypted_tags,
+            transaction.rule_id,
+            payee_id,
+            transaction.id
+        ).execute(&mut *tx).await?;
+        self.write_splits(&mut tx, &transaction.id, &transaction.splits).await?;
+        self.index_transaction(&mut tx, &transaction.id, &transaction.description, &transaction.splits).await?;
+        
+        // A recategorised transaction is how the user corrects a suggestion
+        if old.transfer_id.is_none() {
+            let old_example = TrainingExample {
+                description: old_description,
+                amount: old.amount,
+                category: old_category,
+            };
+            self.relearn_category(&mut tx, &[old_example], &[TrainingExample::of(transaction)]).await?;
+        }
+        
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // Moves a transaction to the trash; its amount stops counting towards
+    // budgets and balances until it is restored. Both legs of a transfer go
+    // together.
+    pub async fn delete_transaction(&self, transaction_id: &str) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        
+        let row = sqlx::query!(
+            "SELECT transfer_id FROM transactions WHERE id = ? AND deleted_at IS NULL",
+            transaction_id
+        ).fetch_optional(&mut *tx).await?
+            .ok_or_else(|| anyhow!("Transaction introuvable: {}", transaction_id))?;
+        
+        let legs = match &row.transfer_id {
+            Some(transfer_id) => sqlx::query!(
+                "SELECT id FROM transactions WHERE transfer_id = ? AND deleted_at IS NULL",
+                transfer_id
+            ).fetch_all(&mut *tx).await?.into_iter().map(|leg| leg.id).collect(),
+            None => vec![transaction_id.to_string()],
+        };
+        for leg in legs {
+            self.set_trashed(&mut tx, &leg, true).await?;
+        }
+        
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn restore_transaction(&self, transaction_id: &str) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        
+        let row = sqlx::query!(
+            "SELECT transfer_id FROM transactions WHERE id = ? AND deleted_at IS NOT NULL",
+            transaction_id
+        ).fetch_optional(&mut *tx).await?
+            .ok_or_else(|| anyhow!("Transaction introuvable dans la corbeille: {}", transaction_id))?;
+        
+        let legs = match &row.transfer_id {
+            Some(transfer_id) => sqlx::query!(
+                "SELECT id FROM transactions WHERE transfer_id = ? AND deleted_at IS NOT NULL",
+                transfer_id
+            ).fetch_all(&mut *tx).await?.into_iter().map(|leg| leg.id).collect(),
+            None => vec![transaction_id.to_string()],
+        };
+        for leg in legs {
+            self.set_trashed(&mut tx, &leg, false).await?;
+        }
+        
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // Trashed rows leave the category model and restored ones rejoin it
+    async fn set_trashed(&self, tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>, transaction_id: &str, trashed: bool) -> Result<()> {
+        let examples: Vec<TrainingExample> = self.stored_training_example(tx, transaction_id).await?
+            .into_iter()
+            .collect();
+        if trashed {
+            sqlx::query!("UPDATE transactions SET deleted_at = CURRENT_TIMESTAMP WHERE id = ?", transaction_id)
+                .execute(&mut **tx).await?;
+            self.relearn_category(tx, &examples, &[]).await?;
+        } else {
+            // The same bank movement may have been imported again meanwhile
+            let reimported = sqlx::query!(
+                "SELECT live.id FROM transactions live JOIN transactions trashed ON trashed.id = ? 
+                 WHERE live.deleted_at IS NULL AND live.account = trashed.account AND live.external_id = trashed.external_id",
+                transaction_id
+            ).fetch_optional(&mut **tx).await?;
+            if reimported.is_some() {
+                return Err(anyhow!("Transaction en double détectée"));
+            }
+            sqlx::query!("UPDATE transactions SET deleted_at = NULL WHERE id = ?", transaction_id)
+                .execute(&mut **tx).await?;
+            self.relearn_category(tx, &[], &examples).await?;
+        }
+        
+        Ok(())
+    }
+
+    pub async fn get_trash(&self) -> Result<Vec<TrashedTransaction>> {
+        let rows = sqlx::query!(
+            "SELECT id, description_encrypted, amount, date, category_encrypted, account, external_id, transfer_id, tags_encrypted, rule_id, payee_id, deleted_at 
+             FROM transactions WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC"
+        ).fetch_all(&self.pool).await?;
+        
+        let mut transactions = Vec::new();
+        let mut deleted_at = Vec::new();
+        for row in rows {
+            transactions.push(Transaction {
+                id: row.id,
+                description: self.security.decrypt(&row.description_encrypted, &self.encryption_key)?,
+                amount: row.amount,
+                date: row.date,
+                category: self.security.decrypt(&row.category_encrypted, &self.encryption_key)?,
+                account: row.account,
+                external_id: row.external_id,
+                transfer_id: row.transfer_id,
+                splits: Vec::new(),
+                tags: self.decrypt_tags(row.tags_encrypted)?,
+                rule_id: row.rule_id,
+                payee_id: row.payee_id,
+            });
+            deleted_at.push(row.deleted_at.map(|d| d.to_string()).unwrap_or_default());
+        }
+        self.attach_splits(&mut transactions).await?;
+        
+        Ok(transactions
+            .into_iter()
+            .zip(deleted_at)
+            .map(|(transaction, deleted_at)| TrashedTransaction {
+                transaction,
+                deleted_at,
+            })
+            .collect())
+    }
+
+    // Permanently removes trashed transactions older than the retention period
+    pub async fn purge_trash(&self) -> Result<u64> {
+        let retention = format!("-{} days", self.get_trash_retention().await?);
+        
+        let mut tx = self.pool.begin().await?;
+        
+        sqlx::query!(
+            "DELETE FROM search_tokens WHERE transaction_id IN 
+             (SELECT id FROM transactions WHERE deleted_at IS NOT NULL AND deleted_at <= datetime('now', ?))",
+            retention
+        ).execute(&mut *tx).await?;
+        sqlx::query!(
+            "DELETE FROM transaction_splits WHERE transaction_id IN 
+             (SELECT id FROM transactions WHERE deleted_at IS NOT NULL AND deleted_at <= datetime('now', ?))",
+            retention
+        ).execute(&mut *tx).await?;
+        let purged = sqlx::query!(
+            "DELETE FROM transactions WHERE deleted_at IS NOT NULL AND deleted_at <= datetime('now', ?)",
+            retention
+        ).execute(&mut *tx).await?
+            .rows_affected();
+        
+        tx.commit().await?;
+        Ok(purged)
+    }
+
+    pub async fn get_trash_retention(&self) -> Result<u32> {
+        Ok(self.get_setting(TRASH_RETENTION_KEY).await?.unwrap_or(DEFAULT_TRASH_RETENTION_DAYS))
+    }
+
+    pub async fn set_trash_retention(&self, days: u32) -> Result<()> {
+        self.set_setting(TRASH_RETENTION_KEY, &days).await
+    }
+
+    // Replaces the splits of a transaction
+    async fn write_splits(&self, conn: &mut SqliteConnection, transaction_id: &str, splits: &[TransactionSplit]) -> Result<()> {
+        sqlx::query!("DELETE FROM transaction_splits WHERE transaction_id = ?", transaction_id)
+            .execute(&mut *conn).await?;
+        
+        for (position, split) in splits.iter().enumerate() {
+            let id = if split.id.is_empty() { self.security.generate_secure_id() } else { split.id.clone() };
+            let position = position as i64;
+            let encrypted_category = self.security.encrypt(&split.category, &self.encryption_key)?;
+            let category_index = self.category_index(&split.category)?;
+            let encrypted_memo = match &split.memo {
+                Some(memo) => Some(self.security.encrypt(memo, &self.encryption_key)?),
+                None => None,
+            };
+            
+            sqlx::query!(
+                "INSERT INTO transaction_splits (id, transaction_id, position, amount, category_encrypted, category_index, memo_encrypted) 
+                 VALUES (?, ?, ?, ?, ?, ?, ?)",
+                id,
+                transaction_id,
+                position,
+                split.amount,
+                encrypted_category,
+                category_index,
+                encrypted_memo
+            ).execute(&mut *conn).await?;
+        }
+        
+        Ok(())
+    }
+
+    // Splits of several transactions in one query, keyed by parent ID
+    async fn load_splits(&self, transaction_ids: &[String]) -> Result<HashMap<String, Vec<TransactionSplit>>> {
+        let mut splits: HashMap<String, Vec<TransactionSplit>> = HashMap::new();
+        if transaction_ids.is_empty() {
+            return Ok(splits);
+        }
+        
+        let mut select = QueryBuilder::<Sqlite>::new(
+            "SELECT id, transaction_id, amount, category_encrypted, memo_encrypted FROM transaction_splits 
+             WHERE transaction_id IN ("
+        );
+        let mut separated = select.separated(", ");
+        for transaction_id in transaction_ids {
+            separated.push_bind(transaction_id.as_str());
+        }
+        select.push(") ORDER BY transaction_id, position ASC");
+        
+        for row in select.build().fetch_all(&self.pool).await? {
+            let memo: Option<String> = row.get("memo_encrypted");
+            let split = TransactionSplit {
+                id: row.get("id"),
+                amount: row.get("amount"),
+                category: self.security.decrypt(&row.get::<String, _>("category_encrypted"), &self.encryption_key)?,
+                memo: match memo {
+                    Some(memo) => Some(self.security.decrypt(&memo, &self.encryption_key)?),
+                    None => None,
+                },
+            };
+            splits.entry(row.get("transaction_id")).or_default().push(split);
+        }
+        
+        Ok(splits)
+    }
+
+    async fn attach_splits(&self, transactions: &mut [Transaction]) -> Result<()> {
+        let ids: Vec<String> = transactions.iter().map(|t| t.id.clone()).collect();
+        let mut splits = self.load_splits(&ids).await?;
+        for transaction in transactions {
+            transaction.splits = splits.remove(&transaction.id).unwrap_or_default();
+        }
+        Ok(())
+    }
+
+    // Creates both legs of a transfer in one transaction: a debit on the source
+    // account and a credit on the destination, linked by a shared transfer ID
+    pub async fn create_transfer(&self, transfer: &TransferRequest) -> Result<String> {
+        if transfer.amount <= 0.0 {
+            return Err(anyhow!("Le montant d'un virement doit être positif"));
+        }
+        let from_account = self.resolve_account(&transfer.from_account).await?;
+        let to_account = self.resolve_account(&transfer.to_account).await?;
+        if from_account == to_account {
+            return Err(anyhow!("Les comptes source et destination doivent être différents"));
+        }
+        
+        let transfer_id = self.security.generate_secure_id();
+        let leg = |account: &str, amount: f64| Transaction {
+            id: self.security.generate_secure_id(),
+            description: transfer.description.clone(),
+            amount,
+            date: transfer.date.clone(),
+            category: TRANSFER_CATEGORY.to_string(),
+            account: account.to_string(),
+            external_id: None,
+            transfer_id: Some(transfer_id.clone()),
+            splits: Vec::new(),
+            tags: Vec::new(),
+            rule_id: None,
+            payee_id: None,
+        };
+        let debit = leg(&from_account, -transfer.amount);
+        let credit = leg(&to_account, transfer.amount);
+        
+        let mut tx = self.pool.begin().await?;
+        let mut payees = self.load_payees(&mut tx).await?;
+        for leg in [&debit, &credit] {
+            self.insert_transaction_with(&mut tx, leg, None, &mut payees).await?;
+        }
+        tx.commit().await?;
+        
+        Ok(transfer_id)
+    }
+
+    // Links two existing transactions as the legs of one transfer; they must
+    // be on different accounts and cancel each other out
+    pub async fn link_transfer(&self, first_id: &str, second_id: &str) -> Result<String> {
+        let mut tx = self.pool.begin().await?;
+        let first = self.get_transaction_with(&mut tx, first_id).await?
+            .ok_or_else(|| anyhow!("Transaction introuvable: {}", first_id))?;
+        let second = self.get_transaction_with(&mut tx, second_id).await?
+            .ok_or_else(|| anyhow!("Transaction introuvable: {}", second_id))?;
+        let transfer_id = self.link_transfer_with(&mut tx, &first, &second).await?;
+        tx.commit().await?;
+        
+        Ok(transfer_id)
+    }
+
+    // Both legs are filed under the transfer category; the category each had
+    // before is kept so unlinking can give it back
+    async fn link_transfer_with(&self, conn: &mut SqliteConnection, first: &Transaction, second: &Transaction) -> Result<String> {
+        if first.account == second.account {
+            return Err(anyhow!("Les deux opérations d'un virement doivent être sur des comptes différents"));
+        }
+        if (first.amount + second.amount).abs() >= 0.005 {
+            return Err(anyhow!("Les montants d'un virement doivent être opposés"));
+        }
+        if first.transfer_id.is_some() || second.transfer_id.is_some() {
+            return Err(anyhow!("Transaction déjà rattachée à un virement"));
+        }
+        
+        let transfer_id = self.security.generate_secure_id();
+        let encrypted_category = self.security.encrypt(TRANSFER_CATEGORY, &self.encryption_key)?;
+        let category_index = self.category_index(TRANSFER_CATEGORY)?;
+        for leg in [first, second] {
+            sqlx::query!(
+                "UPDATE transactions SET transfer_id = ?, previous_category_encrypted = category_encrypted, category_encrypted = ?, category_index = ? WHERE id = ?",
+                transfer_id,
+                encrypted_category,
+                category_index,
+                leg.id
+            ).execute(&mut *conn).await?;
+        }
+        // Transfers say nothing about spending habits
+        self.relearn_category(&mut *conn, &[TrainingExample::of(first), TrainingExample::of(second)], &[]).await?;
+        
+        Ok(transfer_id)
+    }
+
+    // Turns a transfer back into two independent transactions. Legs in the
+    // trash are unlinked too, so restoring one does not bring back half a
+    // transfer.
+    pub async fn unlink_transfer(&self, transfer_id: &str) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        
+        let rows = sqlx::query!("SELECT id FROM transactions WHERE transfer_id = ?", transfer_id)
+            .fetch_all(&mut *tx).await?;
+        if rows.is_empty() {
+            return Err(anyhow!("Virement introuvable: {}", transfer_id));
+        }
+        
+        let mut relearned = Vec::new();
+        for row in rows {
+            relearned.extend(self.unlink_leg(&mut tx, &row.id).await?);
+        }
+        self.relearn_category(&mut tx, &[], &relearned).await?;
+        
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // Gives an unlinked leg back the category it had before being linked,
+    // unless it was recategorised since. Returns what the category model
+    // should learn again, nothing for a leg in the trash.
+    async fn unlink_leg(&self, conn: &mut SqliteConnection, transaction_id: &str) -> Result<Option<TrainingExample>> {
+        let row = sqlx::query!(
+            "SELECT description_encrypted, amount, category_encrypted, previous_category_encrypted, deleted_at 
+             FROM transactions WHERE id = ?",
+            transaction_id
+        ).fetch_one(&mut *conn).await?;
+        
+        let current = self.security.decrypt(&row.category_encrypted, &self.encryption_key)?;
+        let category = if current != TRANSFER_CATEGORY {
+            current
+        } else if let Some(previous) = &row.previous_category_encrypted {
+            self.security.decrypt(previous, &self.encryption_key)?
+        } else {
+            DEFAULT_CATEGORY.to_string()
+        };
+        let encrypted_category = self.security.encrypt(&category, &self.encryption_key)?;
+        let category_index = self.category_index(&category)?;
+        sqlx::query!(
+            "UPDATE transactions SET transfer_id = NULL, previous_category_encrypted = NULL, category_encrypted = ?, category_index = ? WHERE id = ?",
+            encrypted_category,
+            category_index,
+            transaction_id
+        ).execute(&mut *conn).await?;
+        
+        if row.deleted_at.is_some() {
+            return Ok(None);
+        }
+        Ok(Some(TrainingExample {
+            description: self.security.decrypt(&row.description_encrypted, &self.encryption_key)?,
+            amount: row.amount,
+            category,
+        }))
+    }
+
+    // Pairs the unlinked transactions of an import batch with their other leg:
+    // an opposite amount on another account within a few days. Both sides must
+    // read like a transfer, or one side must and the other be the only
+    // candidate. Returns the number of transfers created.
+    pub async fn pair_transfers(&self, batch_id: &str) -> Result<i32> {
+        let rows = sqlx::query!(
+            "SELECT id FROM transactions WHERE batch_id = ? AND transfer_id IS NULL AND deleted_at IS NULL",
+            batch_id
+        ).fetch_all(&self.pool).await?;
+        
+        let ids: Vec<String> = rows.into_iter().map(|row| row.id).collect();
+        self.pair_transfer_ids(&ids).await
+    }
+
+    // All or nothing, so a failure part-way does not leave half the pairs linked
+    async fn pair_transfer_ids(&self, ids: &[String]) -> Result<i32> {
+        let mut tx = self.pool.begin().await?;
+        let mut paired = 0;
+        for id in ids {
+            // An earlier iteration may already have paired this row
+            let transaction = match self.get_transaction_with(&mut tx, id).await? {
+                Some(transaction) if transaction.transfer_id.is_none() => transaction,
+                _ => continue,
+            };
+            
+            let window_start = format!("-{} days", transfers::PAIRING_WINDOW_DAYS);
+            let window_end = format!("+{} days", transfers::PAIRING_WINDOW_DAYS);
+            let candidates = sqlx::query!(
+                "SELECT id, date FROM transactions 
+                 WHERE account != ? AND ABS(amount + ?) < 0.005 AND transfer_id IS NULL AND deleted_at IS NULL 
+                 AND date BETWEEN date(?, ?) AND date(?, ?)",
+                transaction.account,
+                transaction.amount,
+                transaction.date,
+                window_start,
+                transaction.date,
+                window_end
+            ).fetch_all(&mut *tx).await?;
+            
+            let day = chrono::NaiveDate::parse_from_str(&transaction.date, "%Y-%m-%d")?;
+            let is_transfer = transfers::looks_like_transfer(&transaction.description, &transaction.category);
+            let only_candidate = candidates.len() == 1;
+            let mut best: Option<(i64, Transaction)> = None;
+            for candidate in candidates {
+                let distance = match chrono::NaiveDate::parse_from_str(&candidate.date, "%Y-%m-%d") {
+                    Ok(candidate_day) => (day - candidate_day).num_days().abs(),
+                    Err(_) => continue,
+                };
+                if best.as_ref().map(|(best_distance, _)| distance >= *best_distance).unwrap_or(false) {
+                    continue;
+                }
+                let other = match self.get_transaction_with(&mut tx, &candidate.id).await? {
+                    Some(other) => other,
+                    None => continue,
+                };
+                let other_is_transfer = transfers::looks_like_transfer(&other.description, &other.category);
+                if (is_transfer && other_is_transfer) || (only_candidate && (is_transfer || other_is_transfer)) {
+                    best = Some((distance, other));
+                }
+            }
+            
+            if let Some((_, other)) = best {
+                self.link_transfer_with(&mut tx, &transaction, &other).await?;
+                paired += 1;
+            }
+        }
+        tx.commit().await?;
+        
+        Ok(paired)
+    }
+
+    // Scores the stored transactions of the same account and amount around the
+    // transaction's date and returns the best match. Trashed rows and rows of
+    // `batch_id` are ignored: a statement listing the same purchase twice
+    // means two purchases. Scoring also skips the rows in `matched`: earlier
+    // rows of the same file were already taken as duplicates of them.
+    pub async fn detect_duplicate(&self, transaction: &Transaction, batch_id: Option<&str>, matched: &HashSet<String>) -> Result<DuplicateMatch> {
+        // The bank's own identifier wins over the content, so re-importing
+        // overlapping statements is idempotent
+        if let Some(external_id) = &transaction.external_id {
+            let existing = sqlx::query!(
+                "SELECT id FROM transactions WHERE account = ? AND external_id = ? AND deleted_at IS NULL LIMIT 1",
+                transaction.account,
+                external_id
+            ).fetch_optional(&self.pool).await?;
+            
+            if let Some(existing) = existing {
+                return Ok(DuplicateMatch {
+                    level: DuplicateLevel::Certain,
+                    score: 1.0,
+                    transaction_id: Some(existing.id),
+                });
+            }
+        }
+        
+        let date = parse_date(&transaction.date)?;
+        let day = chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d")?;
+        let window_start = format!("-{} days", duplicates::DATE_WINDOW_DAYS);
+        let window_end = format!("+{} days", duplicates::DATE_WINDOW_DAYS);
+        
+        let rows = sqlx::query!(
+            "SELECT id, description_encrypted, date, external_id, batch_id FROM transactions 
+             WHERE account = ? AND ABS(amount - ?) < 0.005 AND deleted_at IS NULL 
+             AND date BETWEEN date(?, ?) AND date(?, ?)",
+            transaction.account,
+            transaction.amount,
+            date,
+            window_start,
+            date,
+            window_end
+        ).fetch_all(&self.pool).await?;
+        
+        let description = duplicates::normalize_description(&transaction.description);
+        let mut best = DuplicateMatch::none();
+        for row in rows {
+            if batch_id.is_some() && row.batch_id.as_deref() == batch_id {
+                continue;
+            }
+            if matched.contains(&row.id) {
+                continue;
+            }
+            // Two different bank identifiers are two different movements
+            if transaction.external_id.is_some() && row.external_id.is_some() {
+                continue;
+            }
+            
+            let stored_day = match chrono::NaiveDate::parse_from_str(&row.date, "%Y-%m-%d") {
+                Ok(stored_day) => stored_day,
+                Err(_) => continue,
+            };
+            let stored_description = self.security.decrypt(&row.description_encrypted, &self.encryption_key)?;
+            let similarity = duplicates::description_similarity(
+                &description,
+                &duplicates::normalize_description(&stored_description),
+            );
+            
+            let score = duplicates::score((day - stored_day).num_days(), similarity);
+            if score > best.score {
+                best = DuplicateMatch {
+                    level: duplicates::classify(score),
+                    score,
+                    transaction_id: Some(row.id),
+                };
+            }
+        }
+        
+        if best.level == DuplicateLevel::NotDuplicate {
+            return Ok(DuplicateMatch::none());
+        }
+        Ok(best)
+    }
+
+    fn transaction_hash(&self, transaction: &Transaction) -> Result<String> {
+        // Create hash for duplicate detection
+        let hash_input = format!("{}{}{}{}", 
+            transaction.description, transaction.amount, transaction.date, transaction.account);
+        self.security.create_hash(&hash_input)
+    }
+
+    // Filters, sorts and pages with a keyset cursor: the next page starts
+    // strictly after the last row of the previous one in (sort key, id) order,
+    // so paging stays stable while rows are added
+    pub async fn get_transactions(&self, query: &TransactionQuery) -> Result<TransactionPage> {
+        let limit = query.limit.unwrap_or(100).clamp(1, MAX_PAGE_SIZE);
+        let category_index = match &query.category {
+            Some(category) => Some(self.category_index(category)?),
+            None => None,
+        };
+        let date_from = query.date_from.as_deref().map(parse_date).transpose()?;
+        let date_to = query.date_to.as_deref().map(parse_date).transpose()?;
+        let filters = TransactionFilters {
+            account: query.account.as_deref(),
+            date_from: date_from.as_deref(),
+            date_to: date_to.as_deref(),
+            min_amount: query.min_amount,
+            max_amount: query.max_amount,
+            category_index: category_index.as_deref(),
+        };
+        
+        let mut count_query = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) FROM transactions WHERE deleted_at IS NULL");
+        filters.push(&mut count_query);
+        let total_count: i64 = count_query.build_query_scalar().fetch_one(&self.pool).await?;
+        
+        let (column, descending) = sort_key(query.sort);
+        let (order, comparison) = if descending { ("DESC", "<") } else { ("ASC", ">") };
+        
+        let mut select = QueryBuilder::<Sqlite>::new(
+            "SELECT id, description_encrypted, amount, date, category_encrypted, account, external_id, transfer_id, tags_encrypted, rule_id, payee_id 
+             FROM transactions WHERE deleted_at IS NULL"
+        );
+        filters.push(&mut select);
+        if let Some(cursor) = &query.cursor {
+            let (value, id) = decode_cursor(cursor)?;
+            select.push(format!(" AND ({} {} ", column, comparison));
+            push_sort_value(&mut select, query.sort, &value)?;
+            select.push(format!(" OR ({} = ", column));
+            push_sort_value(&mut select, query.sort, &value)?;
+            select.push(format!(" AND id {} ", comparison));
+            select.push_bind(id);
+            select.push("))");
+        }
+        select.push(format!(" ORDER BY {} {}, id {} LIMIT ", column, order, order));
+        // One extra row tells whether there is a next page
+        select.push_bind(limit + 1);
+        
+        let rows = select.build().fetch_all(&self.pool).await?;
+        let has_more = rows.len() > limit as usize;
+        
+        let mut transactions = Vec::new();
+        for row in rows.into_iter().take(limit as usize) {
+            let description = self.security.decrypt(&row.get::<String, _>("description_encrypted"), &self.encryption_key)?;
+            let category = self.security.decrypt(&row.get::<String, _>("category_encrypted"), &self.encryption_key)?;
+            
+            transactions.push(Transaction {
+                id: row.get("id"),
+                description,
+                amount: row.get("amount"),
+                date: row.get("date"),
+                category,
+                account: row.get("account"),
+                external_id: row.get("external_id"),
+                transfer_id: row.get("transfer_id"),
+                splits: Vec::new(),
+                tags: self.decrypt_tags(row.get("tags_encrypted"))?,
+                rule_id: row.get("rule_id"),
+                payee_id: row.get("payee_id"),
+            });
+        }
+        self.attach_splits(&mut transactions).await?;
+        
+        let next_cursor = match transactions.last() {
+            Some(last) if has_more => Some(encode_cursor(query.sort, last)),
+            _ => None,
+        };
+        
+        Ok(TransactionPage {
+            transactions,
+            total_count,
+            next_cursor,
+        })
+    }
+
+    // Finds transactions whose description or split memos contain every
+    // query word, as a whole word or a prefix. Whole-word matches rank first, then newer rows.
+    pub async fn search_transactions(&self, query: &str, limit: Option<i32>) -> Result<Vec<SearchResult>> {
+        let terms = search::query_terms(query);
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+        let limit = limit.unwrap_or(50).clamp(1, MAX_PAGE_SIZE);
+        
+        let mut hashes = Vec::new();
+        for term in &terms {
+            hashes.push(self.security.blind_index(SEARCH_INDEX_DOMAIN, term, &self.index_key)?);
+        }
+        
+        let mut select = QueryBuilder::<Sqlite>::new(
+            "SELECT t.id, t.description_encrypted, t.amount, t.date, t.category_encrypted, t.account, t.external_id, t.transfer_id, t.tags_encrypted, t.rule_id, t.payee_id, 
+             SUM(s.exact) as exact_matches 
+             FROM search_tokens s JOIN transactions t ON t.id = s.transaction_id 
+             WHERE t.deleted_at IS NULL AND s.token_hash IN ("
+        );
+        let mut separated = select.separated(", ");
+        for hash in hashes {
+            separated.push_bind(hash);
+        }
+        select.push(") GROUP BY t.id HAVING COUNT(*) = ");
+        select.push_bind(terms.len() as i64);
+        select.push(" ORDER BY exact_matches DESC, t.date DESC, t.id DESC LIMIT ");
+        select.push_bind(limit);
+        
+        let rows = select.build().fetch_all(&self.pool).await?;
+        
+        let mut transactions = Vec::new();
+        let mut scores = Vec::new();
+        for row in rows {
+            let description = self.security.decrypt(&row.get::<String, _>("description_encrypted"), &self.encryption_key)?;
+            let category = self.security.decrypt(&row.get::<String, _>("category_encrypted"), &self.encryption_key)?;
+            let exact_matches: i64 = row.get("exact_matches");
+            
+            transactions.push(Transaction {
+                id: row.get("id"),
+                description,
+                amount: row.get("amount"),
+                date: row.get("date"),
+                category,
+                account: row.get("account"),
+                external_id: row.get("external_id"),
+                transfer_id: row.get("transfer_id"),
+                splits: Vec::new(),
+                tags: self.decrypt_tags(row.get("tags_encrypted"))?,
+                rule_id: row.get("rule_id"),
+                payee_id: row.get("payee_id"),
+            });
+            scores.push(exact_matches as f64 / terms.len() as f64);
+        }
+        self.attach_splits(&mut transactions).await?;
+        
+        Ok(transactions
+            .into_iter()
+            .zip(scores)
+            .map(|(transaction, score)| SearchResult {
+                transaction,
+                score,
+            })
+            .collect())
+    }
+
+    pub async fn get_transaction(&self, transaction_id: &str) -> Result<Option<Transaction>> {
+        let mut conn = self.pool.acquire().await?;
+        let transaction = self.get_transaction_with(&mut conn, transaction_id).await?;
+        // Give the connection back before the splits take one
+        drop(conn);
+        match transaction {
+            Some(transaction) => {
+                let mut transactions = vec![transaction];
+                self.attach_splits(&mut transactions).await?;
+                Ok(transactions.pop())
+            }
+            None => Ok(None),
+        }
+    }
+
+    // Reads a transaction through the caller's connection, without its splits
+    async fn get_transaction_with(&self, conn: &mut SqliteConnection, transaction_id: &str) -> Result<Option<Transaction>> {
+        let row = sqlx::query!(
+            "SELECT id, description_encrypted, amount, date, category_encrypted, account, external_id, transfer_id, tags_encrypted, rule_id, payee_id 
+             FROM transactions WHERE id = ? AND deleted_at IS NULL",
+            transaction_id
+        ).fetch_optional(&mut *conn).await?;
+        
+        match row {
+            Some(row) => Ok(Some(Transaction {
+                id: row.id,
+                description: self.security.decrypt(&row.description_encrypted, &self.encryption_key)?,
+                amount: row.amount,
+                date: row.date,
+                category: self.security.decrypt(&row.category_encrypted, &self.encryption_key)?,
+                account: row.account,
+                external_id: row.external_id,
+                transfer_id: row.transfer_id,
+                splits: Vec::new(),
+                tags: self.decrypt_tags(row.tags_encrypted)?,
+                rule_id: row.rule_id,
+                payee_id: row.payee_id,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn get_balance_history(&self, days: i32) -> Result<Vec<BalancePoint>> {
+        let since = format!("-{} days", days);
+        let rows = sqlx::query!(
+            "SELECT date, amount FROM transactions 
+             WHERE date >= date('now', ?) AND deleted_at IS NULL 
+             ORDER BY date ASC",
+            since
+        ).fetch_all(&self.pool).await?;
+
+        let mut balance = 0.0;
+        let mut history = Vec::new();
+        let mut current_date = String::new();
+        let mut daily_total = 0.0;
+        
+        for row in rows {
+            if row.date != current_date {
+                if !current_date.is_empty() {
+                    balance += daily_total;
+                    history.push(BalancePoint {
+                        date: current_date.clone(),
+                        balance,
+                    });
+                }
+                current_date = row.date;
+                daily_total = 0.0;
+            }
+            daily_total += row.amount;
+        }
+        
+        // Add the last day
+        if !current_date.is_empty() {
+            balance += daily_total;
+            history.push(BalancePoint {
+                date: current_date,
+                balance,
+            });
+        }
+        
+        Ok(history)
+    }
+
+    pub async fn get_financial_metrics(&self) -> Result<FinancialMetrics> {
+        // Calculate burn rate (average daily expenses over last 30 days)
+        let burn_rate_row = sqlx::query!(
+            "SELECT AVG(daily_expense) as burn_rate FROM (
+                SELECT DATE(date) as day, SUM(ABS(amount)) as daily_expense 
+                FROM transactions 
+                WHERE amount < 0 AND date >= date('now', '-30 days') AND deleted_at IS NULL AND transfer_id IS NULL
+                GROUP BY DATE(date)
+            )"
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        let burn_rate = burn_rate_row.burn_rate.unwrap_or(0.0);
+
+        // Calculate current balance
+        let balance_row = sqlx::query!(
+            "SELECT SUM(amount) as balance FROM transactions WHERE deleted_at IS NULL"
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        let balance = balance_row.balance.unwrap_or(0.0);
+
+        // Calculate runway (days until balance reaches zero)
+        let runway = if burn_rate > 0.0 { balance / burn_rate } else { 999.0 };
+
+        // Calculate ITT (Income Tension Index)
+        let income_row = sqlx::query!(
+            "SELECT SUM(amount) as income FROM transactions 
+             WHERE amount > 0 AND date >= date('now', '-30 days') AND deleted_at IS NULL AND transfer_id IS NULL"
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        let expense_row = sqlx::query!(
+            "SELECT SUM(ABS(amount)) as expenses FROM transactions 
+             WHERE amount < 0 AND date >= date('now', '-30 days') AND deleted_at IS NULL AND transfer_id IS NULL"
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        let income = income_row.income.unwrap_or(0.0);
+        let expenses = expense_row.expenses.unwrap_or(0.0);
+        let itt = if expenses > 0.0 { income / expenses } else { 999.0 };
+
+        // Calculate volatility (standard deviation of daily balances)
+        let volatility = self.calculate_volatility().await?;
+
+        // Calculate max drawdown
+        let drawdown = self.calculate_max_drawdown().await?;
+
+        Ok(FinancialMetrics {
+            balance,
+            burn_rate,
+            runway,
+            itt,
+            volatility,
+            drawdown,
+        })
+    }
+
+    // Debits of the last `days` days per category, largest first. A split
+    // transaction counts each split under its own category; transfers are left out.
+    // With `depth`, sub-categories are added into their ancestor at that level
+    pub async fn get_spending_by_category(&self, days: i32, depth: Option<usize>) -> Result<Vec<CategorySpending>> {
+        let since = format!("-{} days", days);
+        let rows = sqlx::query!(
+            "SELECT id, amount, category_encrypted FROM transactions 
+             WHERE deleted_at IS NULL AND transfer_id IS NULL AND date >= date('now', ?)",
+            since
+        ).fetch_all(&self.pool).await?;
+        
+        let ids: Vec<String> = rows.iter().map(|row| row.id.clone()).collect();
+        let mut splits = self.load_splits(&ids).await?;
+        
+        let mut totals: HashMap<String, f64> = HashMap::new();
+        for row in rows {
+            let category = self.security.decrypt(&row.category_encrypted, &self.encryption_key)?;
+            let row_splits = splits.remove(&row.id).unwrap_or_default();
+            for (category, amount) in category_amounts(&category, row.amount, &row_splits) {
+                if amount < 0.0 {
+                    let category = match depth {
+                        Some(depth) => category_rollup(&category, depth),
+                        None => category,
+                    };
+                    *totals.entry(category).or_insert(0.0) += spending(amount);
+                }
+            }
+        }
+        
+        let mut spending_by_category: Vec<CategorySpending> = totals
+            .into_iter()
+            .map(|(category, amount)| CategorySpending { category, amount })
+            .collect();
+        spending_by_category.sort_by(|a, b| b.amount.partial_cmp(&a.amount).unwrap_or(std::cmp::Ordering::Equal));
+        
+        Ok(spending_by_category)
+    }
+
+    pub async fn get_spending_by_payee(&self, days: i32) -> Result<Vec<PayeeSpending>> {
+        let since = format!("-{} days", days);
+        let rows = sqlx::query!(
+            "SELECT payee_id, SUM(amount) as total, COUNT(*) as count FROM transactions 
+             WHERE deleted_at IS NULL AND transfer_id IS NULL AND payee_id IS NOT NULL AND amount < 0 
+             AND date >= date('now', ?) 
+             GROUP BY payee_id",
+            since
+        ).fetch_all(&self.pool).await?;
+        
+        let mut conn = self.pool.acquire().await?;
+        let names: HashMap<String, String> = self.load_payees(&mut conn).await?
+            .into_iter()
+            .map(|payee| (payee.id, payee.name))
+            .collect();
+        
+        let mut spending_by_payee: Vec<PayeeSpending> = rows
+            .into_iter()
+            .filter_map(|row| {
+                let payee_id = row.payee_id?;
+                Some(PayeeSpending {
+                    payee: names.get(&payee_id).cloned().unwrap_or_default(),
+                    payee_id,
+                    amount: spending(row.total),
+                    transaction_count: row.count,
+                })
+            })
+            .collect();
+        spending_by_payee.sort_by(|a, b| b.amount.partial_cmp(&a.amount).unwrap_or(std::cmp::Ordering::Equal));
+        
+        Ok(spending_by_payee)
+    }
+
+    async fn calculate_volatility(&self) -> Result<f64> {
+        let balances = self.get_balance_history(30).await?;
+        if balances.len() < 2 {
+            return Ok(0.0);
+        }
+
+        let mean = balances.iter().map(|b| b.balance).sum::<f64>() / balances.len() as f64;
+        let variance = balances.iter()
+            .map(|b| (b.balance - mean).powi(2))
+            .sum::<f64>() / balances.len() as f64;
+        
+        Ok(variance.sqrt())
+    }
+
+    async fn calculate_max_drawdown(&self) -> Result<f64> {
+        let balances = self.get_balance_history(90).await?;
+        if balances.is_empty() {
+            return Ok(0.0);
+        }
+
+        let mut max_balance = balances[0].balance;
+        let mut max_drawdown = 0.0;
+
+        for point in &balances {
+            if point.balance > max_balance {
+                max_balance = point.balance;
+            }
+            let drawdown = max_balance - point.balance;
+            if drawdown > max_drawdown {
+                max_drawdown = drawdown;
+            }
+        }
+
+        Ok(-max_drawdown) // Return as negative value
+    }
+
+    pub async fn get_categories(&self) -> Result<Vec<Category>> {
+        let mut conn = self.pool.acquire().await?;
+        let mut categories = self.load_categories(&mut conn).await?;
+        categories.sort_by_key(|category| category.path.to_lowercase());
+        Ok(categories)
+    }
+
+    // Decrypts every category and builds its full path from its parents
+    async fn load_categories(&self, conn: &mut SqliteConnection) -> Result<Vec<Category>> {
+        let rows = sqlx::query!(
+            "SELECT id, name_encrypted, parent_id, color, icon FROM categories ORDER BY created_at ASC"
+        ).fetch_all(&mut *conn).await?;
+        
+        let mut categories = Vec::new();
+        for row in rows {
+            categories.push(Category {
+                id: row.id,
+                name: self.security.decrypt(&row.name_encrypted, &self.encryption_key)?,
+                parent_id: row.parent_id,
+                color: row.color,
+                icon: row.icon,
+                path: String::new(),
+            });
+        }
+        
+        let names: HashMap<String, (String, Option<String>)> = categories
+            .iter()
+            .map(|category| (category.id.clone(), (category.name.clone(), category.parent_id.clone())))
+            .collect();
+        for category in categories.iter_mut() {
+            let mut levels = vec![category.name.clone()];
+            let mut parent = category.parent_id.clone();
+            // The depth bound stops a corrupted parent chain from looping
+            while let Some((name, grand_parent)) = parent.and_then(|id| names.get(&id)) {
+                if levels.len() > names.len() {
+                    break;
+                }
+                levels.push(name.clone());
+                parent = grand_parent.clone();
+            }
+            levels.reverse();
+            category.path = levels.join(CATEGORY_PATH_SEPARATOR);
+        }
+        
+        Ok(categories)
+    }
+
+    pub async fn create_category(&self, category: &Category) -> Result<Category> {
+        let mut tx = self.pool.begin().await?;
+        let categories = self.load_categories(&mut tx).await?;
+        let id = if category.id.is_empty() { self.security.generate_secure_id() } else { category.id.clone() };
+        validate_category(category, &id, &categories)?;
+        
+        let encrypted_name = self.security.encrypt(category.name.trim(), &self.encryption_key)?;
+        sqlx::query!(
+            "INSERT INTO categories (id, name_encrypted, parent_id, color, icon) VALUES (?, ?, ?, ?, ?)",
+            id,
+            encrypted_name,
+            category.parent_id,
+            category.color,
+            category.icon
+        ).execute(&mut *tx).await?;
+        tx.commit().await?;
+        
+        self.get_category(&id).await
+    }
+
+    // Renaming or moving a category rewrites the transactions, splits,
+    // budgets, rules and import profiles filed under it or its sub-categories
+    // in the same transaction
+    pub async fn update_category(&self, category: &Category) -> Result<Category> {
+        let mut tx = self.pool.begin().await?;
+        let categories = self.load_categories(&mut tx).await?;
+        let old_path = categories
+            .iter()
+            .find(|existing| existing.id == category.id)
+            .map(|existing| existing.path.clone())
+            .ok_or_else(|| anyhow!("Catégorie introuvable: {}", category.id))?;
+        validate_category(category, &category.id, &categories)?;
+        
+        let encrypted_name = self.security.encrypt(category.name.trim(), &self.encryption_key)?;
+        sqlx::query!(
+            "UPDATE categories SET name_encrypted = ?, parent_id = ?, color = ?, icon = ? WHERE id = ?",
+            encrypted_name,
+            category.parent_id,
+            category.color,
+            category.icon,
+            category.id
+        ).execute(&mut *tx).await?;
+        
+        let new_path = self.load_categories(&mut tx).await?
+            .into_iter()
+            .find(|existing| existing.id == category.id)
+            .map(|existing| existing.path)
+            .unwrap_or_default();
+        if new_path != old_path {
+            self.rewrite_category_references(&mut tx, &old_path, &new_path).await?;
+        }
+        tx.commit().await?;
+        
+        self.get_category(&category.id).await
+    }
+
+    // Files everything under `source` into `target`. Sub-categories of the
+    // source move under the target, merging with same-named ones already there.
+    pub async fn merge_category(&self, source_id: &str, target_id: &str) -> Result<Category> {
+        let mut tx = self.pool.begin().await?;
+        let categories = self.load_categories(&mut tx).await?;
+        let find = |id: &str| categories.iter().find(|category| category.id == id);
+        let source = find(source_id).ok_or_else(|| anyhow!("Catégorie introuvable: {}", source_id))?;
+        let target = find(target_id).ok_or_else(|| anyhow!("Catégorie introuvable: {}", target_id))?;
+        if source.id == target.id || rebase_category(&target.path, &source.path, "").is_some() {
+            return Err(anyhow!("Impossible de fusionner une catégorie dans elle-même ou dans une sous-catégorie"));
+        }
+        
+        // Shallowest first, so a parent is mapped before its children
+        let mut moved: Vec<(&Category, String)> = categories
+            .iter()
+            .filter_map(|category| {
+                rebase_category(&category.path, &source.path, &target.path).map(|path| (category, path))
+            })
+            .collect();
+        moved.sort_by_key(|(category, _)| category.path.split(CATEGORY_PATH_SEPARATOR).count());
+        
+        let mut mapped: HashMap<String, String> = HashMap::new();
+        for (category, path) in moved {
+            let existing = categories
+                .iter()
+                .find(|other| other.id != category.id && other.path.to_lowercase() == path.to_lowercase());
+            match existing {
+                Some(existing) => {
+                    sqlx::query!("DELETE FROM categories WHERE id = ?", category.id)
+                        .execute(&mut *tx).await?;
+                    mapped.insert(category.id.clone(), existing.id.clone());
+                }
+                None => {
+                    let parent_id = category.parent_id.as_ref().and_then(|parent| mapped.get(parent)).cloned();
+                    sqlx::query!(
+                        "UPDATE categories SET parent_id = ? WHERE id = ?",
+                        parent_id,
+                        category.id
+                    ).execute(&mut *tx).await?;
+                    mapped.insert(category.id.clone(), category.id.clone());
+                }
+            }
+        }
+        
+        self.rewrite_category_references(&mut tx, &source.path, &target.path).await?;
+        tx.commit().await?;
+        
+        self.get_category(target_id).await
+    }
+
+    // Categories still referenced or with sub-categories are kept; merge them instead
+    pub async fn delete_category(&self, category_id: &str) -> Result<()> {
+        let category = self.get_category(category_id).await?;
+        
+        let children = sqlx::query!(
+            "SELECT COUNT(*) as count FROM categories WHERE parent_id = ?",
+            category_id
+        ).fetch_one(&self.pool).await?.count;
+        if children > 0 {
+            return Err(anyhow!("La catégorie contient {} sous-catégorie(s)", children));
+        }
+        
+        let category_index = self.category_index(&category.path)?;
+        let used = sqlx::query!(
+            "SELECT COUNT(*) as count FROM transactions 
+             WHERE category_index = ? OR id IN (SELECT transaction_id FROM transaction_splits WHERE category_index = ?)",
+            category_index,
+            category_index
+        ).fetch_one(&self.pool).await?.count.unwrap_or(0);
+        if used > 0 {
+            return Err(anyhow!("Catégorie utilisée par {} transaction(s)", used));
+        }
+        let budgets = sqlx::query!(
+            "SELECT COUNT(*) as count FROM budgets WHERE category_index = ?",
+            category_index
+        ).fetch_one(&self.pool).await?.count;
+        if budgets > 0 {
+            return Err(anyhow!("Catégorie utilisée par {} budget(s)", budgets));
+        }
+        
+        // Rules and import profiles would go on filing rows under it
+        let rules = self.get_rules().await?;
+        let rule = rules.iter().find(|rule| {
+            rule.actions.iter().any(|action| match action {
+                RuleAction::SetCategory { category: target } => is_in_category(target, &category.path),
+                _ => false,
+            })
+        });
+        if let Some(rule) = rule {
+            return Err(anyhow!("Catégorie utilisée par la règle {}", rule.name));
+        }
+        let profiles = self.get_import_profiles().await?;
+        if let Some(profile) = profiles.iter().find(|profile| is_in_category(&profile.mapping.default_category, &category.path)) {
+            return Err(anyhow!("Catégorie utilisée par le profil d'import {}", profile.name));
+        }
+        
+        sqlx::query!("DELETE FROM categories WHERE id = ?", category_id)
+            .execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn get_category(&self, category_id: &str) -> Result<Category> {
+        let mut conn = self.pool.acquire().await?;
+        self.load_categories(&mut conn).await?
+            .into_iter()
+            .find(|category| category.id == category_id)
+            .ok_or_else(|| anyhow!("Catégorie introuvable: {}", category_id))
+    }
+
+    // Categories are encrypted, so every row is decrypted and checked. Trashed
+    // transactions are rewritten too so they restore under the new name, and
+    // rules and import profiles keep filing rows under it.
+    async fn rewrite_category_references(&self, conn: &mut SqliteConnection, from: &str, to: &str) -> Result<()> {
+        let transactions = sqlx::query!("SELECT id, category_encrypted FROM transactions")
+            .fetch_all(&mut *conn).await?;
+        for row in transactions {
+            let category = self.security.decrypt(&row.category_encrypted, &self.encryption_key)?;
+            if let Some(category) = rebase_category(&category, from, to) {
+                let encrypted_category = self.security.encrypt(&category, &self.encryption_key)?;
+                let category_index = self.category_index(&category)?;
+                sqlx::query!(
+                    "UPDATE transactions SET category_encrypted = ?, category_index = ? WHERE id = ?",
+                    encrypted_category,
+                    category_index,
+                    row.id
+                ).execute(&mut *conn).await?;
+            }
+        }
+        
+        let splits = sqlx::query!("SELECT id, category_encrypted FROM transaction_splits")
+            .fetch_all(&mut *conn).await?;
+        for row in splits {
+            let category = self.security.decrypt(&row.category_encrypted, &self.encryption_key)?;
+            if let Some(category) = rebase_category(&category, from, to) {
+                let encrypted_category = self.security.encrypt(&category, &self.encryption_key)?;
+                let category_index = self.category_index(&category)?;
+                sqlx::query!(
+                    "UPDATE transaction_splits SET category_encrypted = ?, category_index = ? WHERE id = ?",
+                    encrypted_category,
+                    category_index,
+                    row.id
+                ).execute(&mut *conn).await?;
+            }
+        }
+        
+        // When the destination already has a budget for the same period, that
+        // one is kept and the moved one dropped
+        let budgets = sqlx::query!("SELECT id, category_encrypted, period FROM budgets")
+            .fetch_all(&mut *conn).await?;
+        let mut decrypted = Vec::new();
+        for row in budgets {
+            let category = self.security.decrypt(&row.category_encrypted, &self.encryption_key)?;
+            decrypted.push((row.id, category, row.period));
+        }
+        for (id, category, period) in &decrypted {
+            let new_category = match rebase_category(category, from, to) {
+                Some(new_category) => new_category,
+                None => continue,
+            };
+            let taken = decrypted.iter().any(|(other_id, other_category, other_period)| {
+                other_id != id && other_period == period && other_category.trim().to_lowercase() == new_category.to_lowercase()
+            });
+            if taken {
+                sqlx::query!("DELETE FROM budgets WHERE id = ?", id)
+                    .execute(&mut *conn).await?;
+            } else {
+                let encrypted_category = self.security.encrypt(&new_category, &self.encryption_key)?;
+                let category_index = self.category_index(&new_category)?;
+                sqlx::query!(
+                    "UPDATE budgets SET category_encrypted = ?, category_index = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+                    encrypted_category,
+                    category_index,
+                    id
+                ).execute(&mut *conn).await?;
+            }
+        }
+        
+        for mut rule in self.load_rules(&mut *conn).await? {
+            let mut rebased = false;
+            for action in rule.actions.iter_mut() {
+                if let RuleAction::SetCategory { category } = action {
+                    if let Some(new_category) = rebase_category(category, from, to) {
+                        *category = new_category;
+                        rebased = true;
+                    }
+                }
+            }
+            if rebased {
+                self.write_rule(&mut *conn, &rule).await?;
+            }
+        }
+        
+        let pattern = format!("{}%", IMPORT_PROFILE_PREFIX);
+        let profiles = sqlx::query!("SELECT key, value_encrypted FROM settings WHERE key LIKE ?", pattern)
+            .fetch_all(&mut *conn).await?;
+        for row in profiles {
+            let json = self.security.decrypt(&row.value_encrypted, &self.encryption_key)?;
+            let mut profile: ImportProfile = serde_json::from_str(&json)?;
+            if let Some(new_category) = rebase_category(&profile.mapping.default_category, from, to) {
+                profile.mapping.default_category = new_category;
+                self.set_setting_with(&mut *conn, &row.key, &profile).await?;
+            }
+        }
+        
+        // The category model learned the old names
+        if let Some(mut model) = self.get_setting_with::<CategoryModel>(&mut *conn, CATEGORY_MODEL_KEY).await? {
+            model.rebase(from, to);
+            self.set_setting_with(conn, CATEGORY_MODEL_KEY, &model).await?;
+        }
+        
+        Ok(())
+    }
+
+    pub async fn get_rules(&self) -> Result<Vec<Rule>> {
+        let mut conn = self.pool.acquire().await?;
+        self.load_rules(&mut conn).await
+    }
+
+    async fn load_rules(&self, conn: &mut SqliteConnection) -> Result<Vec<Rule>> {
+        let rows = sqlx::query!(
+            "SELECT id, priority, rule_encrypted FROM rules ORDER BY priority ASC, created_at ASC"
+        ).fetch_all(&mut *conn).await?;
+        
+        let mut rules = Vec::new();
+        for row in rows {
+            let json = self.security.decrypt(&row.rule_encrypted, &self.encryption_key)?;
+            let rule: Rule = serde_json::from_str(&json)?;
+            rules.push(Rule {
+                id: row.id,
+                priority: row.priority as i32,
+                ..rule
+            });
+        }
+        
+        Ok(rules)
+    }
+
+    // Creates the rule when its ID is empty or unknown, replaces it otherwise.
+    // Account conditions are stored against the account ID like transactions.
+    pub async fn save_rule(&self, rule: &Rule) -> Result<Rule> {
+        rules::validate(rule)?;
+        for action in &rule.actions {
+            if let RuleAction::SetCategory { category } = action {
+                if !self.category_exists(category.trim()).await? {
+                    return Err(anyhow!("Catégorie inconnue: {}", category.trim()));
+                }
+            }
+        }
+        let mut rule = rule.clone();
+        if rule.id.is_empty() {
+            rule.id = self.security.generate_secure_id();
+        }
+        for condition in rule.conditions.iter_mut() {
+            if let RuleCondition::Account { account } = condition {
+                *account = self.resolve_account(account).await?;
+            }
+        }
+        
+        let mut conn = self.pool.acquire().await?;
+        self.write_rule(&mut conn, &rule).await?;
+        Ok(rule)
+    }
+
+    async fn write_rule(&self, conn: &mut SqliteConnection, rule: &Rule) -> Result<()> {
+        let encrypted_rule = self.security.encrypt(&serde_json::to_string(rule)?, &self.encryption_key)?;
+        sqlx::query!(
+            "INSERT INTO rules (id, priority, rule_encrypted) VALUES (?, ?, ?) 
+             ON CONFLICT(id) DO UPDATE SET priority = excluded.priority, rule_encrypted = excluded.rule_encrypted",
+            rule.id,
+            rule.priority,
+            encrypted_rule
+        ).execute(&mut *conn).await?;
+        
+        Ok(())
+    }
+
+    // Transactions keep the changes the rule made but no longer point to it
+    pub async fn delete_rule(&self, rule_id: &str) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let result = sqlx::query!("DELETE FROM rules WHERE id = ?", rule_id)
+            .execute(&mut *tx).await?;
+        if result.rows_affected() == 0 {
+            return Err(anyhow!("Règle introuvable: {}", rule_id));
+        }
+        sqlx::query!("UPDATE transactions SET rule_id = NULL WHERE rule_id = ?", rule_id)
+            .execute(&mut *tx).await?;
+        tx.commit().await?;
+        
+        Ok(())
+    }
+
+    pub async fn rule_engine(&self) -> Result<RuleEngine> {
+        RuleEngine::new(self.get_rules().await?)
+    }
+
+    // Runs the rules over the given transactions, or over every transaction
+    // when `ids` is None. Linked transfers are left alone; rows a rule marks
+    // as transfers are paired with their other leg when it can be found.
+    pub async fn apply_rules(&self, ids: Option<Vec<String>>) -> Result<Vec<RuleApplication>> {
+        let engine = self.rule_engine().await?;
+        let ids = match ids {
+            Some(ids) => ids,
+            None => sqlx::query!("SELECT id FROM transactions WHERE deleted_at IS NULL ORDER BY date ASC")
+                .fetch_all(&self.pool).await?
+                .into_iter()
+                .map(|row| row.id)
+                .collect(),
+        };
+        
+        let mut known = self.get_payees().await?;
+        let mut applications = Vec::new();
+        let mut marked_transfers = Vec::new();
+        for id in ids {
+            let original = match self.get_transaction(&id).await? {
+                Some(transaction) if transaction.transfer_id.is_none() => transaction,
+                _ => continue,
+            };
+            let mut transaction = original.clone();
+            let rule = match engine.apply(&mut transaction) {
+                Some(rule) => rule,
+                None => continue,
+            };
+            if let Some(payee) = rules::renamed_payee(rule) {
+                transaction.payee_id = Some(self.rule_payee(payee, &mut known).await?);
+            }
+            
+            let changed = transaction.category != original.category
+                || transaction.tags != original.tags
+                || transaction.rule_id != original.rule_id
+                || transaction.payee_id != original.payee_id;
+            if changed {
+                self.update_transaction(&transaction).await?;
+            }
+            if transaction.category == TRANSFER_CATEGORY {
+                marked_transfers.push(transaction.id.clone());
+            }
+            applications.push(RuleApplication {
+                transaction_id: transaction.id,
+                rule_id: rule.id.clone(),
+                rule_name: rule.name.clone(),
+            });
+        }
+        self.pair_transfer_ids(&marked_transfers).await?;
+        
+        Ok(applications)
+    }
+
+    pub async fn get_payees(&self) -> Result<Vec<Payee>> {
+        let mut conn = self.pool.acquire().await?;
+        let mut payees = self.load_payees(&mut conn).await?;
+        payees.sort_by_key(|payee| payee.name.to_lowercase());
+        Ok(payees)
+    }
+
+    async fn load_payees(&self, conn: &mut SqliteConnection) -> Result<Vec<Payee>> {
+        let rows = sqlx::query!("SELECT id, name_encrypted, aliases_encrypted FROM payees")
+            .fetch_all(&mut *conn).await?;
+        
+        let mut payees = Vec::new();
+        for row in rows {
+            let aliases = self.security.decrypt(&row.aliases_encrypted, &self.encryption_key)?;
+            payees.push(Payee {
+                id: row.id,
+                name: self.security.decrypt(&row.name_encrypted, &self.encryption_key)?,
+                aliases: serde_json::from_str(&aliases)?,
+            });
+        }
+        
+        Ok(payees)
+    }
+
+    // Maps a description to its payee, creating one named after the cleaned
+    // description when no alias matches. None when nothing is left of it.
+    async fn assign_payee(&self, conn: &mut SqliteConnection, description: &str) -> Result<Option<String>> {
+        let mut known = self.load_payees(&mut *conn).await?;
+        self.assign_payee_from(conn, description, &mut known).await
+    }
+
+    // Same as assign_payee with the payees already loaded; created payees are
+    // added to `known`
+    async fn assign_payee_from(&self, conn: &mut SqliteConnection, description: &str, known: &mut Vec<Payee>) -> Result<Option<String>> {
+        let normalized = payees::normalize_payee(description);
+        if normalized.is_empty() {
+            return Ok(None);
+        }
+        if let Some(payee) = payees::find_payee(&normalized, known) {
+            return Ok(Some(payee.id.clone()));
+        }
+        
+        let payee = Payee {
+            id: self.security.generate_secure_id(),
+            name: payees::display_name(&normalized),
+            aliases: vec![normalized],
+        };
+        self.write_payee(&mut *conn, &payee).await?;
+        let payee_id = payee.id.clone();
+        known.push(payee);
+        Ok(Some(payee_id))
+    }
+
+    // The payee a rule renames transactions to: the payee of that name or
+    // alias, or a new one named as the rule says. Created payees are added
+    // to `known`.
+    pub async fn rule_payee(&self, name: &str, known: &mut Vec<Payee>) -> Result<String> {
+        let name = name.trim();
+        let normalized = payees::normalize_payee(name);
+        let existing = known.iter().find(|payee| {
+            payee.name.to_lowercase() == name.to_lowercase()
+                || (!normalized.is_empty() && payee.aliases.contains(&normalized))
+        });
+        if let Some(payee) = existing {
+            return Ok(payee.id.clone());
+        }
+        
+        let payee = Payee {
+            id: self.security.generate_secure_id(),
+            name: name.to_string(),
+            aliases: if normalized.is_empty() { Vec::new() } else { vec![normalized] },
+        };
+        let mut conn = self.pool.acquire().await?;
+        self.write_payee(&mut conn, &payee).await?;
+        let payee_id = payee.id.clone();
+        known.push(payee);
+        Ok(payee_id)
+    }
+
+    async fn write_payee(&self, conn: &mut SqliteConnection, payee: &Payee) -> Result<()> {
+        let encrypted_name = self.security.encrypt(payee.name.trim(), &self.encryption_key)?;
+        let encrypted_aliases = self.security.encrypt(&serde_json::to_string(&payee.aliases)?, &self.encryption_key)?;
+        
+        sqlx::query!(
+            "INSERT INTO payees (id, name_encrypted, aliases_encrypted) VALUES (?, ?, ?) 
+             ON CONFLICT(id) DO UPDATE SET name_encrypted = excluded.name_encrypted, aliases_encrypted = excluded.aliases_encrypted",
+            payee.id,
+            encrypted_name,
+            encrypted_aliases
+        ).execute(&mut *conn).await?;
+        
+        Ok(())
+    }
+
+    // Renames a payee or changes its aliases. Aliases are normalised like
+    // descriptions and only affect transactions added afterwards.
+    pub async fn update_payee(&self, payee: &Payee) -> Result<Payee> {
+        if payee.name.trim().is_empty() {
+            return Err(anyhow!("Le nom du bénéficiaire est obligatoire"));
+        }
+        
+        let mut tx = self.pool.begin().await?;
+        let known = self.load_payees(&mut tx).await?;
+        if !known.iter().any(|other| other.id == payee.id) {
+            return Err(anyhow!("Bénéficiaire introuvable: {}", payee.id));
+        }
+        
+        let mut aliases: Vec<String> = payee.aliases
+            .iter()
+            .map(|alias| payees::normalize_payee(alias))
+            .filter(|alias| !alias.is_empty())
+            .collect();
+        aliases.sort();
+        aliases.dedup();
+        for alias in &aliases {
+            if let Some(other) = known.iter().find(|other| other.id != payee.id && other.aliases.contains(alias)) {
+                return Err(anyhow!("L'alias {} appartient déjà à {}", alias, other.name));
+            }
+        }
+        
+        let payee = Payee {
+            id: payee.id.clone(),
+            name: payee.name.trim().to_string(),
+            aliases,
+        };
+        self.write_payee(&mut tx, &payee).await?;
+        tx.commit().await?;
+        
+        Ok(payee)
+    }
+
+    // Moves the source's transactions and aliases to the target and removes
+    // the source, so "Carrefour Paris" and "Carrefour City" become one payee
+    pub async fn merge_payees(&self, source_id: &str, target_id: &str) -> Result<Payee> {
+        if source_id == target_id {
+            return Err(anyhow!("Impossible de fusionner un bénéficiaire avec lui-même"));
+        }
+        
+        let mut tx = self.pool.begin().await?;
+        let known = self.load_payees(&mut tx).await?;
+        let source = known.iter().find(|payee| payee.id == source_id)
+            .ok_or_else(|| anyhow!("Bénéficiaire introuvable: {}", source_id))?;
+        let mut target = known.iter().find(|payee| payee.id == target_id)
+            .ok_or_else(|| anyhow!("Bénéficiaire introuvable: {}", target_id))?
+            .clone();
+        
+        for alias in &source.aliases {
+            if !target.aliases.contains(alias) {
+                target.aliases.push(alias.clone());
+            }
+        }
+        self.write_payee(&mut tx, &target).await?;
+        sqlx::query!(
+            "UPDATE transactions SET payee_id = ? WHERE payee_id = ?",
+            target_id,
+            source_id
+        ).execute(&mut *tx).await?;
+        sqlx::query!("DELETE FROM payees WHERE id = ?", source_id)
+            .execute(&mut *tx).await?;
+        tx.commit().await?;
+        
+        Ok(target)
+    }
+
+    // Payees still referenced, trashed transactions included, are kept
+    pub async fn delete_payee(&self, payee_id: &str) -> Result<()> {
+        let used = sqlx::query!(
+            "SELECT COUNT(*) as count FROM transactions WHERE payee_id = ?",
+            payee_id
+        ).fetch_one(&self.pool).await?.count;
+        if used > 0 {
+            return Err(anyhow!("Bénéficiaire utilisé par {} transaction(s)", used));
+        }
+        
+        let result = sqlx::query!("DELETE FROM payees WHERE id = ?", payee_id)
+            .execute(&self.pool).await?;
+        if result.rows_affected() == 0 {
+            return Err(anyhow!("Bénéficiaire introuvable: {}", payee_id));
+        }
+        Ok(())
+    }
+
+    // Learns every categorised transaction from scratch and replaces the
+    // stored model. Returns how many transactions it learned from.
+    pub async fn retrain_category_model(&self) -> Result<usize> {
+        let rows = sqlx::query!(
+            "SELECT description_encrypted, amount, category_encrypted FROM transactions 
+             WHERE deleted_at IS NULL AND transfer_id IS NULL"
+        ).fetch_all(&self.pool).await?;
+        
+        let mut model = CategoryModel::default();
+        let mut learned = 0;
+        for row in rows {
+            let category = self.security.decrypt(&row.category_encrypted, &self.encryption_key)?;
+            if !classifier::is_trainable(&category) {
+                continue;
+            }
+            let description = self.security.decrypt(&row.description_encrypted, &self.encryption_key)?;
+            model.learn(&description, row.amount, &category);
+            learned += 1;
+        }
+        
+        self.set_setting(CATEGORY_MODEL_KEY, &model).await?;
+        Ok(learned)
+    }
+
+    // The model lives encrypted in settings; the first use trains it
+    async fn category_model(&self) -> Result<CategoryModel> {
+        if let Some(model) = self.get_setting(CATEGORY_MODEL_KEY).await? {
+            return Ok(model);
+        }
+        self.retrain_category_model().await?;
+        Ok(self.get_setting(CATEGORY_MODEL_KEY).await?.unwrap_or_default())
+    }
+
+    // Takes the `forget` examples out of the stored model and teaches it the
+    // `learn` ones, inside the caller's transaction. Nothing happens before
+    // the model is first trained: training will read the current rows.
+    async fn relearn_category(&self, conn: &mut SqliteConnection, forget: &[TrainingExample], learn: &[TrainingExample]) -> Result<()> {
+        if forget == learn {
+            return Ok(());
+        }
+        let mut model: CategoryModel = match self.get_setting_with(&mut *conn, CATEGORY_MODEL_KEY).await? {
+            Some(model) => model,
+            None => return Ok(()),
+        };
+        for example in forget {
+            model.forget(&example.description, example.amount, &example.category);
+        }
+        for example in learn {
+            model.learn(&example.description, example.amount, &example.category);
+        }
+        self.set_setting_with(conn, CATEGORY_MODEL_KEY, &model).await
+    }
+
+    // What a stored transaction taught the model; None for transfers, which
+    // the model never learns
+    async fn stored_training_example(&self, conn: &mut SqliteConnection, transaction_id: &str) -> Result<Option<TrainingExample>> {
+        let row = sqlx::query!(
+            "SELECT description_encrypted, amount, category_encrypted FROM transactions WHERE id = ? AND transfer_id IS NULL",
+            transaction_id
+        ).fetch_optional(&mut *conn).await?;
+        
+        match row {
+            Some(row) => Ok(Some(TrainingExample {
+                description: self.security.decrypt(&row.description_encrypted, &self.encryption_key)?,
+                amount: row.amount,
+                category: self.security.decrypt(&row.category_encrypted, &self.encryption_key)?,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    // The live, non-transfer rows of an import batch, as the model learned them
+    async fn batch_training_examples(&self, conn: &mut SqliteConnection, batch_id: &str) -> Result<Vec<TrainingExample>> {
+        let rows = sqlx::query!(
+            "SELECT description_encrypted, amount, category_encrypted FROM transactions 
+             WHERE batch_id = ? AND deleted_at IS NULL AND transfer_id IS NULL",
+            batch_id
+        ).fetch_all(&mut *conn).await?;
+        
+        let mut examples = Vec::new();
+        for row in rows {
+            examples.push(TrainingExample {
+                description: self.security.decrypt(&row.description_encrypted, &self.encryption_key)?,
+                amount: row.amount,
+                category: self.security.decrypt(&row.category_encrypted, &self.encryption_key)?,
+            });
+        }
+        
+        Ok(examples)
+    }
+
+    pub async fn suggest_category(&self, description: &str, amount: f64) -> Result<Option<CategorySuggestion>> {
+        let model = self.category_model().await?;
+        Ok(model.suggest(description, amount)
+            .filter(|(_, confidence)| *confidence >= classifier::MIN_CONFIDENCE)
+            .map(|(category, confidence)| CategorySuggestion {
+                transaction_id: None,
+                category,
+                confidence,
+            }))
+    }
+
+    // Suggestions for every uncategorised transaction, most confident first
+    pub async fn get_category_suggestions(&self) -> Result<Vec<CategorySuggestion>> {
+        let model = self.category_model().await?;
+        let category_index = self.category_index(DEFAULT_CATEGORY)?;
+        let rows = sqlx::query!(
+            "SELECT id, description_encrypted, amount FROM transactions 
+             WHERE deleted_at IS NULL AND transfer_id IS NULL AND category_index = ?",
+            category_index
+        ).fetch_all(&self.pool).await?;
+        
+        let mut suggestions = Vec::new();
+        for row in rows {
+            let description = self.security.decrypt(&row.description_encrypted, &self.encryption_key)?;
+            if let Some((category, confidence)) = model.suggest(&description, row.amount) {
+                if confidence >= classifier::MIN_CONFIDENCE {
+                    suggestions.push(CategorySuggestion {
+                        transaction_id: Some(row.id),
+                        category,
+                        confidence,
+                    });
+                }
+            }
+        }
+        suggestions.sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap_or(std::cmp::Ordering::Equal));
+        
+        Ok(suggestions)
+    }
+
+    // Spent is what the budget's category and its sub-categories, split lines
+    // included, have debited during the period containing today. Transfers
+    // never count.
+    pub async fn get_budgets(&self) -> Result<Vec<Budget>> {
+        let rows = sqlx::query!(
+            "SELECT id, category_encrypted, amount, period, start_day FROM budgets"
+        ).fetch_all(&self.pool).await?;
+        
+        let today = Utc::now().date_naive();
+        let mut budgets = Vec::new();
+        let mut windows = Vec::new();
+        for row in rows {
+            let start_day = row.start_day as u32;
+            let (start, end) = budget_period(&row.period, today, start_day)
+                .ok_or_else(|| anyhow!("Période de budget inconnue: {}", row.period))?;
+            budgets.push(Budget {
+                id: row.id,
+                category: self.security.decrypt(&row.category_encrypted, &self.encryption_key)?,
+                amount: row.amount,
+                spent: 0.0,
+                period: row.period,
+                start_day,
+                period_start: Some(start.format("%Y-%m-%d").to_string()),
+                period_end: Some((end - chrono::Duration::days(1)).format("%Y-%m-%d").to_string()),
+            });
+            windows.push((start.format("%Y-%m-%d").to_string(), end.format("%Y-%m-%d").to_string()));
+        }
+        
+        let earliest = match windows.iter().map(|(start, _)| start).min() {
+            Some(earliest) => earliest.clone(),
+            None => return Ok(budgets),
+        };
+        let latest = windows.iter().map(|(_, end)| end).max().cloned().unwrap_or_default();
+        let transactions = sqlx::query!(
+            "SELECT id, amount, date, category_encrypted FROM transactions 
+             WHERE deleted_at IS NULL AND transfer_id IS NULL AND date >= ? AND date < ?",
+            earliest,
+            latest
+        ).fetch_all(&self.pool).await?;
+        
+        let ids: Vec<String> = transactions.iter().map(|row| row.id.clone()).collect();
+        let mut splits = self.load_splits(&ids).await?;
+        for row in transactions {
+            let category = self.security.decrypt(&row.category_encrypted, &self.encryption_key)?;
+            let row_splits = splits.remove(&row.id).unwrap_or_default();
+            for (category, amount) in category_amounts(&category, row.amount, &row_splits) {
+                for (budget, (start, end)) in budgets.iter_mut().zip(&windows) {
+                    if row.date >= *start && row.date < *end && is_in_category(&category, &budget.category) {
+                        budget.spent += spending(amount);
+                    }
+                }
+            }
+        }
+        
+        Ok(budgets)
+    }
+
+    // Creates the budget when its ID is empty or unknown, replaces it
+    // otherwise. The spent figure sent by the client is ignored.
+    pub async fn set_budget(&self, budget: &Budget) -> Result<Budget> {
+        let category = budget.category.trim();
+        if !(budget.amount.is_finite() && budget.amount > 0.0) {
+            return Err(anyhow!("Le montant du budget doit être positif"));
+        }
+        if !BUDGET_PERIODS.contains(&budget.period.as_str()) {
+            return Err(anyhow!("Période de budget inconnue: {}", budget.period));
+        }
+        let max_start_day = if budget.period == "weekly" { 7 } else { 31 };
+        if budget.start_day < 1 || budget.start_day > max_start_day {
+            return Err(anyhow!("Jour de début invalide pour un budget {}: {}", budget.period, budget.start_day));
+        }
+        if !self.category_exists(category).await? {
+            return Err(anyhow!("Catégorie inconnue: {}", category));
+        }
+        
+        let id = if budget.id.is_empty() { self.security.generate_secure_id() } else { budget.id.clone() };
+        let category_index = self.category_index(category)?;
+        let taken = sqlx::query!(
+            "SELECT id FROM budgets WHERE category_index = ? AND period = ? AND id != ?",
+            category_index,
+            budget.period,
+            id
+        ).fetch_optional(&self.pool).await?;
+        if taken.is_some() {
+            return Err(anyhow!("Un budget {} existe déjà pour {}", budget.period, category));
+        }
+        
+        let encrypted_category = self.security.encrypt(category, &self.encryption_key)?;
+        sqlx::query!(
+            "INSERT INTO budgets (id, category_encrypted, category_index, amount, period, start_day, updated_at) 
+             VALUES (?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP) 
+             ON CONFLICT(id) DO UPDATE SET category_encrypted = excluded.category_encrypted, 
+             category_index = excluded.category_index, amount = excluded.amount, 
+             period = excluded.period, start_day = excluded.start_day, updated_at = excluded.updated_at",
+            id,
+            encrypted_category,
+            category_index,
+            budget.amount,
+            budget.period,
+            budget.start_day
+        ).execute(&self.pool).await?;
+        
+        self.get_budgets().await?
+            .into_iter()
+            .find(|saved| saved.id == id)
+            .ok_or_else(|| anyhow!("Budget introuvable: {}", id))
+    }
+
+    pub async fn delete_budget(&self, budget_id: &str) -> Result<()> {
+        let result = sqlx::query!("DELETE FROM budgets WHERE id = ?", budget_id)
+            .execute(&self.pool).await?;
+        
+        if result.rows_affected() == 0 {
+            return Err(anyhow!("Budget introuvable: {}", budget_id));
+        }
+        Ok(())
+    }
+
+    // A category exists when it is managed in the categories table or
+    // already used by a transaction or split
+    async fn category_exists(&self, category: &str) -> Result<bool> {
+        if category.is_empty() {
+            return Ok(false);
+        }
+        let managed = self.get_categories().await?
+            .iter()
+            .any(|existing| existing.path.to_lowercase() == category.to_lowercase());
+        if managed {
+            return Ok(true);
+        }
+        
+        let category_index = self.category_index(category)?;
+        let used = sqlx::query!(
+            "SELECT EXISTS (
+                SELECT 1 FROM transactions WHERE category_index = ? AND deleted_at IS NULL 
+                UNION ALL SELECT 1 FROM transaction_splits WHERE category_index = ?
+             ) as used",
+            category_index,
+            category_index
+        ).fetch_one(&self.pool).await?.used;
+        
+        Ok(used.unwrap_or(0) != 0)
+    }
+
+    pub async fn resolve_account(&self, account: &str) -> Result<String> {
+        let mut conn = self.pool.acquire().await?;
+        self.resolve_account_with(&mut conn, account).await
+    }
+
+    async fn resolve_account_with(&self, conn: &mut SqliteConnection, account: &str) -> Result<String> {
+        self.load_account_directory(conn).await?.resolve(account)
+    }
+
+    // Decrypts the account names and bank identifiers once, so an import can
+    // resolve all of its rows against them
+    pub async fn account_directory(&self) -> Result<AccountDirectory> {
+        let mut conn = self.pool.acquire().await?;
+        self.load_account_directory(&mut conn).await
+    }
+
+    async fn load_account_directory(&self, conn: &mut SqliteConnection) -> Result<AccountDirectory> {
+        let rows = sqlx::query!("SELECT id, name_encrypted, bank_identifier_encrypted FROM accounts")
+            .fetch_all(&mut *conn).await?;
+        
+        let mut accounts = Vec::new();
+        for row in rows {
+            let name = self.security.decrypt(&row.name_encrypted, &self.encryption_key)?;
+            let bank_identifier = match &row.bank_identifier_encrypted {
+                Some(bank_identifier) => Some(self.security.decrypt(bank_identifier, &self.encryption_key)?),
+                None => None,
+            };
+            accounts.push(AccountKey::new(&row.id, &name, bank_identifier.as_deref()));
+        }
+        
+        Ok(AccountDirectory { accounts })
+    }
+
+    pub async fn get_accounts(&self) -> Result<Vec<Account>> {
+        let rows = sqlx::query!(
+            "SELECT id FROM accounts ORDER BY created_at ASC"
+        ).fetch_all(&self.pool).await?;
+        
+        let mut accounts = Vec::new();
+        for row in rows {
+            accounts.push(self.get_account(&row.id).await?);
+        }
+        
+        Ok(accounts)
+    }
+
+    // The balance is always computed from the transactions; the legacy balance
+    // column is left unused
+    pub async fn get_account(&self, account_id: &str) -> Result<Account> {
+        let row = sqlx::query!(
+            "SELECT id, name_encrypted, type as account_type, opening_balance, opening_date, bank_identifier_encrypted 
+             FROM accounts WHERE id = ?",
+            account_id
+        ).fetch_optional(&self.pool).await?
+            .ok_or_else(|| anyhow!("Compte introuvable: {}", account_id))?;
+        let opening_balance = row.opening_balance.unwrap_or(0.0);
+        let opening_date = row.opening_date.unwrap_or_default();
+        let bank_identifier = match &row.bank_identifier_encrypted {
+            Some(bank_identifier) => Some(self.security.decrypt(bank_identifier, &self.encryption_key)?),
+            None => None,
+        };
+        
+        let movements = sqlx::query!(
+            "SELECT SUM(amount) as total FROM transactions 
+             WHERE account = ? AND deleted_at IS NULL AND date >= ?",
+            account_id,
+            opening_date
+        ).fetch_one(&self.pool).await?.total.unwrap_or(0.0);
+        
+        Ok(Account {
+            id: row.id,
+            name: self.security.decrypt(&row.name_encrypted, &self.encryption_key)?,
+            account_type: AccountType::from_name(&row.account_type)
+                .ok_or_else(|| anyhow!("Type de compte inconnu: {}", row.account_type))?,
+            opening_balance,
+            opening_date,
+            bank_identifier,
+            balance: opening_balance + movements,
+        })
+    }
+
+    // Existing transactions and rule conditions filed under the new account's
+    // name or bank identifier are moved onto it
+    pub async fn create_account(&self, account: &Account) -> Result<Account> {
+        let (opening_date, bank_identifier) = self.validate_account(account).await?;
+        let id = if account.id.is_empty() { self.security.generate_secure_id() } else { account.id.clone() };
+        let encrypted_name = self.security.encrypt(account.name.trim(), &self.encryption_key)?;
+        let encrypted_bank_identifier = match &bank_identifier {
+            Some(bank_identifier) => Some(self.security.encrypt(bank_identifier, &self.encryption_key)?),
+            None => None,
+        };
+        let account_type = account.account_type.as_str();
+        
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            "INSERT INTO accounts (id, name_encrypted, type, opening_balance, opening_date, bank_identifier_encrypted) VALUES (?, ?, ?, ?, ?, ?)",
+            id,
+            encrypted_name,
+            account_type,
+            account.opening_balance,
+            opening_date,
+            encrypted_bank_identifier
+        ).execute(&mut *tx).await?;
+        self.relink_account(&mut tx, &AccountKey::new(&id, &account.name, bank_identifier.as_deref())).await?;
+        tx.commit().await?;
+        
+        self.get_account(&id).await
+    }
+
+    pub async fn update_account(&self, account: &Account) -> Result<Account> {
+        self.get_account(&account.id).await?;
+        let (opening_date, bank_identifier) = self.validate_account(account).await?;
+        let encrypted_name = self.security.encrypt(account.name.trim(), &self.encryption_key)?;
+        let encrypted_bank_identifier = match &bank_identifier {
+            Some(bank_identifier) => Some(self.security.encrypt(bank_identifier, &self.encryption_key)?),
+            None => None,
+        };
+        let account_type = account.account_type.as_str();
+        
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            "UPDATE accounts SET name_encrypted = ?, type = ?, opening_balance = ?, opening_date = ?, bank_identifier_encrypted = ? WHERE id = ?",
+            encrypted_name,
+            account_type,
+            account.opening_balance,
+            opening_date,
+            encrypted_bank_identifier,
+            account.id
+        ).execute(&mut *tx).await?;
+        self.relink_account(&mut tx, &AccountKey::new(&account.id, &account.name, bank_identifier.as_deref())).await?;
+        tx.commit().await?;
+        
+        self.get_account(&account.id).await
+    }
+
+    // Free-text accounts left on transactions and rule conditions from before
+    // accounts existed are moved onto the account they resolve to
+    async fn relink_account(&self, conn: &mut SqliteConnection, account: &AccountKey) -> Result<()> {
+        let stored = sqlx::query!(
+            "SELECT DISTINCT account FROM transactions WHERE account != ?",
+            account.id
+        ).fetch_all(&mut *conn).await?;
+        for row in stored {
+            if account.matches(&row.account) {
+                sqlx::query!(
+                    "UPDATE transactions SET account = ? WHERE account = ?",
+                    account.id,
+                    row.account
+                ).execute(&mut *conn).await?;
+            }
+        }
+        
+        for mut rule in self.load_rules(&mut *conn).await? {
+            let mut relinked = false;
+            for condition in rule.conditions.iter_mut() {
+                if let RuleCondition::Account { account: value } = condition {
+                    if *value != account.id && account.matches(value) {
+                        *value = account.id.clone();
+                        relinked = true;
+                    }
+                }
+            }
+            if relinked {
+                self.write_rule(&mut *conn, &rule).await?;
+            }
+        }
+        
+        Ok(())
+    }
+
+    // Accounts still holding transactions, trashed ones included, are kept
+    pub async fn delete_account(&self, account_id: &str) -> Result<()> {
+        self.get_account(account_id).await?;
+        
+        let used = sqlx::query!(
+            "SELECT COUNT(*) as count FROM transactions WHERE account = ?",
+            account_id
+        ).fetch_one(&self.pool).await?.count;
+        if used > 0 {
+            return Err(anyhow!("Compte utilisé par {} transaction(s)", used));
+        }
+        
+        sqlx::query!("DELETE FROM accounts WHERE id = ?", account_id)
+            .execute(&self.pool).await?;
+        Ok(())
+    }
+
+    // Returns the normalised opening date and bank identifier
+    async fn validate_account(&self, account: &Account) -> Result<(String, Option<String>)> {
+        let name = account.name.trim();
+        if name.is_empty() {
+            return Err(anyhow!("Le nom du compte est obligatoire"));
+        }
+        let bank_identifier = account.bank_identifier
+            .as_deref()
+            .map(normalize_bank_identifier)
+            .filter(|bank_identifier| !bank_identifier.is_empty());
+        
+        for other in self.get_accounts().await? {
+            if other.id == account.id {
+                continue;
+            }
+            if other.name.trim().eq_ignore_ascii_case(name) {
+                return Err(anyhow!("Un compte porte déjà ce nom: {}", name));
+            }
+            if bank_identifier.is_some() && other.bank_identifier == bank_identifier {
+                return Err(anyhow!("Identifiant bancaire déjà utilisé par le compte {}", other.name));
+            }
+        }
+        
+        Ok((parse_date(&account.opening_date)?, bank_identifier))
+    }
+
+    // Daily balance of one account over the last `days` days, starting from
+    // the balance carried into the period
+    pub async fn get_account_balance_history(&self, account_id: &str, days: i32) -> Result<Vec<BalancePoint>> {
+        let account = self.get_account(account_id).await?;
+        let window_start = (Utc::now().date_naive() - chrono::Duration::days(days as i64))
+            .format("%Y-%m-%d")
+            .to_string();
+        let start = if account.opening_date > window_start { account.opening_date.clone() } else { window_start };
+        
+        let carried = sqlx::query!(
+            "SELECT SUM(amount) as total FROM transactions 
+             WHERE account = ? AND deleted_at IS NULL AND date >= ? AND date < ?",
+            account_id,
+            account.opening_date,
+            start
+        ).fetch_one(&self.pool).await?.total.unwrap_or(0.0);
+        
+        let rows = sqlx::query!(
+            "SELECT date, SUM(amount) as total FROM transactions 
+             WHERE account = ? AND deleted_at IS NULL AND date >= ? 
+             GROUP BY date ORDER BY date ASC",
+            account_id,
+            start
+        ).fetch_all(&self.pool).await?;
+        
+        let mut balance = account.opening_balance + carried;
+        let mut history = vec![BalancePoint {
+            date: start,
+            balance,
+        }];
+        for row in rows {
+            balance += row.total;
+            if history.last().map(|point| point.date == row.date).unwrap_or(false) {
+                history.pop();
+            }
+            history.push(BalancePoint {
+                date: row.date,
+                balance,
+            });
+        }
+        
+        Ok(history)
+    }
+
+    pub async fn create_import_batch(&self, file_name: &str, file_hash: &str, format: FileFormat) -> Result<String> {
+        let id = self.security.generate_secure_id();
+        let encrypted_file_name = self.security.encrypt(file_name, &self.encryption_key)?;
+        let format = format.as_str();
+        
+        sqlx::query!(
+            "INSERT INTO import_batches (id, file_name_encrypted, file_hash, format) VALUES (?, ?, ?, ?)",
+            id,
+            encrypted_file_name,
+            file_hash,
+            format
+        ).execute(&self.pool).await?;
+        
+        Ok(id)
+    }
+
+    pub async fn finish_import_batch(&self, batch_id: &str, imported_count: i32) -> Result<()> {
+        let pending_reviews = sqlx::query!(
+            "SELECT COUNT(*) as count FROM duplicate_reviews WHERE batch_id = ?",
+            batch_id
+        ).fetch_one(&self.pool).await?.count;
+        
+        // A batch that created nothing and left nothing to review has nothing to roll back
+        if imported_count == 0 && pending_reviews == 0 {
+            sqlx::query!("DELETE FROM import_batches WHERE id = ?", batch_id)
+                .execute(&self.pool).await?;
+            return Ok(());
+        }
+        
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            "UPDATE import_batches SET imported_count = ? WHERE id = ?",
+            imported_count,
+            batch_id
+        ).execute(&mut *tx).await?;
+        let examples = self.batch_training_examples(&mut tx, batch_id).await?;
+        self.relearn_category(&mut tx, &[], &examples).await?;
+        tx.commit().await?;
+        
+        Ok(())
+    }
+
+    pub async fn find_import_batch_by_hash(&self, file_hash: &str) -> Result<Option<ImportBatch>> {
+        let row = sqlx::query!(
+            "SELECT id FROM import_batches WHERE file_hash = ? ORDER BY created_at DESC LIMIT 1",
+            file_hash
+        ).fetch_optional(&self.pool).await?;
+        
+        match row {
+            Some(row) => self.get_import_batch(&row.id).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn get_import_batches(&self) -> Result<Vec<ImportBatch>> {
+        let rows = sqlx::query!(
+            "SELECT id FROM import_batches ORDER BY created_at DESC"
+        ).fetch_all(&self.pool).await?;
+        
+        let mut batches = Vec::new();
+        for row in rows {
+            batches.push(self.get_import_batch(&row.id).await?);
+        }
+        
+        Ok(batches)
+    }
+
+    async fn get_import_batch(&self, batch_id: &str) -> Result<ImportBatch> {
+        let row = sqlx::query!(
+            "SELECT id, file_name_encrypted, file_hash, format, imported_count, created_at 
+             FROM import_batches WHERE id = ?",
+            batch_id
+        ).fetch_optional(&self.pool).await?
+            .ok_or_else(|| anyhow!("Lot d'import introuvable: {}", batch_id))?;
+        
+        let transaction_ids = sqlx::query!(
+            "SELECT id FROM transactions WHERE batch_id = ? ORDER BY date ASC",
+            batch_id
+        ).fetch_all(&self.pool).await?
+            .into_iter()
+            .map(|row| row.id)
+            .collect();
+        
+        Ok(ImportBatch {
+            id: row.id,
+            file_name: self.security.decrypt(&row.file_name_encrypted, &self.encryption_key)?,
+            file_hash: row.file_hash,
+            format: row.format,
+            imported_count: row.imported_count as i32,
+            created_at: row.created_at.map(|d| d.to_string()).unwrap_or_default(),
+            transaction_ids,
+        })
+    }
+
+    // Removes every transaction created by an import, all or nothing. Their
+    // transfer partners from other imports become ordinary transactions again
+    // and the category model forgets the removed rows.
+    pub async fn rollback_import_batch(&self, batch_id: &str) -> Result<u64> {
+        let mut tx = self.pool.begin().await?;
+        
+        let exists = sqlx::query!("SELECT id FROM import_batches WHERE id = ?", batch_id)
+            .fetch_optional(&mut *tx).await?;
+        if exists.is_none() {
+            return Err(anyhow!("Lot d'import introuvable: {}", batch_id));
+        }
+        
+        let partners = sqlx::query!(
+            "SELECT id FROM transactions 
+             WHERE (batch_id IS NULL OR batch_id != ?) AND transfer_id IN 
+             (SELECT transfer_id FROM transactions WHERE batch_id = ? AND transfer_id IS NOT NULL)",
+            batch_id,
+            batch_id
+        ).fetch_all(&mut *tx).await?;
+        let mut relinked = Vec::new();
+        for partner in partners {
+            relinked.extend(self.unlink_leg(&mut tx, &partner.id).await?);
+        }
+        let removed = self.batch_training_examples(&mut tx, batch_id).await?;
+        self.relearn_category(&mut tx, &removed, &relinked).await?;
+        
+        let deleted = self.delete_import_batch_with(&mut tx, batch_id).await?;
+        tx.commit().await?;
+        Ok(deleted)
+    }
+
+    // Undoes an import that failed part-way, so the file is not reported as
+    // already imported on the next attempt. Its rows were neither learned by
+    // the category model nor paired yet, so they are only deleted.
+    pub async fn abandon_import_batch(&self, batch_id: &str) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        self.delete_import_batch_with(&mut tx, batch_id).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // Deletes a batch with its transactions and pending reviews; returns the
+    // number of transactions deleted
+    async fn delete_import_batch_with(&self, conn: &mut SqliteConnection, batch_id: &str) -> Result<u64> {
+        sqlx::query!(
+            "DELETE FROM search_tokens WHERE transaction_id IN (SELECT id FROM transactions WHERE batch_id = ?)",
+            batch_id
+        ).execute(&mut *conn).await?;
+        sqlx::query!(
+            "DELETE FROM transaction_splits WHERE transaction_id IN (SELECT id FROM transactions WHERE batch_id = ?)",
+            batch_id
+        ).execute(&mut *conn).await?;
+        let deleted = sqlx::query!("DELETE FROM transactions WHERE batch_id = ?", batch_id)
+            .execute(&mut *conn).await?
+            .rows_affected();
+        sqlx::query!("DELETE FROM duplicate_reviews WHERE batch_id = ?", batch_id)
+            .execute(&mut *conn).await?;
+        sqlx::query!("DELETE FROM import_batches WHERE id = ?", batch_id)
+            .execute(&mut *conn).await?;
+        
+        Ok(deleted)
+    }
+
+    pub async fn queue_duplicate_review(&self, transaction: &Transaction, batch_id: &str, duplicate: &DuplicateMatch) -> Result<()> {
+        let existing_id = duplicate.transaction_id.as_ref()
+            .ok_or_else(|| anyhow!("Aucune transaction correspondante à examiner"))?;
+        let id = self.security.generate_secure_id();
+        let encrypted_transaction = self.security.encrypt(&serde_json::to_string(transaction)?, &self.encryption_key)?;
+        
+        sqlx::query!(
+            "INSERT INTO duplicate_reviews (id, batch_id, transaction_encrypted, existing_id, score) VALUES (?, ?, ?, ?, ?)",
+            id,
+            batch_id,
+            encrypted_transaction,
+            existing_id,
+            duplicate.score
+        ).execute(&self.pool).await?;
+        
+        Ok(())
+    }
+
+    pub async fn get_duplicate_reviews(&self) -> Result<Vec<DuplicateReview>> {
+        let rows = sqlx::query!(
+            "SELECT id, batch_id, transaction_encrypted, existing_id, score, created_at 
+             FROM duplicate_reviews ORDER BY created_at ASC"
+        ).fetch_all(&self.pool).await?;
+        
+        let mut reviews = Vec::new();
+        for row in rows {
+            let transaction = self.security.decrypt(&row.transaction_encrypted, &self.encryption_key)?;
+            
+            reviews.push(DuplicateReview {
+                id: row.id,
+                batch_id: row.batch_id,
+                transaction: serde_json::from_str(&transaction)?,
+                existing: self.get_transaction(&row.existing_id).await?,
+                score: row.score,
+                created_at: row.created_at.map(|d| d.to_string()).unwrap_or_default(),
+            });
+        }
+        
+        Ok(reviews)
+    }
+
+    // Keeping a reviewed row inserts it into its original import batch, so
+    // rolling back the import still removes it
+    pub async fn resolve_duplicate_review(&self, review_id: &str, keep: bool) -> Result<()> {
+        let row = sqlx::query!(
+            "SELECT batch_id, transaction_encrypted FROM duplicate_reviews WHERE id = ?",
+            review_id
+        ).fetch_optional(&self.pool).await?
+            .ok_or_else(|| anyhow!("Doublon à examiner introuvable: {}", review_id))?;
+        
+        if keep {
+            let transaction = self.security.decrypt(&row.transaction_encrypted, &self.encryption_key)?;
+            let transaction: Transaction = serde_json::from_str(&transaction)?;
+            self.insert_transaction(&transaction, row.batch_id.as_deref()).await?;
+            
+            if let Some(batch_id) = &row.batch_id {
+                sqlx::query!(
+                    "UPDATE import_batches SET imported_count = imported_count + 1 WHERE id = ?",
+                    batch_id
+                ).execute(&self.pool).await?;
+            }
+        }
+        
+        sqlx::query!("DELETE FROM duplicate_reviews WHERE id = ?", review_id)
+            .execute(&self.pool).await?;
+        
+        Ok(())
+    }
+
+    async fn get_setting<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        let mut conn = self.pool.acquire().await?;
+        self.get_setting_with(&mut conn, key).await
+    }
+
+    async fn get_setting_with<T: DeserializeOwned>(&self, conn: &mut SqliteConnection, key: &str) -> Result<Option<T>> {
+        let row = sqlx::query!("SELECT value_encrypted FROM settings WHERE key = ?", key)
+            .fetch_optional(&mut *conn).await?;
+        
+        match row {
+            Some(row) => {
+                let json = self.security.decrypt(&row.value_encrypted, &self.encryption_key)?;
+                Ok(Some(serde_json::from_str(&json)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn set_setting<T: Serialize>(&self, key: &str, value: &T) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        self.set_setting_with(&mut conn, key, value).await
+    }
+
+    async fn set_setting_with<T: Serialize>(&self, conn: &mut SqliteConnection, key: &str, value: &T) -> Result<()> {
+        let json = serde_json::to_string(value)?;
+        let encrypted_value = self.security.encrypt(&json, &self.encryption_key)?;
+        
+        sqlx::query!(
+            "INSERT OR REPLACE INTO settings (key, value_encrypted, updated_at) VALUES (?, ?, CURRENT_TIMESTAMP)",
+            key,
+            encrypted_value
+        ).execute(&mut *conn).await?;
+        
+        Ok(())
+    }
+
+    pub async fn get_import_profiles(&self) -> Result<Vec<ImportProfile>> {
+        let pattern = format!("{}%", IMPORT_PROFILE_PREFIX);
+        let rows = sqlx::query!("SELECT value_encrypted FROM settings WHERE key LIKE ?", pattern)
+            .fetch_all(&self.pool).await?;
+        
+        let mut profiles = Vec::new();
+        for row in rows {
+            let json = self.security.decrypt(&row.value_encrypted, &self.encryption_key)?;
+            profiles.push(serde_json::from_str::<ImportProfile>(&json)?);
+        }
+        profiles.sort_by_key(|profile| profile.name.to_lowercase());
+        
+        Ok(profiles)
+    }
+
+    pub async fn get_import_profile(&self, profile_id: &str) -> Result<ImportProfile> {
+        self.get_setting(&format!("{}{}", IMPORT_PROFILE_PREFIX, profile_id)).await?
+            .ok_or_else(|| anyhow!("Profil d'import introuvable: {}", profile_id))
+    }
+
+    pub async fn save_import_profile(&self, profile: &ImportProfile) -> Result<()> {
+        if profile.name.trim().is_empty() {
+            return Err(anyhow!("Le nom du profil est obligatoire"));
+        }
+        self.set_setting(&format!("{}{}", IMPORT_PROFILE_PREFIX, profile.id), profile).await
+    }
+
+    pub async fn delete_import_profile(&self, profile_id: &str) -> Result<()> {
+        let key = format!("{}{}", IMPORT_PROFILE_PREFIX, profile_id);
+        let result = sqlx::query!("DELETE FROM settings WHERE key = ?", key)
+            .execute(&self.pool).await?;
+        
+        if result.rows_affected() == 0 {
+            return Err(anyhow!("Profil d'import introuvable: {}", profile_id));
+        }
+        Ok(())
+    }
+
     pub async fn backup_database(&self, backup_path: &str) -> Result<()> {
         sqlx::query(&format!("VACUUM INTO '{}'", backup_path))
             .execute(&self.pool).await?;
@@ -344,3 +3083,649 @@ impl DatabaseManager {
         Ok(integrity == "ok")
     }
 }
+
+// A transaction's contribution to the category model
+#[derive(PartialEq)]
+struct TrainingExample {
+    description: String,
+    amount: f64,
+    category: String,
+}
+
+impl TrainingExample {
+    fn of(transaction: &Transaction) -> Self {
+        TrainingExample {
+            description: transaction.description.clone(),
+            amount: transaction.amount,
+            category: transaction.category.clone(),
+        }
+    }
+}
+
+// The accounts an import resolves its rows against. Transactions are stored
+// against an account ID; an account name or bank identifier is accepted and
+// resolved. Until the first account is created, free-text accounts are kept
+// as they are so existing databases keep working.
+pub struct AccountDirectory {
+    accounts: Vec<AccountKey>,
+}
+
+impl AccountDirectory {
+    pub fn resolve(&self, account: &str) -> Result<String> {
+        if self.accounts.is_empty() {
+            return Ok(account.to_string());
+        }
+        self.accounts
+            .iter()
+            .find(|key| key.matches(account))
+            .map(|key| key.id.clone())
+            .ok_or_else(|| anyhow!("Compte inconnu: {}", account))
+    }
+}
+
+struct AccountKey {
+    id: String,
+    name: String, // Trimmed and lowercased
+    bank_identifier: Option<String>, // Normalised
+}
+
+impl AccountKey {
+    fn new(id: &str, name: &str, bank_identifier: Option<&str>) -> Self {
+        AccountKey {
+            id: id.to_string(),
+            name: name.trim().to_lowercase(),
+            bank_identifier: bank_identifier
+                .map(normalize_bank_identifier)
+                .filter(|bank_identifier| !bank_identifier.is_empty()),
+        }
+    }
+
+    fn matches(&self, account: &str) -> bool {
+        self.id == account
+            || self.name == account.trim().to_lowercase()
+            || self.bank_identifier.as_deref() == Some(normalize_bank_identifier(account).as_str())
+    }
+}
+
+// Budgets only track spending: the positive size of a debit, zero for a credit
+fn spending(amount: f64) -> f64 {
+    if amount < 0.0 { -amount } else { 0.0 }
+}
+
+struct TransactionFilters<'a> {
+    account: Option<&'a str>,
+    date_from: Option<&'a str>,
+    date_to: Option<&'a str>,
+    min_amount: Option<f64>,
+    max_amount: Option<f64>,
+    category_index: Option<&'a str>,
+}
+
+impl<'a> TransactionFilters<'a> {
+    fn push(&self, builder: &mut QueryBuilder<'a, Sqlite>) {
+        if let Some(account) = self.account {
+            builder.push(" AND account = ").push_bind(account);
+        }
+        if let Some(date_from) = self.date_from {
+            builder.push(" AND date >= ").push_bind(date_from);
+        }
+        if let Some(date_to) = self.date_to {
+            builder.push(" AND date <= ").push_bind(date_to);
+        }
+        if let Some(min_amount) = self.min_amount {
+            builder.push(" AND amount >= ").push_bind(min_amount);
+        }
+        if let Some(max_amount) = self.max_amount {
+            builder.push(" AND amount <= ").push_bind(max_amount);
+        }
+        // A split transaction matches the categories of its splits too
+        if let Some(category_index) = self.category_index {
+            builder.push(" AND (category_index = ").push_bind(category_index);
+            builder.push(" OR id IN (SELECT transaction_id FROM transaction_splits WHERE category_index = ")
+                .push_bind(category_index);
+            builder.push("))");
+        }
+    }
+}
+
+// Column and direction of a sort; ties are broken by id in the same direction
+fn sort_key(sort: TransactionSort) -> (&'static str, bool) {
+    match sort {
+        TransactionSort::DateDesc => ("date", true),
+        TransactionSort::DateAsc => ("date", false),
+        TransactionSort::AmountDesc => ("amount", true),
+        TransactionSort::AmountAsc => ("amount", false),
+    }
+}
+
+fn push_sort_value(builder: &mut QueryBuilder<'_, Sqlite>, sort: TransactionSort, value: &str) -> Result<()> {
+    match sort {
+        TransactionSort::AmountDesc | TransactionSort::AmountAsc => {
+            let amount: f64 = value.parse().map_err(|_| anyhow!("Curseur invalide"))?;
+            builder.push_bind(amount);
+        }
+        TransactionSort::DateDesc | TransactionSort::DateAsc => {
+            builder.push_bind(value.to_string());
+        }
+    }
+    Ok(())
+}
+
+// The cursor is the last row's sort value and id, base64-encoded so the
+// frontend treats it as opaque
+fn encode_cursor(sort: TransactionSort, last: &Transaction) -> String {
+    let value = match sort {
+        TransactionSort::AmountDesc | TransactionSort::AmountAsc => last.amount.to_string(),
+        TransactionSort::DateDesc | TransactionSort::DateAsc => last.date.clone(),
+    };
+    general_purpose::URL_SAFE_NO_PAD.encode(format!("{}\n{}", value, last.id))
+}
+
+fn decode_cursor(cursor: &str) -> Result<(String, String)> {
+    let decoded = general_purpose::URL_SAFE_NO_PAD.decode(cursor)
+        .map_err(|_| anyhow!("Curseur invalide"))?;
+    let decoded = String::from_utf8(decoded).map_err(|_| anyhow!("Curseur invalide"))?;
+    let (value, id) = decoded.split_once('\n').ok_or_else(|| anyhow!("Curseur invalide"))?;
+    Ok((value.to_string(), id.to_string()))
+}
+
+// Checks a category against the others before it is saved under `id`
+fn validate_category(category: &Category, id: &str, categories: &[Category]) -> Result<()> {
+    let name = category.name.trim();
+    if name.is_empty() {
+        return Err(anyhow!("Le nom de la catégorie est obligatoire"));
+    }
+    if name.contains(CATEGORY_PATH_SEPARATOR.trim()) {
+        return Err(anyhow!("Le nom d'une catégorie ne peut pas contenir '{}'", CATEGORY_PATH_SEPARATOR.trim()));
+    }
+    
+    if let Some(parent_id) = &category.parent_id {
+        let parent = categories
+            .iter()
+            .find(|other| &other.id == parent_id)
+            .ok_or_else(|| anyhow!("Catégorie parente introuvable: {}", parent_id))?;
+        // The parent may not be the category itself or one of its descendants
+        let own_path = categories.iter().find(|other| other.id == id).map(|other| other.path.as_str());
+        if parent.id == id || own_path.map(|path| rebase_category(&parent.path, path, "").is_some()).unwrap_or(false) {
+            return Err(anyhow!("Une catégorie ne peut pas être rangée sous elle-même"));
+        }
+    }
+    
+    let duplicate = categories.iter().any(|other| {
+        other.id != id && other.parent_id == category.parent_id && other.name.trim().to_lowercase() == name.to_lowercase()
+    });
+    if duplicate {
+        return Err(anyhow!("Une catégorie porte déjà ce nom: {}", name));
+    }
+    
+    if let Some(color) = &category.color {
+        let hex = color.strip_prefix('#').unwrap_or("");
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(anyhow!("Couleur invalide: {}", color));
+        }
+    }
+    
+    Ok(())
+}
+
+// Splits must account for the whole transaction, to the cent
+fn validate_splits(transaction: &Transaction) -> Result<()> {
+    if transaction.splits.is_empty() {
+        return Ok(());
+    }
+    if transaction.splits.iter().any(|split| split.category.trim().is_empty()) {
+        return Err(anyhow!("Chaque ventilation doit avoir une catégorie"));
+    }
+    
+    let total: f64 = transaction.splits.iter().map(|split| split.amount).sum();
+    if (total - transaction.amount).abs() >= 0.005 {
+        return Err(anyhow!(
+            "La somme des ventilations ({:.2}) ne correspond pas au montant de la transaction ({:.2})",
+            total, transaction.amount
+        ));
+    }
+    
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    // A private in-memory database; one connection, since each connection
+    // to ":memory:" would open a database of its own
+    async fn test_db() -> DatabaseManager {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        DatabaseManager::open(pool, SecurityManager::new(), [7u8; 32]).await.unwrap()
+    }
+
+    fn transaction(id: &str, date: &str, amount: f64, description: &str) -> Transaction {
+        Transaction {
+            id: id.to_string(),
+            description: description.to_string(),
+            amount,
+            date: date.to_string(),
+            category: "Courses".to_string(),
+            account: "Compte courant".to_string(),
+            external_id: None,
+            transfer_id: None,
+            splits: Vec::new(),
+            tags: Vec::new(),
+            rule_id: None,
+            payee_id: None,
+        }
+    }
+
+    async fn all_pages(db: &DatabaseManager, sort: TransactionSort, limit: i32) -> Vec<Vec<String>> {
+        let mut pages = Vec::new();
+        let mut cursor = None;
+        loop {
+            let query = TransactionQuery { sort, limit: Some(limit), cursor, ..Default::default() };
+            let page = db.get_transactions(&query).await.unwrap();
+            assert_eq!(page.total_count, 5);
+            pages.push(page.transactions.into_iter().map(|t| t.id).collect());
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                return pages;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn pages_through_ties_on_the_sort_key_without_gaps() {
+        let db = test_db().await;
+        for (id, date, amount) in [
+            ("a", "2024-01-10", -10.0),
+            ("b", "2024-01-10", -10.0),
+            ("c", "2024-01-10", -25.0),
+            ("d", "2024-01-09", -10.0),
+            ("e", "2024-01-11", 100.0),
+        ] {
+            db.add_transaction(&transaction(id, date, amount, "CB CARREFOUR")).await.unwrap();
+        }
+
+        // Rows sharing a date or an amount are ordered by id across page breaks
+        assert_eq!(all_pages(&db, TransactionSort::DateDesc, 2).await, vec![vec!["e", "c"], vec!["b", "a"], vec!["d"]]);
+        assert_eq!(all_pages(&db, TransactionSort::DateAsc, 2).await, vec![vec!["d", "a"], vec!["b", "c"], vec!["e"]]);
+        assert_eq!(all_pages(&db, TransactionSort::AmountDesc, 2).await, vec![vec!["e", "d"], vec!["b", "a"], vec!["c"]]);
+        assert_eq!(all_pages(&db, TransactionSort::AmountAsc, 3).await, vec![vec!["c", "a", "b"], vec!["d", "e"]]);
+    }
+
+    #[tokio::test]
+    async fn filters_pages_and_rejects_invalid_cursors() {
+        let db = test_db().await;
+        db.add_transaction(&transaction("a", "2024-01-10", -10.0, "CB CARREFOUR")).await.unwrap();
+        db.add_transaction(&Transaction { category: "Loisirs".to_string(), ..transaction("b", "15/01/2024", -30.0, "CINEMA") }).await.unwrap();
+
+        let query = TransactionQuery { category: Some("loisirs".to_string()), ..Default::default() };
+        let page = db.get_transactions(&query).await.unwrap();
+        assert_eq!(page.total_count, 1);
+        assert_eq!(page.transactions[0].date, "2024-01-15");
+        assert!(page.next_cursor.is_none());
+
+        let query = TransactionQuery { date_from: Some("2024-01-11".to_string()), min_amount: Some(-50.0), ..Default::default() };
+        assert_eq!(db.get_transactions(&query).await.unwrap().transactions.len(), 1);
+
+        for cursor in ["not base64!", "bm8gc2VwYXJhdG9y"] {
+            let query = TransactionQuery { cursor: Some(cursor.to_string()), ..Default::default() };
+            assert!(db.get_transactions(&query).await.is_err());
+        }
+        let amount_cursor = general_purpose::URL_SAFE_NO_PAD.encode("abc\na");
+        let query = TransactionQuery { sort: TransactionSort::AmountAsc, cursor: Some(amount_cursor), ..Default::default() };
+        assert!(db.get_transactions(&query).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn trashes_restores_and_purges() {
+        let db = test_db().await;
+        db.add_transaction(&transaction("a", "2024-01-10", -10.0, "CB CARREFOUR")).await.unwrap();
+        db.add_transaction(&transaction("b", "2024-01-11", -20.0, "CB AUCHAN")).await.unwrap();
+
+        db.delete_transaction("a").await.unwrap();
+        assert!(db.get_transaction("a").await.unwrap().is_none());
+        assert!(db.delete_transaction("a").await.is_err());
+        assert_eq!(db.get_transactions(&TransactionQuery::default()).await.unwrap().total_count, 1);
+        let trash = db.get_trash().await.unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].transaction.description, "CB CARREFOUR");
+
+        db.restore_transaction("a").await.unwrap();
+        assert!(db.get_transaction("a").await.unwrap().is_some());
+        assert!(db.restore_transaction("a").await.is_err());
+
+        // Nothing is old enough to purge until the retention runs out
+        db.delete_transaction("b").await.unwrap();
+        assert_eq!(db.purge_trash().await.unwrap(), 0);
+        db.set_trash_retention(0).await.unwrap();
+        assert_eq!(db.purge_trash().await.unwrap(), 1);
+        assert!(db.get_trash().await.unwrap().is_empty());
+        assert!(db.restore_transaction("b").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn rolls_back_an_import_batch() {
+        let db = test_db().await;
+        db.add_transaction(&transaction("kept", "2024-01-09", -5.0, "BOULANGERIE")).await.unwrap();
+
+        let batch_id = db.create_import_batch("releve.csv", "hash", FileFormat::Csv).await.unwrap();
+        let mut payees = Vec::new();
+        for (id, date) in [("a", "2024-01-10"), ("b", "2024-01-11")] {
+            db.add_imported_transaction(&transaction(id, date, -10.0, "CB CARREFOUR"), &batch_id, &mut payees).await.unwrap();
+        }
+        db.finish_import_batch(&batch_id, 2).await.unwrap();
+        let batch = db.find_import_batch_by_hash("hash").await.unwrap().unwrap();
+        assert_eq!(batch.imported_count, 2);
+        assert_eq!(batch.transaction_ids.len(), 2);
+
+        assert_eq!(db.rollback_import_batch(&batch_id).await.unwrap(), 2);
+        assert!(db.find_import_batch_by_hash("hash").await.unwrap().is_none());
+        let page = db.get_transactions(&TransactionQuery::default()).await.unwrap();
+        assert_eq!(page.transactions.iter().map(|t| t.id.as_str()).collect::<Vec<_>>(), vec!["kept"]);
+        assert!(db.search_transactions("carrefour", None).await.unwrap().is_empty());
+        assert!(db.rollback_import_batch(&batch_id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn unlinking_gives_both_legs_their_category_back_even_in_the_trash() {
+        let db = test_db().await;
+        db.add_transaction(&transaction("a", "2024-01-10", -100.0, "VIR VERS LIVRET A")).await.unwrap();
+        db.add_transaction(&Transaction {
+            account: "Livret A".to_string(),
+            category: "Epargne".to_string(),
+            ..transaction("b", "2024-01-10", 100.0, "VIR RECU")
+        }).await.unwrap();
+
+        let transfer_id = db.link_transfer("a", "b").await.unwrap();
+        assert_eq!(db.get_transaction("a").await.unwrap().unwrap().category, TRANSFER_CATEGORY);
+        db.unlink_transfer(&transfer_id).await.unwrap();
+        let first = db.get_transaction("a").await.unwrap().unwrap();
+        assert_eq!((first.category.as_str(), first.transfer_id), ("Courses", None));
+        assert_eq!(db.get_transaction("b").await.unwrap().unwrap().category, "Epargne");
+        assert!(db.unlink_transfer(&transfer_id).await.is_err());
+
+        // Trashing one leg trashes both; unlinking must still reach them
+        let transfer_id = db.link_transfer("a", "b").await.unwrap();
+        db.delete_transaction("a").await.unwrap();
+        db.unlink_transfer(&transfer_id).await.unwrap();
+        let mut trash: Vec<Transaction> = db.get_trash().await.unwrap().into_iter().map(|t| t.transaction).collect();
+        trash.sort_by(|x, y| x.id.cmp(&y.id));
+        assert!(trash.iter().all(|t| t.transfer_id.is_none()));
+        assert_eq!(trash.iter().map(|t| t.category.as_str()).collect::<Vec<_>>(), vec!["Courses", "Epargne"]);
+        db.restore_transaction("a").await.unwrap();
+        assert!(db.get_transaction("b").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn pairs_imported_legs_only_when_they_read_like_a_transfer() {
+        let db = test_db().await;
+        let stored = [
+            ("livret", "Livret A", 100.0, "VIR RECU COMPTE COURANT"),
+            ("refund", "Compte joint", 50.0, "AVOIR FNAC"),
+            ("deposit", "Epargne", 30.0, "DEPOT"),
+            ("deposit-1", "Epargne", 20.0, "DEPOT"),
+            ("deposit-2", "Compte joint", 20.0, "DEPOT"),
+        ];
+        for (id, account, amount, description) in stored {
+            db.add_transaction(&Transaction { account: account.to_string(), ..transaction(id, "2024-01-10", amount, description) }).await.unwrap();
+        }
+
+        let batch_id = db.create_import_batch("releve.csv", "hash", FileFormat::Csv).await.unwrap();
+        let mut payees = Vec::new();
+        let imported = [
+            ("both", -100.0, "VIR VERS LIVRET A"),
+            ("neither", -50.0, "CB FNAC"),
+            ("only-candidate", -30.0, "VIREMENT EMIS"),
+            ("ambiguous", -20.0, "VIREMENT EMIS"),
+        ];
+        for (id, amount, description) in imported {
+            db.add_imported_transaction(&transaction(id, "2024-01-11", amount, description), &batch_id, &mut payees).await.unwrap();
+        }
+
+        assert_eq!(db.pair_transfers(&batch_id).await.unwrap(), 2);
+        let transfer_of = |id: &'static str| {
+            let db = &db;
+            async move { db.get_transaction(id).await.unwrap().unwrap().transfer_id }
+        };
+        assert!(transfer_of("both").await.is_some());
+        assert_eq!(transfer_of("both").await, transfer_of("livret").await);
+        assert_eq!(transfer_of("only-candidate").await, transfer_of("deposit").await);
+        for id in ["neither", "refund", "ambiguous", "deposit-1", "deposit-2"] {
+            assert!(transfer_of(id).await.is_none(), "{} was paired", id);
+        }
+    }
+
+    #[tokio::test]
+    async fn a_stored_row_is_the_duplicate_of_one_incoming_row_only() {
+        let db = test_db().await;
+        db.add_transaction(&transaction("stored", "2024-01-10", -4.5, "CB CAFE DU COIN")).await.unwrap();
+
+        let mut matched = HashSet::new();
+        let incoming = transaction("new", "10/01/2024", -4.5, "CB CAFE DU COIN");
+        let first = db.detect_duplicate(&incoming, None, &matched).await.unwrap();
+        assert_ne!(first.level, DuplicateLevel::NotDuplicate);
+        assert_eq!(first.transaction_id.as_deref(), Some("stored"));
+
+        // The same coffee twice in the file is a second coffee
+        matched.extend(first.transaction_id);
+        let second = db.detect_duplicate(&incoming, None, &matched).await.unwrap();
+        assert_eq!(second.level, DuplicateLevel::NotDuplicate);
+        assert!(second.transaction_id.is_none());
+    }
+
+    #[tokio::test]
+    async fn merges_duplicate_budgets_once_and_records_the_merge() {
+        let db = test_db().await;
+        // Budgets from before the unique index, without a category index
+        sqlx::query("DROP INDEX idx_budgets_category_period").execute(&db.pool).await.unwrap();
+        for (id, amount) in [("small", 100.0), ("large", 250.0)] {
+            let category = db.security.encrypt("Courses", &db.encryption_key).unwrap();
+            sqlx::query("INSERT INTO budgets (id, category_encrypted, amount, period) VALUES (?, ?, ?, 'monthly')")
+                .bind(id)
+                .bind(category)
+                .bind(amount)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+        }
+
+        db.enforce_unique_budgets().await.unwrap();
+        let budgets = db.get_budgets().await.unwrap();
+        assert_eq!(budgets.iter().map(|b| (b.id.as_str(), b.amount)).collect::<Vec<_>>(), vec![("large", 250.0)]);
+        let merged = db.get_merged_budgets().await.unwrap();
+        assert_eq!(merged.len(), 1);
+        assert_eq!((merged[0].budget.id.as_str(), merged[0].budget.category.as_str()), ("small", "Courses"));
+        assert_eq!(merged[0].merged_into, "large");
+
+        // The index now exists, so a second start leaves everything alone
+        db.enforce_unique_budgets().await.unwrap();
+        assert_eq!(db.get_merged_budgets().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn renaming_rules_file_transactions_under_a_payee_and_keep_the_description() {
+        let db = test_db().await;
+        db.add_transaction(&transaction("a", "2024-01-10", -30.0, "CB AMZN MKTP FR 123")).await.unwrap();
+        db.add_transaction(&transaction("b", "2024-01-11", -12.0, "PRLV AMAZON PRIME")).await.unwrap();
+        let prime = db.get_transaction("b").await.unwrap().unwrap().payee_id;
+        db.save_rule(&Rule {
+            id: String::new(),
+            name: "Amazon".to_string(),
+            priority: 0,
+            conditions: vec![RuleCondition::DescriptionContains { value: "amzn".to_string() }],
+            actions: vec![RuleAction::RenamePayee { payee: "Amazon Prime".to_string() }],
+        }).await.unwrap();
+
+        assert_eq!(db.apply_rules(None).await.unwrap().len(), 1);
+        let renamed = db.get_transaction("a").await.unwrap().unwrap();
+        assert_eq!(renamed.description, "CB AMZN MKTP FR 123");
+        // The payee already known under that name is reused
+        assert!(prime.is_some());
+        assert_eq!(renamed.payee_id, prime);
+        assert_eq!(db.get_payees().await.unwrap().iter().filter(|p| p.name == "Amazon Prime").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn abandoning_a_failed_import_forgets_its_file() {
+        let db = test_db().await;
+        let batch_id = db.create_import_batch("releve.csv", "hash", FileFormat::Csv).await.unwrap();
+        let mut payees = Vec::new();
+        db.add_imported_transaction(&transaction("a", "2024-01-10", -10.0, "CB CARREFOUR"), &batch_id, &mut payees).await.unwrap();
+
+        db.abandon_import_batch(&batch_id).await.unwrap();
+        assert!(db.find_import_batch_by_hash("hash").await.unwrap().is_none());
+        assert!(db.get_transaction("a").await.unwrap().is_none());
+        assert!(db.search_transactions("carrefour", None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn suggests_categories_only_above_the_confidence_threshold() {
+        let db = test_db().await;
+        for (id, description, category) in [
+            ("a", "CB CARREFOUR MARKET", "Courses"),
+            ("b", "CB CARREFOUR CITY", "Courses"),
+            ("c", "PAYPAL", "Loisirs"),
+            ("d", "PAYPAL", "Santé"),
+            ("e", "PAYPAL", "Voyages"),
+        ] {
+            db.add_transaction(&Transaction { category: category.to_string(), ..transaction(id, "2024-01-10", -20.0, description) }).await.unwrap();
+        }
+        db.add_transaction(&Transaction { category: DEFAULT_CATEGORY.to_string(), ..transaction("new", "2024-01-11", -20.0, "CB CARREFOUR EXPRESS") }).await.unwrap();
+        db.add_transaction(&Transaction { category: DEFAULT_CATEGORY.to_string(), ..transaction("unsure", "2024-01-11", -20.0, "PAYPAL") }).await.unwrap();
+
+        let suggestion = db.suggest_category("CB CARREFOUR EXPRESS", -20.0).await.unwrap().unwrap();
+        assert_eq!(suggestion.category, "Courses");
+        assert!(suggestion.confidence >= classifier::MIN_CONFIDENCE);
+        // Three categories share PayPal payments, so none is likely enough
+        assert!(db.suggest_category("PAYPAL", -20.0).await.unwrap().is_none());
+
+        let suggestions = db.get_category_suggestions().await.unwrap();
+        assert_eq!(suggestions.iter().map(|s| s.transaction_id.as_deref()).collect::<Vec<_>>(), vec![Some("new")]);
+    }
+
+    #[tokio::test]
+    async fn finds_transactions_by_their_split_memos() {
+        let db = test_db().await;
+        let split = |amount: f64, category: &str, memo: Option<&str>| TransactionSplit {
+            id: String::new(),
+            amount,
+            category: category.to_string(),
+            memo: memo.map(str::to_string),
+        };
+        db.add_transaction(&Transaction {
+            splits: vec![split(-30.0, "Courses", Some("Cadeau anniversaire Léa")), split(-20.0, "Maison", None)],
+            ..transaction("a", "2024-01-10", -50.0, "CB CARREFOUR")
+        }).await.unwrap();
+
+        let found = |query: &'static str| {
+            let db = &db;
+            async move {
+                db.search_transactions(query, None).await.unwrap().into_iter().map(|r| r.transaction.id).collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(found("anniv").await, vec!["a"]);
+        // Words may come from the description and a memo at once
+        assert_eq!(found("carrefour cadeau").await, vec!["a"]);
+
+        let mut edited = db.get_transaction("a").await.unwrap().unwrap();
+        edited.splits[0].memo = None;
+        db.update_transaction(&edited).await.unwrap();
+        assert!(found("anniv").await.is_empty());
+        assert_eq!(found("carrefour").await, vec!["a"]);
+
+        // A memo written before memos were indexed is found once the index
+        // is rebuilt on the next start
+        let memo = db.security.encrypt("Ampoules", &db.encryption_key).unwrap();
+        sqlx::query("UPDATE transaction_splits SET memo_encrypted = ? WHERE transaction_id = 'a' AND position = 1")
+            .bind(memo)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        db.backfill_search_index().await.unwrap();
+        assert!(found("ampoule").await.is_empty());
+        sqlx::query("DELETE FROM settings WHERE key = ?").bind(SEARCH_INDEX_VERSION_KEY).execute(&db.pool).await.unwrap();
+        db.backfill_search_index().await.unwrap();
+        assert_eq!(found("ampoule").await, vec!["a"]);
+    }
+
+    #[tokio::test]
+    async fn rekeys_blind_indexes_made_with_the_encryption_key() {
+        let db = test_db().await;
+        db.add_transaction(&Transaction { category: "Loisirs".to_string(), ..transaction("a", "2024-01-10", -10.0, "CINEMA GAUMONT") }).await.unwrap();
+        let stored = sqlx::query("SELECT category_index FROM transactions WHERE id = 'a'").fetch_one(&db.pool).await.unwrap();
+        let raw_key_index = db.security.blind_index(CATEGORY_INDEX_DOMAIN, "loisirs", &db.encryption_key).unwrap();
+        assert_ne!(stored.get::<String, _>("category_index"), raw_key_index);
+
+        // A database from before the index key: indexes keyed with the AES key
+        sqlx::query("UPDATE transactions SET category_index = ?").bind(&raw_key_index).execute(&db.pool).await.unwrap();
+        let token = db.security.blind_index(SEARCH_INDEX_DOMAIN, "cinema", &db.encryption_key).unwrap();
+        sqlx::query("DELETE FROM search_tokens").execute(&db.pool).await.unwrap();
+        sqlx::query("INSERT INTO search_tokens (transaction_id, token_hash, exact) VALUES ('a', ?, 1)")
+            .bind(token)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM settings WHERE key = ?").bind(INDEX_KEY_VERSION_KEY).execute(&db.pool).await.unwrap();
+
+        db.rebuild_blind_indexes().await.unwrap();
+        db.backfill_search_index().await.unwrap();
+        let query = TransactionQuery { category: Some("Loisirs".to_string()), ..Default::default() };
+        assert_eq!(db.get_transactions(&query).await.unwrap().total_count, 1);
+        assert_eq!(db.search_transactions("gaumont", None).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn refuses_to_delete_a_category_that_budgets_rules_or_profiles_use() {
+        let db = test_db().await;
+        let category = db.create_category(&Category {
+            id: String::new(),
+            name: "Voyages".to_string(),
+            parent_id: None,
+            color: None,
+            icon: None,
+            path: String::new(),
+        }).await.unwrap();
+
+        let budget = db.set_budget(&Budget {
+            id: String::new(),
+            category: "voyages".to_string(),
+            amount: 200.0,
+            spent: 0.0,
+            period: "monthly".to_string(),
+            start_day: 1,
+            period_start: None,
+            period_end: None,
+        }).await.unwrap();
+        assert!(db.delete_category(&category.id).await.unwrap_err().to_string().contains("budget"));
+        db.delete_budget(&budget.id).await.unwrap();
+
+        let rule = db.save_rule(&Rule {
+            id: String::new(),
+            name: "SNCF".to_string(),
+            priority: 0,
+            conditions: vec![RuleCondition::DescriptionContains { value: "sncf".to_string() }],
+            actions: vec![RuleAction::SetCategory { category: "Voyages".to_string() }],
+        }).await.unwrap();
+        assert!(db.delete_category(&category.id).await.unwrap_err().to_string().contains("SNCF"));
+        db.delete_rule(&rule.id).await.unwrap();
+
+        let mapping = ColumnMapping { default_category: "Voyages".to_string(), ..Default::default() };
+        db.save_import_profile(&ImportProfile {
+            id: "carte".to_string(),
+            name: "Carte voyage".to_string(),
+            mapping,
+            account: None,
+            encoding: None,
+            header_fingerprint: None,
+        }).await.unwrap();
+        assert!(db.delete_category(&category.id).await.unwrap_err().to_string().contains("Carte voyage"));
+        db.delete_import_profile("carte").await.unwrap();
+
+        db.delete_category(&category.id).await.unwrap();
+        assert!(db.get_categories().await.unwrap().iter().all(|c| c.id != category.id));
+    }
+}
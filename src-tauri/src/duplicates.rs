@@ -0,0 +1,115 @@
+use crate::models::DuplicateLevel;
+
+// Days either side of a transaction's date searched for duplicates
+pub const DATE_WINDOW_DAYS: i64 = 3;
+
+const CERTAIN_THRESHOLD: f64 = 0.95;
+const PROBABLE_THRESHOLD: f64 = 0.6;
+
+// Uppercases, strips accents and punctuation, and drops every token holding a
+// digit: banks append reference numbers, card digits and dates that differ
+// between two exports of the same movement.
+pub fn normalize_description(description: &str) -> String {
+    let cleaned: String = description
+        .to_uppercase()
+        .chars()
+        .map(|c| match c {
+            'À' | 'Â' | 'Ä' => 'A',
+            'É' | 'È' | 'Ê' | 'Ë' => 'E',
+            'Î' | 'Ï' => 'I',
+            'Ô' | 'Ö' => 'O',
+            'Ù' | 'Û' | 'Ü' => 'U',
+            'Ç' => 'C',
+            c if c.is_alphanumeric() => c,
+            _ => ' ',
+        })
+        .collect();
+
+    cleaned
+        .split_whitespace()
+        .filter(|token| !token.chars().any(|c| c.is_ascii_digit()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Token overlap between two normalised descriptions, from 0 to 1. Half
+// Jaccard, half containment, so "CARREFOUR" still matches "CARREFOUR CITY".
+pub fn description_similarity(a: &str, b: &str) -> f64 {
+    let a_tokens: Vec<&str> = a.split_whitespace().collect();
+    let b_tokens: Vec<&str> = b.split_whitespace().collect();
+    if a_tokens.is_empty() || b_tokens.is_empty() {
+        return if a_tokens.is_empty() && b_tokens.is_empty() { 1.0 } else { 0.0 };
+    }
+
+    let shared = a_tokens.iter().filter(|token| b_tokens.contains(token)).count() as f64;
+    let union = (a_tokens.len() + b_tokens.len()) as f64 - shared;
+    let smallest = a_tokens.len().min(b_tokens.len()) as f64;
+
+    0.5 * (shared / union) + 0.5 * (shared / smallest).min(1.0)
+}
+
+// Scores a stored transaction that already has the same account and amount.
+// The date counts for half, decreasing by a quarter per day apart.
+pub fn score(day_distance: i64, similarity: f64) -> f64 {
+    let day_distance = day_distance.abs();
+    if day_distance > DATE_WINDOW_DAYS {
+        return 0.0;
+    }
+    let date_score = 1.0 - day_distance as f64 / (DATE_WINDOW_DAYS + 1) as f64;
+    0.5 * date_score + 0.5 * similarity
+}
+
+pub fn classify(score: f64) -> DuplicateLevel {
+    if score >= CERTAIN_THRESHOLD {
+        DuplicateLevel::Certain
+    } else if score >= PROBABLE_THRESHOLD {
+        DuplicateLevel::Probable
+    } else {
+        DuplicateLevel::NotDuplicate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalises_away_references_accents_and_punctuation() {
+        assert_eq!(normalize_description("CB Carrefour 15/01 CARTE 4970XXXX1234"), "CB CARREFOUR CARTE");
+        assert_eq!(normalize_description("Prélèvement  EDF-Électricité"), "PRELEVEMENT EDF ELECTRICITE");
+        assert_eq!(normalize_description("12345"), "");
+    }
+
+    #[test]
+    fn measures_token_overlap() {
+        assert_eq!(description_similarity("CB CARREFOUR", "CB CARREFOUR"), 1.0);
+        assert_eq!(description_similarity("CARREFOUR", "CARREFOUR CITY"), 0.75);
+        assert_eq!(description_similarity("CARREFOUR", "AUCHAN"), 0.0);
+        assert_eq!(description_similarity("", ""), 1.0);
+        assert_eq!(description_similarity("", "AUCHAN"), 0.0);
+    }
+
+    #[test]
+    fn weighs_the_date_gap_against_the_description() {
+        assert_eq!(score(0, 1.0), 1.0);
+        assert_eq!(score(1, 1.0), 0.875);
+        assert_eq!(score(-1, 1.0), score(1, 1.0));
+        assert_eq!(score(DATE_WINDOW_DAYS, 1.0), 0.625);
+        assert_eq!(score(DATE_WINDOW_DAYS + 1, 1.0), 0.0);
+        assert_eq!(score(-(DATE_WINDOW_DAYS + 1), 1.0), 0.0);
+    }
+
+    #[test]
+    fn classifies_scores_against_the_thresholds() {
+        assert_eq!(classify(score(0, 1.0)), DuplicateLevel::Certain);
+        assert_eq!(classify(CERTAIN_THRESHOLD), DuplicateLevel::Certain);
+        // Same day with a slightly different label is still certain
+        assert_eq!(classify(score(0, 0.9)), DuplicateLevel::Certain);
+        assert_eq!(classify(score(1, 1.0)), DuplicateLevel::Probable);
+        assert_eq!(classify(score(DATE_WINDOW_DAYS, 1.0)), DuplicateLevel::Probable);
+        assert_eq!(classify(PROBABLE_THRESHOLD), DuplicateLevel::Probable);
+        assert_eq!(classify(score(0, 0.1)), DuplicateLevel::NotDuplicate);
+        assert_eq!(classify(score(2, 0.5)), DuplicateLevel::NotDuplicate);
+        assert_eq!(classify(score(DATE_WINDOW_DAYS + 1, 1.0)), DuplicateLevel::NotDuplicate);
+    }
+}
@@ -8,6 +8,13 @@ mod security;
 mod commands;
 mod models;
 mod utils;
+mod parsers;
+mod duplicates;
+mod search;
+mod transfers;
+mod rules;
+mod classifier;
+mod payees;
 
 use tauri::{Manager, State};
 use std::sync::Mutex;
@@ -32,14 +39,56 @@ fn main() {
             commands::auth::lock_app,
             commands::auth::is_locked,
             commands::transactions::get_transactions,
+            commands::transactions::search_transactions,
             commands::transactions::add_transaction,
             commands::transactions::update_transaction,
             commands::transactions::delete_transaction,
+            commands::transactions::get_trash,
+            commands::transactions::restore_transaction,
+            commands::transactions::purge_trash,
+            commands::transactions::get_trash_retention,
+            commands::transactions::set_trash_retention,
+            commands::transactions::create_transfer,
+            commands::transactions::link_transfer,
+            commands::transactions::unlink_transfer,
             commands::budgets::get_budgets,
             commands::budgets::set_budget,
+            commands::budgets::delete_budget,
+            commands::budgets::get_merged_budgets,
             commands::analytics::get_financial_metrics,
             commands::analytics::get_balance_history,
+            commands::analytics::get_spending_by_category,
+            commands::analytics::get_spending_by_payee,
             commands::import::import_file,
+            commands::import::preview_import,
+            commands::import::get_import_batches,
+            commands::import::rollback_import_batch,
+            commands::import::get_duplicate_reviews,
+            commands::import::resolve_duplicate_review,
+            commands::profiles::get_import_profiles,
+            commands::profiles::save_import_profile,
+            commands::profiles::delete_import_profile,
+            commands::accounts::get_accounts,
+            commands::accounts::create_account,
+            commands::accounts::update_account,
+            commands::accounts::delete_account,
+            commands::accounts::get_account_balance_history,
+            commands::categories::get_categories,
+            commands::categories::create_category,
+            commands::categories::update_category,
+            commands::categories::merge_category,
+            commands::categories::delete_category,
+            commands::categories::suggest_category,
+            commands::categories::get_category_suggestions,
+            commands::categories::retrain_category_model,
+            commands::rules::get_rules,
+            commands::rules::save_rule,
+            commands::rules::delete_rule,
+            commands::rules::apply_rules,
+            commands::payees::get_payees,
+            commands::payees::update_payee,
+            commands::payees::merge_payees,
+            commands::payees::delete_payee,
         ])
         .setup(|app| {
             // Initialize security manager and check for existing database
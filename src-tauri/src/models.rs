@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
+use crate::parsers::{DEFAULT_ACCOUNT, DEFAULT_CATEGORY};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Transaction {
@@ -9,15 +10,275 @@ pub struct Transaction {
     pub date: String,
     pub category: String,
     pub account: String,
+    #[serde(default)]
+    pub external_id: Option<String>,
+    #[serde(default)]
+    pub transfer_id: Option<String>, // Shared by the two legs of a transfer
+    #[serde(default)]
+    pub splits: Vec<TransactionSplit>, // Empty unless the amount is spread over several categories
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub rule_id: Option<String>, // Categorisation rule that last fired on this transaction
+    #[serde(default)]
+    pub payee_id: Option<String>, // Derived from the description when not set
+}
+
+// What budgets and category analytics count: the splits when there are any,
+// the whole amount otherwise
+pub fn category_amounts(category: &str, amount: f64, splits: &[TransactionSplit]) -> Vec<(String, f64)> {
+    if splits.is_empty() {
+        vec![(category.to_string(), amount)]
+    } else {
+        splits.iter().map(|split| (split.category.clone(), split.amount)).collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TransactionSplit {
+    #[serde(default)]
+    pub id: String, // Generated when empty
+    pub amount: f64,
+    pub category: String,
+    #[serde(default)]
+    pub memo: Option<String>,
+}
+
+// Every condition of a rule must hold for it to fire
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleCondition {
+    DescriptionContains { value: String }, // Ignores case and accents
+    DescriptionMatches { pattern: String }, // Case-insensitive regex
+    AmountRange { min: Option<f64>, max: Option<f64> }, // Signed amount, bounds included
+    Account { account: String },
+    DayOfMonth { from: u32, to: u32 },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleAction {
+    SetCategory { category: String },
+    RenamePayee { payee: String }, // Files the transaction under this payee; the description is kept
+    AddTag { tag: String },
+    MarkAsTransfer,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Rule {
+    #[serde(default)]
+    pub id: String, // Generated when empty
+    pub name: String,
+    #[serde(default)]
+    pub priority: i32, // Lowest first; only the first matching rule fires
+    pub conditions: Vec<RuleCondition>,
+    pub actions: Vec<RuleAction>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CategorySuggestion {
+    pub transaction_id: Option<String>, // None for a description typed in a form
+    pub category: String,
+    pub confidence: f64, // Posterior probability, from 0 to 1
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RuleApplication {
+    pub transaction_id: String,
+    pub rule_id: String,
+    pub rule_name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransferRequest {
+    pub from_account: String,
+    pub to_account: String,
+    pub amount: f64, // Positive; debited from from_account
+    pub date: String,
+    pub description: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Category {
+    #[serde(default)]
+    pub id: String, // Generated when empty
+    pub name: String, // One level only, e.g. "Électricité"
+    #[serde(default)]
+    pub parent_id: Option<String>,
+    #[serde(default)]
+    pub color: Option<String>, // "#RRGGBB"
+    #[serde(default)]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub path: String, // "Logement > Électricité", as stored on transactions; ignored on save
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Payee {
+    #[serde(default)]
+    pub id: String, // Generated when empty
+    pub name: String,
+    #[serde(default)]
+    pub aliases: Vec<String>, // Normalised descriptions mapped to this payee, e.g. "CARREFOUR"
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PayeeSpending {
+    pub payee_id: String,
+    pub payee: String,
+    pub amount: f64, // Positive total of debits
+    pub transaction_count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CategorySpending {
+    pub category: String,
+    pub amount: f64, // Positive total of debits
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionSort {
+    DateDesc,
+    DateAsc,
+    AmountDesc,
+    AmountAsc,
+}
+
+impl Default for TransactionSort {
+    fn default() -> Self {
+        TransactionSort::DateDesc
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TransactionQuery {
+    #[serde(default)]
+    pub account: Option<String>,
+    #[serde(default)]
+    pub date_from: Option<String>, // Inclusive
+    #[serde(default)]
+    pub date_to: Option<String>, // Inclusive
+    #[serde(default)]
+    pub min_amount: Option<f64>,
+    #[serde(default)]
+    pub max_amount: Option<f64>,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub sort: TransactionSort,
+    #[serde(default)]
+    pub limit: Option<i32>,
+    #[serde(default)]
+    pub cursor: Option<String>, // next_cursor of the previous page
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransactionPage {
+    pub transactions: Vec<Transaction>,
+    pub total_count: i64, // Rows matching the filters, across all pages
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub transaction: Transaction,
+    pub score: f64, // Share of query words matched as whole words rather than prefixes
+}
+
+// Expired trash is purged on unlock; a failed purge is reported here rather
+// than keeping the app locked
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UnlockResult {
+    pub purged_count: u64,
+    pub warnings: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+pub struct TrashedTransaction {
+    pub transaction: Transaction,
+    pub deleted_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Budget {
-    pub id: String,
+    #[serde(default)]
+    pub id: String, // Generated when empty
     pub category: String,
     pub amount: f64,
-    pub spent: f64,
-    pub period: String, // "monthly", "weekly", etc.
+    #[serde(default)]
+    pub spent: f64, // Computed from the transactions of the current period; ignored on save
+    pub period: String, // "weekly", "monthly", "quarterly" or "yearly"
+    #[serde(default = "default_start_day")]
+    pub start_day: u32, // Weekday for weekly budgets (1 = Monday), day of the month otherwise
+    #[serde(default)]
+    pub period_start: Option<String>, // Current period, first and last day; ignored on save
+    #[serde(default)]
+    pub period_end: Option<String>,
+}
+
+// A duplicate budget dropped when one budget per category and period became
+// the rule; `merged_into` is the budget kept in its place
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MergedBudget {
+    pub budget: Budget,
+    pub merged_into: String,
+    pub merged_at: String,
+}
+
+fn default_start_day() -> u32 {
+    1
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountType {
+    Checking,
+    Savings,
+    CreditCard,
+    Cash,
+    Loan,
+    Investment,
+}
+
+impl AccountType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountType::Checking => "checking",
+            AccountType::Savings => "savings",
+            AccountType::CreditCard => "credit_card",
+            AccountType::Cash => "cash",
+            AccountType::Loan => "loan",
+            AccountType::Investment => "investment",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "checking" => Some(AccountType::Checking),
+            "savings" => Some(AccountType::Savings),
+            "credit_card" => Some(AccountType::CreditCard),
+            "cash" => Some(AccountType::Cash),
+            "loan" => Some(AccountType::Loan),
+            "investment" => Some(AccountType::Investment),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Account {
+    #[serde(default)]
+    pub id: String, // Generated when empty
+    pub name: String,
+    #[serde(rename = "type")]
+    pub account_type: AccountType,
+    pub opening_balance: f64,
+    pub opening_date: String, // The opening balance is the balance at the start of this day
+    #[serde(default)]
+    pub bank_identifier: Option<String>, // IBAN or account number found in OFX, MT940 and CAMT statements
+    #[serde(default)]
+    pub balance: f64, // Opening balance plus transactions since; ignored on save
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -42,5 +303,222 @@ pub struct ImportResult {
     pub imported_count: i32,
     pub duplicate_count: i32,
     pub error_count: i32,
+    pub review_count: i32, // Probable duplicates queued for review
+    pub transfer_count: i32, // Imported rows paired with the other leg of a transfer
+    pub rule_count: i32, // Imported rows changed by a categorisation rule
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+    pub batch_id: Option<String>,
+    pub encoding: Option<String>, // Encoding the file was decoded with
+    pub account_id: Option<String>,
+    pub ledger_balance: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportBatch {
+    pub id: String,
+    pub file_name: String,
+    pub file_hash: String,
+    pub format: String,
+    pub imported_count: i32,
+    pub created_at: String,
+    pub transaction_ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateLevel {
+    Certain,
+    Probable,
+    #[serde(rename = "none")]
+    NotDuplicate,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DuplicateMatch {
+    pub level: DuplicateLevel,
+    pub score: f64,
+    pub transaction_id: Option<String>, // Best matching stored transaction
+}
+
+impl DuplicateMatch {
+    pub fn none() -> Self {
+        DuplicateMatch { level: DuplicateLevel::NotDuplicate, score: 0.0, transaction_id: None }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DuplicateReview {
+    pub id: String,
+    pub batch_id: Option<String>,
+    pub transaction: Transaction,
+    pub existing: Option<Transaction>, // None once the matched transaction was deleted
+    pub score: f64,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DateOrder {
+    DayFirst,   // 15/01/2024
+    MonthFirst, // 01/15/2024
+}
+
+impl Default for DateOrder {
+    fn default() -> Self {
+        DateOrder::DayFirst
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub struct AmountLocale {
+    // None lets the parser infer the separator from the amount itself
+    pub decimal_separator: Option<char>,
+    pub thousands_separator: Option<char>,
+}
+
+impl AmountLocale {
+    pub fn french() -> Self {
+        AmountLocale { decimal_separator: Some(','), thousands_separator: Some(' ') }
+    }
+
+    pub fn english() -> Self {
+        AmountLocale { decimal_separator: Some('.'), thousands_separator: Some(',') }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ColumnMapping {
+    // Each column is referenced by its header name or by its zero-based index
+    pub date: String,
+    pub description: String,
+    pub amount: Option<String>,
+    pub debit: Option<String>,
+    pub credit: Option<String>,
+    pub category: Option<String>,
+    pub account: Option<String>,
+    pub delimiter: char,
+    pub has_header: bool,
+    #[serde(default)]
+    pub date_order: DateOrder,
+    #[serde(default)]
+    pub amount_locale: AmountLocale,
+    #[serde(default)]
+    pub skip_rows: usize, // Lines above the header row
+    #[serde(default)]
+    pub invert_sign: bool,
+    #[serde(default = "default_category")]
+    pub default_category: String,
+    #[serde(default = "default_account")]
+    pub default_account: String,
+}
+
+fn default_category() -> String {
+    DEFAULT_CATEGORY.to_string()
+}
+
+fn default_account() -> String {
+    DEFAULT_ACCOUNT.to_string()
+}
+
+impl Default for ColumnMapping {
+    fn default() -> Self {
+        ColumnMapping {
+            date: "date".to_string(),
+            description: "description".to_string(),
+            amount: Some("amount".to_string()),
+            debit: None,
+            credit: None,
+            category: Some("category".to_string()),
+            account: Some("account".to_string()),
+            delimiter: ',',
+            has_header: true,
+            date_order: DateOrder::DayFirst,
+            amount_locale: AmountLocale::default(),
+            skip_rows: 0,
+            invert_sign: false,
+            default_category: DEFAULT_CATEGORY.to_string(),
+            default_account: DEFAULT_ACCOUNT.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum FileFormat {
+    Csv,
+    Ofx,
+    Qif,
+    Mt940,
+    Camt053,
+    Unknown,
+}
+
+impl FileFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileFormat::Csv => "CSV",
+            FileFormat::Ofx => "OFX",
+            FileFormat::Qif => "QIF",
+            FileFormat::Mt940 => "MT940",
+            FileFormat::Camt053 => "CAMT053",
+            FileFormat::Unknown => "UNKNOWN",
+        }
+    }
+
+    // Maps the file_type sent by the import page; "auto" is handled by the caller
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_uppercase().as_str() {
+            "CSV" | "TXT" => Some(FileFormat::Csv),
+            "OFX" | "QFX" => Some(FileFormat::Ofx),
+            "QIF" => Some(FileFormat::Qif),
+            "MT940" | "STA" => Some(FileFormat::Mt940),
+            "CAMT" | "CAMT053" | "CAMT.053" => Some(FileFormat::Camt053),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FormatDetection {
+    pub format: FileFormat,
+    pub confidence: f32,
+    pub delimiter: Option<char>,
+    pub encoding: String,
+    pub has_header: bool,
+    pub headers: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImportProfile {
+    pub id: String,
+    pub name: String,
+    pub mapping: ColumnMapping,
+    pub account: Option<String>, // Every imported row goes to this account when set
+    #[serde(default)]
+    pub encoding: Option<String>, // Overrides detection for banks that mislabel their files
+    #[serde(default)]
+    pub header_fingerprint: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportCandidate {
+    pub line: usize,
+    pub transaction: Transaction,
+    pub duplicate: DuplicateMatch, // Best match among the stored transactions
+    pub duplicate_of_line: Option<usize>, // Earlier line of the same file with the same external ID
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportPreview {
+    pub format: FileFormat,
+    pub encoding: String,
+    pub candidates: Vec<ImportCandidate>,
+    pub duplicates: Vec<ImportCandidate>,
+    pub probable_duplicates: Vec<ImportCandidate>, // Queued for review on import
     pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+    pub suggested_profile: Option<ImportProfile>,
+    pub account_id: Option<String>,
+    pub ledger_balance: Option<f64>,
 }
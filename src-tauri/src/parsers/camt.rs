@@ -0,0 +1,377 @@
+use anyhow::{Result, anyhow};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use crate::models::AmountLocale;
+use crate::utils::parse_amount;
+use super::{verify_balances, ParseOutput, ParsedTransaction, StatementInfo, DEFAULT_ACCOUNT, DEFAULT_CATEGORY};
+
+// ISO 20022 amounts are plain decimals with a '.' separator
+const ISO_AMOUNT: AmountLocale = AmountLocale { decimal_separator: Some('.'), thousands_separator: None };
+
+#[derive(Default)]
+struct PendingBalance {
+    code: Option<String>,
+    amount: Option<String>,
+    indicator: Option<String>,
+    date: Option<String>,
+}
+
+#[derive(Default)]
+struct PendingEntry {
+    line: usize,
+    amount: Option<String>,
+    indicator: Option<String>,
+    reversal: bool,
+    booking_date: Option<String>,
+    value_date: Option<String>,
+    reference: Option<String>,
+    creditor: Option<String>,
+    debtor: Option<String>,
+    remittance: Vec<String>,
+    additional_info: Option<String>,
+}
+
+#[derive(Default)]
+struct Statement {
+    line: usize,
+    opening: Option<f64>,
+    closing: Option<f64>,
+    movements: f64,
+}
+
+// Parses ISO 20022 bank-to-customer statements (camt.053). Each <Stmt> is
+// checked against its opening (OPBD/PRCD) and closing (CLBD) balances.
+pub fn parse(content: &str) -> Result<ParseOutput> {
+    if !content.contains("BkToCstmrStmt") {
+        return Err(anyhow!("Fichier CAMT.053 invalide: élément BkToCstmrStmt introuvable"));
+    }
+
+    let mut reader = Reader::from_str(content);
+    reader.trim_text(true);
+
+    let mut output = ParseOutput::default();
+    let mut info = StatementInfo::default();
+    let mut path: Vec<String> = Vec::new();
+    let mut statement: Option<Statement> = None;
+    let mut balance: Option<PendingBalance> = None;
+    let mut entry: Option<PendingEntry> = None;
+
+    loop {
+        let position = reader.buffer_position();
+        let event = reader.read_event()
+            .map_err(|e| anyhow!("XML invalide à la ligne {}: {}", line_number(content, position), e))?;
+
+        match event {
+            Event::Start(element) => {
+                let name = String::from_utf8_lossy(element.local_name().as_ref()).to_string();
+                match name.as_str() {
+                    "Stmt" => statement = Some(Statement { line: line_number(content, position), ..Default::default() }),
+                    "Bal" => balance = Some(PendingBalance::default()),
+                    "Ntry" => entry = Some(PendingEntry { line: line_number(content, position), ..Default::default() }),
+                    _ => {}
+                }
+                path.push(name);
+            }
+            Event::Text(text) => {
+                let value = text.unescape()?.trim().to_string();
+                if value.is_empty() {
+                    continue;
+                }
+                let tail: Vec<&str> = path.iter().rev().take(3).map(String::as_str).collect();
+
+                if let Some(current) = entry.as_mut() {
+                    read_entry_field(current, &tail, value);
+                } else if let Some(current) = balance.as_mut() {
+                    match tail.as_slice() {
+                        ["Cd", "CdOrPrtry", "Tp"] => current.code = Some(value),
+                        ["Amt", "Bal", ..] => current.amount = Some(value),
+                        ["CdtDbtInd", "Bal", ..] => current.indicator = Some(value),
+                        ["Dt", "Dt", "Bal"] | ["DtTm", "Dt", "Bal"] => current.date = Some(value),
+                        _ => {}
+                    }
+                } else {
+                    match tail.as_slice() {
+                        ["IBAN", "Id", "Acct"] => info.account_id = Some(value),
+                        ["Id", "Othr", "Id"] if info.account_id.is_none() => info.account_id = Some(value),
+                        ["Ccy", "Acct", ..] => info.currency = Some(value),
+                        ["BIC", ..] | ["BICFI", ..] => info.bank_id = Some(value),
+                        _ => {}
+                    }
+                }
+            }
+            Event::End(element) => {
+                let name = String::from_utf8_lossy(element.local_name().as_ref()).to_string();
+                path.pop();
+                match name.as_str() {
+                    "Bal" => {
+                        if let Some(finished) = balance.take() {
+                            let line = line_number(content, position);
+                            apply_balance(finished, statement.as_mut(), &mut info, &mut output, line);
+                        }
+                    }
+                    "Ntry" => {
+                        if let Some(finished) = entry.take() {
+                            let line = finished.line;
+                            match build_transaction(finished, &info) {
+                                Ok(transaction) => {
+                                    if let Some(current) = statement.as_mut() {
+                                        current.movements += transaction.amount;
+                                    }
+                                    output.transactions.push(transaction);
+                                }
+                                Err(e) => output.push_error(line, e),
+                            }
+                        }
+                    }
+                    "Stmt" => {
+                        if let Some(finished) = statement.take() {
+                            if finished.closing.is_none() {
+                                output.push_error(finished.line, "Relevé incomplet: solde de clôture CLBD manquant");
+                            } else if let Some(error) = verify_balances(finished.opening, finished.closing, finished.movements) {
+                                output.push_error(finished.line, error);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if statement.is_some() || entry.is_some() {
+        output.errors.push("Relevé incomplet: fichier CAMT.053 tronqué".to_string());
+    }
+
+    if let Some(account_id) = &info.account_id {
+        for transaction in &mut output.transactions {
+            transaction.account = account_id.clone();
+        }
+    }
+
+    output.statement = Some(info);
+    Ok(output)
+}
+
+fn read_entry_field(entry: &mut PendingEntry, tail: &[&str], value: String) {
+    match tail {
+        ["Amt", "Ntry", ..] => entry.amount = Some(value),
+        ["CdtDbtInd", "Ntry", ..] => entry.indicator = Some(value),
+        ["RvslInd", "Ntry", ..] => entry.reversal = value == "true",
+        ["Dt", "BookgDt", ..] | ["DtTm", "BookgDt", ..] => entry.booking_date = Some(value),
+        ["Dt", "ValDt", ..] | ["DtTm", "ValDt", ..] => entry.value_date = Some(value),
+        ["AcctSvcrRef", "Ntry", ..] => entry.reference = Some(value),
+        ["Ustrd", "RmtInf", ..] => entry.remittance.push(value),
+        ["AddtlNtryInf", ..] | ["AddtlTxInf", ..] => entry.additional_info = Some(value),
+        ["Nm", "Cdtr", ..] | ["Nm", "Pty", "Cdtr"] => entry.creditor = Some(value),
+        ["Nm", "Dbtr", ..] | ["Nm", "Pty", "Dbtr"] => entry.debtor = Some(value),
+        _ => {}
+    }
+}
+
+fn apply_balance(
+    balance: PendingBalance,
+    statement: Option<&mut Statement>,
+    info: &mut StatementInfo,
+    output: &mut ParseOutput,
+    line: usize,
+) {
+    let amount = match balance.amount.as_deref().map(|raw| parse_amount(raw, &ISO_AMOUNT)) {
+        Some(Ok(amount)) => amount.magnitude,
+        _ => {
+            output.push_error(line, "Solde CAMT invalide");
+            return;
+        }
+    };
+    let amount = if balance.indicator.as_deref() == Some("DBIT") { -amount } else { amount };
+
+    match balance.code.as_deref() {
+        Some("OPBD") | Some("PRCD") => {
+            if info.opening_balance.is_none() {
+                info.opening_balance = Some(amount);
+            }
+            if let Some(statement) = statement {
+                statement.opening = Some(amount);
+            }
+        }
+        Some("CLBD") => {
+            info.closing_balance = Some(amount);
+            info.ledger_balance = Some(amount);
+            info.ledger_balance_date = balance.date.map(|d| d.chars().take(10).collect());
+            if let Some(statement) = statement {
+                statement.closing = Some(amount);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn build_transaction(entry: PendingEntry, info: &StatementInfo) -> Result<ParsedTransaction> {
+    let raw_amount = entry.amount.ok_or_else(|| anyhow!("Montant manquant"))?;
+    let amount = parse_amount(&raw_amount, &ISO_AMOUNT)?.magnitude;
+    let mut sign = match entry.indicator.as_deref() {
+        Some("CRDT") => 1.0,
+        Some("DBIT") => -1.0,
+        _ => return Err(anyhow!("Sens CdtDbtInd manquant")),
+    };
+    if entry.reversal {
+        sign = -sign;
+    }
+
+    let date = entry.booking_date
+        .or(entry.value_date)
+        .ok_or_else(|| anyhow!("Date de comptabilisation manquante"))?;
+    // ISODate or ISODateTime: the first ten characters are YYYY-MM-DD
+    let date: String = date.chars().take(10).collect();
+    chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d").map_err(|_| anyhow!("Format de date invalide"))?;
+
+    // The counterparty is the creditor of a debit and the debtor of a credit
+    let counterparty = if sign < 0.0 { entry.creditor } else { entry.debtor };
+    let mut parts = Vec::new();
+    if let Some(counterparty) = counterparty {
+        parts.push(counterparty);
+    }
+    parts.extend(entry.remittance);
+    if parts.is_empty() {
+        if let Some(additional_info) = entry.additional_info {
+            parts.push(additional_info);
+        }
+    }
+    if parts.is_empty() {
+        return Err(anyhow!("Description manquante"));
+    }
+
+    Ok(ParsedTransaction {
+        line: entry.line,
+        date,
+        description: parts.join(" - "),
+        amount: sign * amount,
+        category: DEFAULT_CATEGORY.to_string(),
+        account: info.account_id.clone().unwrap_or_else(|| DEFAULT_ACCOUNT.to_string()),
+        external_id: entry.reference,
+        splits: Vec::new(),
+    })
+}
+
+// The reader position sits before the whitespace it trims, so skip it to
+// land on the line of the element itself
+fn line_number(content: &str, offset: usize) -> usize {
+    let bytes = content.as_bytes();
+    let mut offset = offset.min(bytes.len());
+    while offset < bytes.len() && bytes[offset].is_ascii_whitespace() {
+        offset += 1;
+    }
+    bytes[..offset].iter().filter(|b| **b == b'\n').count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATEMENT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.02">
+  <BkToCstmrStmt>
+    <Stmt>
+      <Id>STMT-2024-01</Id>
+      <Acct>
+        <Id><IBAN>FR7630004000031234567890143</IBAN></Id>
+        <Ccy>EUR</Ccy>
+        <Svcr><FinInstnId><BIC>BNPAFRPP</BIC></FinInstnId></Svcr>
+      </Acct>
+      <Bal>
+        <Tp><CdOrPrtry><Cd>OPBD</Cd></CdOrPrtry></Tp>
+        <Amt Ccy="EUR">1000.00</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <Dt><Dt>2024-01-01</Dt></Dt>
+      </Bal>
+      <Bal>
+        <Tp><CdOrPrtry><Cd>CLBD</Cd></CdOrPrtry></Tp>
+        <Amt Ccy="EUR">957.50</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <Dt><Dt>2024-01-31</Dt></Dt>
+      </Bal>
+      <Ntry>
+        <Amt Ccy="EUR">42.50</Amt>
+        <CdtDbtInd>DBIT</CdtDbtInd>
+        <BookgDt><Dt>2024-01-15</Dt></BookgDt>
+        <ValDt><Dt>2024-01-16</Dt></ValDt>
+        <AcctSvcrRef>REF-0001</AcctSvcrRef>
+        <NtryDtls><TxDtls>
+          <RltdPties><Cdtr><Nm>CARREFOUR</Nm></Cdtr></RltdPties>
+          <RmtInf><Ustrd>FACTURE 123</Ustrd></RmtInf>
+        </TxDtls></NtryDtls>
+      </Ntry>
+    </Stmt>
+  </BkToCstmrStmt>
+</Document>"#;
+
+    #[test]
+    fn parses_entries_and_balances() {
+        let output = parse(STATEMENT).unwrap();
+        assert!(output.errors.is_empty());
+        assert_eq!(output.transactions.len(), 1);
+
+        let transaction = &output.transactions[0];
+        assert_eq!(transaction.date, "2024-01-15");
+        assert_eq!(transaction.amount, -42.5);
+        assert_eq!(transaction.description, "CARREFOUR - FACTURE 123");
+        assert_eq!(transaction.external_id.as_deref(), Some("REF-0001"));
+        assert_eq!(transaction.account, "FR7630004000031234567890143");
+        assert_eq!(transaction.line, 23);
+
+        let statement = output.statement.unwrap();
+        assert_eq!(statement.currency.as_deref(), Some("EUR"));
+        assert_eq!(statement.bank_id.as_deref(), Some("BNPAFRPP"));
+        assert_eq!(statement.opening_balance, Some(1000.0));
+        assert_eq!(statement.closing_balance, Some(957.5));
+        assert_eq!(statement.ledger_balance_date.as_deref(), Some("2024-01-31"));
+    }
+
+    #[test]
+    fn reads_other_account_identifiers() {
+        let content = STATEMENT.replace(
+            "<IBAN>FR7630004000031234567890143</IBAN>",
+            "<Othr><Id>00012345678</Id></Othr>",
+        );
+        let output = parse(&content).unwrap();
+        assert_eq!(output.statement.unwrap().account_id.as_deref(), Some("00012345678"));
+        assert_eq!(output.transactions[0].account, "00012345678");
+    }
+
+    #[test]
+    fn reports_a_balance_mismatch() {
+        let content = STATEMENT.replace("957.50", "950.00");
+        let output = parse(&content).unwrap();
+        assert_eq!(output.transactions.len(), 1);
+        assert_eq!(output.errors.len(), 1);
+        assert!(output.errors[0].starts_with("Ligne 4: Relevé incomplet"));
+    }
+
+    #[test]
+    fn reports_a_truncated_file() {
+        let end = STATEMENT.find("</Ntry>").unwrap();
+        let output = parse(&STATEMENT[..end]).unwrap();
+        assert!(output.transactions.is_empty());
+        assert_eq!(output.errors, vec!["Relevé incomplet: fichier CAMT.053 tronqué".to_string()]);
+    }
+
+    #[test]
+    fn applies_reversals_and_falls_back_to_the_value_date() {
+        let content = STATEMENT
+            .replace("<BookgDt><Dt>2024-01-15</Dt></BookgDt>", "<RvslInd>true</RvslInd>")
+            .replace("957.50", "1042.50");
+        let output = parse(&content).unwrap();
+        assert!(output.errors.is_empty());
+        let transaction = &output.transactions[0];
+        assert_eq!(transaction.amount, 42.5);
+        assert_eq!(transaction.date, "2024-01-16");
+        // A reversed debit is a credit, so the debtor would be the counterparty
+        assert_eq!(transaction.description, "FACTURE 123");
+    }
+
+    #[test]
+    fn rejects_other_xml_documents() {
+        assert!(parse("<Document><CstmrCdtTrfInitn/></Document>").is_err());
+    }
+}
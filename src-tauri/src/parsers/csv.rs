@@ -0,0 +1,322 @@
+use csv::{Position, Reader, ReaderBuilder, StringRecord};
+use sha2::{Digest, Sha256};
+use anyhow::{Result, anyhow};
+use crate::models::ColumnMapping;
+use crate::utils::{parse_amount, parse_date_with_order};
+use super::{ParseOutput, ParsedTransaction};
+
+struct ResolvedColumns {
+    date: usize,
+    description: usize,
+    amount: Option<usize>,
+    debit: Option<usize>,
+    credit: Option<usize>,
+    category: Option<usize>,
+    account: Option<usize>,
+}
+
+pub fn parse(content: &str, mapping: &ColumnMapping) -> Result<ParseOutput> {
+    let body = body(content, mapping);
+    let mut reader = reader(body, mapping)?;
+
+    let headers = if mapping.has_header {
+        Some(reader.headers()?.clone())
+    } else {
+        None
+    };
+    let columns = resolve_columns(headers.as_ref(), mapping)?;
+
+    let mut output = ParseOutput::default();
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                let line = e.position().map(|p| file_line(body, p, mapping)).unwrap_or(0);
+                output.push_error(line, format!("Ligne illisible: {}", e));
+                continue;
+            }
+        };
+
+        let line = record.position().map(|p| file_line(body, p, mapping)).unwrap_or(0);
+        if record.iter().all(|field| field.is_empty()) {
+            continue;
+        }
+
+        match parse_record(&record, &columns, mapping, line) {
+            Ok(transaction) => output.transactions.push(transaction),
+            Err(e) => output.push_error(line, e),
+        }
+    }
+
+    Ok(output)
+}
+
+// Identifies a bank's export layout by its header row, so a saved import
+// profile can be suggested for a new file. Names are compared case- and
+// whitespace-insensitively.
+pub fn header_fingerprint(content: &str, mapping: &ColumnMapping) -> Option<String> {
+    if !mapping.has_header {
+        return None;
+    }
+    let mut reader = reader(body(content, mapping), mapping).ok()?;
+    let headers = reader.headers().ok()?;
+    if headers.iter().all(|h| h.is_empty()) {
+        return None;
+    }
+
+    let normalised = headers
+        .iter()
+        .map(|h| h.trim().to_lowercase())
+        .collect::<Vec<_>>()
+        .join("|");
+    Some(hex::encode(Sha256::digest(normalised.as_bytes())))
+}
+
+// Banks often put the account name and period above the real header
+fn body<'a>(content: &'a str, mapping: &ColumnMapping) -> &'a str {
+    let body = content
+        .split_inclusive('\n')
+        .skip(mapping.skip_rows)
+        .map(str::len)
+        .sum::<usize>();
+    &content[content.len() - body..]
+}
+
+fn reader<'a>(body: &'a str, mapping: &ColumnMapping) -> Result<Reader<&'a [u8]>> {
+    // The csv crate splits on a single byte
+    if !mapping.delimiter.is_ascii() {
+        return Err(anyhow!("Séparateur non pris en charge: {}", mapping.delimiter));
+    }
+
+    Ok(ReaderBuilder::new()
+        .delimiter(mapping.delimiter as u8)
+        .has_headers(mapping.has_header)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(body.as_bytes()))
+}
+
+// The line of the file a record starts on. The csv crate's own line count
+// goes wrong after blank lines, which it folds into the next record, so the
+// line is counted from the record's byte offset past any blank lines.
+fn file_line(body: &str, position: &Position, mapping: &ColumnMapping) -> usize {
+    let start = (position.byte() as usize).min(body.len());
+    let (before, after) = body.as_bytes().split_at(start);
+    let blank = after.iter().take_while(|byte| **byte == b'\n' || **byte == b'\r');
+    let newlines = before.iter().chain(blank).filter(|byte| **byte == b'\n').count();
+    mapping.skip_rows + newlines + 1
+}
+
+fn parse_record(
+    record: &StringRecord,
+    columns: &ResolvedColumns,
+    mapping: &ColumnMapping,
+    line: usize,
+) -> Result<ParsedTransaction> {
+    let date_field = field(record, columns.date).ok_or_else(|| anyhow!("Date manquante"))?;
+    let date = parse_date_with_order(date_field, mapping.date_order).map_err(|_| anyhow!("Format de date invalide"))?;
+
+    let description = field(record, columns.description)
+        .ok_or_else(|| anyhow!("Description manquante"))?
+        .to_string();
+
+    let mut amount = match columns.amount {
+        Some(index) => {
+            let raw = field(record, index).ok_or_else(|| anyhow!("Montant manquant"))?;
+            parse_amount(raw, &mapping.amount_locale)?.value()
+        }
+        None => {
+            let debit = optional_amount(record, columns.debit, mapping)?;
+            let credit = optional_amount(record, columns.credit, mapping)?;
+            if debit.is_none() && credit.is_none() {
+                return Err(anyhow!("Montant manquant"));
+            }
+            credit.unwrap_or(0.0).abs() - debit.unwrap_or(0.0).abs()
+        }
+    };
+
+    // Credit card exports often list purchases as positive amounts
+    if mapping.invert_sign {
+        amount = -amount;
+    }
+
+    let category = columns.category
+        .and_then(|index| field(record, index))
+        .unwrap_or(mapping.default_category.as_str())
+        .to_string();
+    let account = columns.account
+        .and_then(|index| field(record, index))
+        .unwrap_or(mapping.default_account.as_str())
+        .to_string();
+
+    Ok(ParsedTransaction {
+        line,
+        date,
+        description,
+        amount,
+        category,
+        account,
+        external_id: None,
+        splits: Vec::new(),
+    })
+}
+
+fn resolve_columns(headers: Option<&StringRecord>, mapping: &ColumnMapping) -> Result<ResolvedColumns> {
+    let required = |name: &str| {
+        resolve_column(headers, name)
+            .ok_or_else(|| anyhow!("Colonne introuvable dans le fichier: {}", name))
+    };
+    let optional = |name: &Option<String>| -> Result<Option<usize>> {
+        match name {
+            Some(name) => required(name).map(Some),
+            None => Ok(None),
+        }
+    };
+
+    let columns = ResolvedColumns {
+        date: required(&mapping.date)?,
+        description: required(&mapping.description)?,
+        amount: optional(&mapping.amount)?,
+        debit: optional(&mapping.debit)?,
+        credit: optional(&mapping.credit)?,
+        // Category and account fall back to the mapping defaults when absent
+        category: mapping.category.as_deref().and_then(|name| resolve_column(headers, name)),
+        account: mapping.account.as_deref().and_then(|name| resolve_column(headers, name)),
+    };
+
+    if columns.amount.is_none() && columns.debit.is_none() && columns.credit.is_none() {
+        return Err(anyhow!("Aucune colonne de montant (montant ou débit/crédit) n'est définie"));
+    }
+
+    Ok(columns)
+}
+
+fn resolve_column(headers: Option<&StringRecord>, name: &str) -> Option<usize> {
+    let name = name.trim();
+    if let Some(headers) = headers {
+        if let Some(index) = headers.iter().position(|h| h.trim().eq_ignore_ascii_case(name)) {
+            return Some(index);
+        }
+    }
+    name.parse::<usize>().ok()
+}
+
+fn field(record: &StringRecord, index: usize) -> Option<&str> {
+    record.get(index).filter(|value| !value.is_empty())
+}
+
+fn optional_amount(record: &StringRecord, index: Option<usize>, mapping: &ColumnMapping) -> Result<Option<f64>> {
+    match index.and_then(|index| field(record, index)) {
+        Some(raw) => parse_amount(raw, &mapping.amount_locale).map(|amount| Some(amount.value())),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AmountLocale;
+    use crate::parsers::{DEFAULT_ACCOUNT, DEFAULT_CATEGORY};
+
+    fn french_mapping() -> ColumnMapping {
+        ColumnMapping {
+            date: "Date".to_string(),
+            description: "Libellé".to_string(),
+            amount: Some("Montant".to_string()),
+            category: None,
+            account: None,
+            delimiter: ';',
+            amount_locale: AmountLocale::french(),
+            ..ColumnMapping::default()
+        }
+    }
+
+    #[test]
+    fn maps_columns_by_header_name_or_index() {
+        let content = "Date;Libellé;Montant;Catégorie\n15/01/2024;CB CARREFOUR;-42,50;Courses\n16/01/2024;VIR SALAIRE;2 500,00;\n";
+        let mapping = ColumnMapping { category: Some(" catégorie ".to_string()), ..french_mapping() };
+        let output = parse(content, &mapping).unwrap();
+        assert!(output.errors.is_empty());
+        assert_eq!(output.transactions.len(), 2);
+
+        let purchase = &output.transactions[0];
+        assert_eq!((purchase.line, purchase.date.as_str(), purchase.amount), (2, "2024-01-15", -42.5));
+        assert_eq!(purchase.description, "CB CARREFOUR");
+        assert_eq!(purchase.category, "Courses");
+        assert_eq!(purchase.account, DEFAULT_ACCOUNT);
+        // An empty cell falls back to the mapping's default
+        assert_eq!(output.transactions[1].category, DEFAULT_CATEGORY);
+        assert_eq!(output.transactions[1].amount, 2500.0);
+
+        let by_index = ColumnMapping {
+            date: "0".to_string(),
+            description: "1".to_string(),
+            amount: Some("2".to_string()),
+            has_header: false,
+            ..french_mapping()
+        };
+        let output = parse("15/01/2024;CB CARREFOUR;-42,50\n", &by_index).unwrap();
+        assert_eq!(output.transactions[0].line, 1);
+        assert_eq!(output.transactions[0].amount, -42.5);
+
+        let missing = ColumnMapping { amount: Some("Somme".to_string()), ..french_mapping() };
+        assert!(parse(content, &missing).is_err());
+    }
+
+    #[test]
+    fn combines_debit_and_credit_columns() {
+        let content = "Date;Libellé;Débit;Crédit\n15/01/2024;CB CARREFOUR;42,50;\n16/01/2024;VIR SALAIRE;;2500,00\n17/01/2024;FRAIS;-3,00;\n";
+        let mapping = ColumnMapping {
+            amount: None,
+            debit: Some("Débit".to_string()),
+            credit: Some("Crédit".to_string()),
+            ..french_mapping()
+        };
+        let output = parse(content, &mapping).unwrap();
+        let amounts: Vec<f64> = output.transactions.iter().map(|t| t.amount).collect();
+        // Debits are taken as withdrawals whatever their sign in the file
+        assert_eq!(amounts, vec![-42.5, 2500.0, -3.0]);
+
+        let inverted = ColumnMapping { invert_sign: true, ..mapping };
+        assert_eq!(parse(content, &inverted).unwrap().transactions[0].amount, 42.5);
+
+        let no_amount = ColumnMapping { amount: None, ..french_mapping() };
+        assert!(parse(content, &no_amount).is_err());
+    }
+
+    #[test]
+    fn skips_rows_above_the_header_and_reports_file_lines() {
+        let content = "Compte courant n° 0123\nPériode du 01/01/2024 au 31/01/2024\nDate;Libellé;Montant\n15/01/2024;CB CARREFOUR;-42,50\n32/01/2024;CB FNAC;-10,00\n";
+        let mapping = ColumnMapping { skip_rows: 2, ..french_mapping() };
+        let output = parse(content, &mapping).unwrap();
+        assert_eq!(output.transactions.len(), 1);
+        assert_eq!(output.transactions[0].line, 4);
+        assert_eq!(output.errors, vec!["Ligne 5: Format de date invalide"]);
+
+        // Without skipping, the title line is taken for the header
+        assert!(parse(content, &french_mapping()).is_err());
+    }
+
+    #[test]
+    fn reports_bad_rows_and_keeps_the_others() {
+        let content = "Date;Libellé;Montant\n15/01/2024;CB CARREFOUR;abc\n\n;CB FNAC;-10,00\n16/01/2024;;-5,00\n17/01/2024;CB AUCHAN;\n18/01/2024;CB LIDL;-7,20\n";
+        let output = parse(content, &french_mapping()).unwrap();
+        assert_eq!(output.transactions.len(), 1);
+        assert_eq!(output.transactions[0].line, 7);
+        assert_eq!(output.errors.len(), 4);
+        // The blank line is skipped without an error but still counted
+        assert!(output.errors[0].starts_with("Ligne 2: "));
+        assert_eq!(output.errors[1..], [
+            "Ligne 4: Date manquante".to_string(),
+            "Ligne 5: Description manquante".to_string(),
+            "Ligne 6: Montant manquant".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn rejects_a_delimiter_the_reader_cannot_split_on() {
+        let mapping = ColumnMapping { delimiter: '¦', ..french_mapping() };
+        assert!(parse("Date¦Libellé¦Montant\n15/01/2024¦CB¦-1,00\n", &mapping).is_err());
+        assert!(header_fingerprint("Date¦Libellé¦Montant\n", &mapping).is_none());
+    }
+}
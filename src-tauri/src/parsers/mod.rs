@@ -0,0 +1,75 @@
+pub mod csv;
+pub mod ofx;
+pub mod qif;
+pub mod mt940;
+pub mod camt;
+
+pub const DEFAULT_CATEGORY: &str = "Non catégorisé";
+pub const DEFAULT_ACCOUNT: &str = "Compte principal";
+pub const CATEGORY_PATH_SEPARATOR: &str = " > ";
+pub const TRANSFER_CATEGORY: &str = "Virement";
+
+// A statement line as read from an imported file, before it becomes a Transaction
+#[derive(Debug, Clone)]
+pub struct ParsedTransaction {
+    pub line: usize,
+    pub date: String,
+    pub description: String,
+    pub amount: f64,
+    pub category: String,
+    pub account: String,
+    pub external_id: Option<String>, // Bank-assigned identifier such as the OFX FITID
+    pub splits: Vec<ParsedSplit>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ParsedSplit {
+    pub category: String,
+    pub memo: String,
+    pub amount: f64,
+}
+
+// Account-level data carried by structured statements (OFX, MT940...)
+#[derive(Debug, Default, Clone)]
+pub struct StatementInfo {
+    pub account_id: Option<String>,
+    pub bank_id: Option<String>,
+    pub branch_id: Option<String>,
+    pub account_type: Option<String>,
+    pub currency: Option<String>,
+    pub ledger_balance: Option<f64>,
+    pub ledger_balance_date: Option<String>,
+    pub opening_balance: Option<f64>,
+    pub closing_balance: Option<f64>,
+}
+
+#[derive(Debug, Default)]
+pub struct ParseOutput {
+    pub transactions: Vec<ParsedTransaction>,
+    pub errors: Vec<String>,
+    pub statement: Option<StatementInfo>,
+}
+
+impl ParseOutput {
+    pub fn push_error(&mut self, line: usize, reason: impl std::fmt::Display) {
+        self.errors.push(format!("Ligne {}: {}", line, reason));
+    }
+}
+
+// Opening balance plus the parsed movements must give the closing balance;
+// a gap means the statement was truncated or lines failed to parse
+pub fn verify_balances(opening: Option<f64>, closing: Option<f64>, movements: f64) -> Option<String> {
+    let (opening, closing) = match (opening, closing) {
+        (Some(opening), Some(closing)) => (opening, closing),
+        _ => return None,
+    };
+    let expected = opening + movements;
+    if (expected - closing).abs() > 0.005 {
+        Some(format!(
+            "Relevé incomplet: solde d'ouverture {:.2} + mouvements {:.2} = {:.2}, solde de clôture {:.2}",
+            opening, movements, expected, closing
+        ))
+    } else {
+        None
+    }
+}
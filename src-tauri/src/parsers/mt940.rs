@@ -0,0 +1,380 @@
+use anyhow::{Result, anyhow};
+use chrono::NaiveDate;
+use crate::models::AmountLocale;
+use crate::utils::parse_amount;
+use super::{verify_balances, ParseOutput, ParsedTransaction, StatementInfo, DEFAULT_ACCOUNT, DEFAULT_CATEGORY};
+
+// SWIFT amounts always use a comma as decimal separator and no grouping
+const SWIFT_AMOUNT: AmountLocale = AmountLocale { decimal_separator: Some(','), thousands_separator: None };
+
+// One tagged field (":61:...") with its continuation lines
+struct Field {
+    tag: String,
+    value: String,
+    line: usize,
+}
+
+struct Balance {
+    amount: f64,
+    date: String,
+    currency: String,
+}
+
+#[derive(Default)]
+struct Statement {
+    line: usize,
+    account: Option<String>,
+    opening: Option<Balance>,
+    closing: Option<Balance>,
+    movements: f64,
+}
+
+// Parses SWIFT MT940 customer statements. A file may hold several statements
+// (one per :20: field), each checked against its own opening and closing balance.
+pub fn parse(content: &str) -> Result<ParseOutput> {
+    let fields = read_fields(content);
+    if !fields.iter().any(|f| f.tag == "20") || !fields.iter().any(|f| f.tag == "61" || f.tag == "62F") {
+        return Err(anyhow!("Fichier MT940 invalide: champs :20: ou :62F: introuvables"));
+    }
+
+    let mut output = ParseOutput::default();
+    let mut info = StatementInfo::default();
+    let mut statement: Option<Statement> = None;
+    let mut last_transaction: Option<usize> = None;
+
+    for field in &fields {
+        match field.tag.as_str() {
+            "20" => {
+                if let Some(finished) = statement.take() {
+                    finish_statement(finished, &mut output, &mut info);
+                }
+                statement = Some(Statement { line: field.line, ..Default::default() });
+                last_transaction = None;
+            }
+            "25" => {
+                let account = field.value.trim().to_string();
+                if let Some(current) = statement.as_mut() {
+                    current.account = Some(account.clone());
+                }
+                info.account_id = Some(account);
+            }
+            "60F" | "60M" => match parse_balance(&field.value) {
+                Ok(balance) => {
+                    if info.opening_balance.is_none() {
+                        info.opening_balance = Some(balance.amount);
+                    }
+                    info.currency = Some(balance.currency.clone());
+                    if let Some(current) = statement.as_mut() {
+                        current.opening = Some(balance);
+                    }
+                }
+                Err(e) => output.push_error(field.line, e),
+            },
+            "61" => {
+                last_transaction = None;
+                match parse_movement(&field.value, field.line) {
+                    Ok(mut transaction) => {
+                        if let Some(current) = statement.as_mut() {
+                            current.movements += transaction.amount;
+                            if let Some(account) = &current.account {
+                                transaction.account = account.clone();
+                            }
+                        }
+                        output.transactions.push(transaction);
+                        last_transaction = Some(output.transactions.len() - 1);
+                    }
+                    Err(e) => output.push_error(field.line, e),
+                }
+            }
+            "86" => {
+                // Information to account owner, describing the preceding :61:
+                if let Some(index) = last_transaction {
+                    let details = clean_details(&field.value);
+                    if !details.is_empty() {
+                        output.transactions[index].description = details;
+                    }
+                }
+            }
+            "62F" | "62M" => match parse_balance(&field.value) {
+                Ok(balance) => {
+                    info.closing_balance = Some(balance.amount);
+                    info.ledger_balance = Some(balance.amount);
+                    info.ledger_balance_date = Some(balance.date.clone());
+                    if let Some(current) = statement.as_mut() {
+                        current.closing = Some(balance);
+                    }
+                }
+                Err(e) => output.push_error(field.line, e),
+            },
+            _ => {}
+        }
+    }
+
+    if let Some(finished) = statement.take() {
+        finish_statement(finished, &mut output, &mut info);
+    }
+
+    output.statement = Some(info);
+    Ok(output)
+}
+
+fn finish_statement(statement: Statement, output: &mut ParseOutput, info: &mut StatementInfo) {
+    if info.account_id.is_none() {
+        info.account_id = statement.account.clone();
+    }
+    let opening = statement.opening.as_ref().map(|b| b.amount);
+    let closing = statement.closing.as_ref().map(|b| b.amount);
+    if closing.is_none() {
+        output.push_error(statement.line, "Relevé incomplet: solde de clôture :62F: manquant");
+        return;
+    }
+    if let Some(error) = verify_balances(opening, closing, statement.movements) {
+        output.push_error(statement.line, error);
+    }
+}
+
+fn read_fields(content: &str) -> Vec<Field> {
+    let mut fields: Vec<Field> = Vec::new();
+
+    for (index, raw_line) in content.lines().enumerate() {
+        let line = raw_line.trim_end_matches('\r');
+        // SWIFT envelope blocks ({1:...}{4:) and the message trailer (-})
+        if line.starts_with('{') || line.starts_with("-}") || line.trim() == "-" {
+            continue;
+        }
+
+        if let Some(rest) = line.strip_prefix(':') {
+            if let Some(end) = rest.find(':') {
+                let tag = &rest[..end];
+                if !tag.is_empty() && tag.len() <= 3 && tag.chars().all(|c| c.is_ascii_alphanumeric()) {
+                    fields.push(Field {
+                        tag: tag.to_string(),
+                        value: rest[end + 1..].to_string(),
+                        line: index + 1,
+                    });
+                    continue;
+                }
+            }
+        }
+
+        if let Some(last) = fields.last_mut() {
+            last.value.push('\n');
+            last.value.push_str(line);
+        }
+    }
+
+    fields
+}
+
+// C240101EUR1234,56
+fn parse_balance(value: &str) -> Result<Balance> {
+    let value = value.trim();
+    if value.len() < 11 || !value.is_ascii() {
+        return Err(anyhow!("Solde MT940 invalide: {}", value));
+    }
+    let sign = match &value[..1] {
+        "C" => 1.0,
+        "D" => -1.0,
+        other => return Err(anyhow!("Sens de solde inconnu: {}", other)),
+    };
+    let date = parse_mt940_date(&value[1..7])?;
+    let currency = value[7..10].to_string();
+    let amount = parse_amount(&value[10..], &SWIFT_AMOUNT)?.magnitude;
+
+    Ok(Balance { amount: sign * amount, date, currency })
+}
+
+// :61:2401020102D12,50NTRFNONREF//8327000090031789
+//      value date, optional entry date, debit/credit mark, optional funds
+//      code, amount, transaction type, references
+fn parse_movement(value: &str, line: usize) -> Result<ParsedTransaction> {
+    let first_line = value.lines().next().unwrap_or("").trim();
+    let supplementary = value.lines().nth(1).map(str::trim).unwrap_or("");
+    let bytes = first_line.as_bytes();
+    if bytes.len() < 10 || !first_line.is_ascii() {
+        return Err(anyhow!("Mouvement :61: invalide"));
+    }
+
+    let date = parse_mt940_date(&first_line[..6]).map_err(|_| anyhow!("Format de date invalide"))?;
+    let mut position = 6;
+    if bytes.len() > position + 4 && bytes[position..position + 4].iter().all(|b| b.is_ascii_digit()) {
+        position += 4;
+    }
+
+    let rest = &first_line[position..];
+    let (sign, mark_length) = if rest.starts_with("RC") {
+        (-1.0, 2) // Reversal of a credit
+    } else if rest.starts_with("RD") {
+        (1.0, 2) // Reversal of a debit
+    } else if rest.starts_with('C') {
+        (1.0, 1)
+    } else if rest.starts_with('D') {
+        (-1.0, 1)
+    } else {
+        return Err(anyhow!("Sens de mouvement inconnu"));
+    };
+    position += mark_length;
+
+    // Optional third character of the currency code (funds code)
+    if bytes.get(position).map(|b| b.is_ascii_alphabetic()).unwrap_or(false) {
+        position += 1;
+    }
+
+    let amount_length = first_line[position..]
+        .find(|c: char| !(c.is_ascii_digit() || c == ','))
+        .unwrap_or(first_line.len() - position);
+    let raw_amount = &first_line[position..position + amount_length];
+    let amount = parse_amount(raw_amount, &SWIFT_AMOUNT)?.magnitude;
+    position += amount_length;
+
+    // Transaction type (N/F/S + 3 characters) followed by the references
+    let references = first_line.get(position + 4..).unwrap_or("");
+    let customer_reference = references.split("//").next().unwrap_or("").trim();
+    let description = if !supplementary.is_empty() {
+        supplementary.to_string()
+    } else if !customer_reference.is_empty() && customer_reference != "NONREF" {
+        customer_reference.to_string()
+    } else {
+        first_line.get(position..position + 4).unwrap_or("").to_string()
+    };
+
+    Ok(ParsedTransaction {
+        line,
+        date,
+        description,
+        amount: sign * amount,
+        category: DEFAULT_CATEGORY.to_string(),
+        account: DEFAULT_ACCOUNT.to_string(),
+        external_id: None,
+        splits: Vec::new(),
+    })
+}
+
+// Structured :86: fields (?20...?29 remittance, ?32/?33 counterparty) are
+// flattened to plain text; other banks send free text over several lines.
+fn clean_details(value: &str) -> String {
+    let joined = value.lines().map(str::trim).collect::<Vec<_>>().join("");
+    let structured = joined.len() > 4
+        && joined.as_bytes()[..3].iter().all(|b| b.is_ascii_digit())
+        && joined.as_bytes()[3] == b'?';
+    if !structured {
+        return value.lines().map(str::trim).filter(|l| !l.is_empty()).collect::<Vec<_>>().join(" ");
+    }
+
+    let mut counterparty = Vec::new();
+    let mut remittance = Vec::new();
+    for part in joined.split('?').skip(1) {
+        // A '?' inside the text can be followed by anything, including a
+        // multi-byte character, so the code is only read on a char boundary
+        let (code, text) = match (part.get(..2), part.get(2..)) {
+            (Some(code), Some(text)) => (code, text),
+            _ => continue,
+        };
+        let text = text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        match code {
+            "32" | "33" => counterparty.push(text),
+            "20" | "21" | "22" | "23" | "24" | "25" | "26" | "27" | "28" | "29"
+            | "60" | "61" | "62" | "63" => remittance.push(text),
+            _ => {}
+        }
+    }
+
+    counterparty.extend(remittance);
+    counterparty.join(" ")
+}
+
+fn parse_mt940_date(value: &str) -> Result<String> {
+    let date = NaiveDate::parse_from_str(&format!("20{}", value), "%Y%m%d")
+        .map_err(|_| anyhow!("Date MT940 invalide: {}", value))?;
+    Ok(date.format("%Y-%m-%d").to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATEMENT: &str = "{1:F01BNPAFRPPAXXX0000000000}{2:O9401200240131BNPAFRPPAXXX00000000002401311200N}{4:
+:20:STMT240131
+:25:FR7630004000031234567890143
+:28C:00012/001
+:60F:C240101EUR1000,00
+:61:2401020102D42,50NTRFNONREF//8327000090031789
+:86:CB CARREFOUR
+PARIS
+:61:2401050105C2500,00NTRFSALAIRE
+:86:166?00VIREMENT?20SALAIRE JANVIER?21REF 1234?32ACME SAS
+:62F:C240131EUR3457,50
+-}";
+
+    #[test]
+    fn parses_movements_and_balances() {
+        let output = parse(STATEMENT).unwrap();
+        assert!(output.errors.is_empty());
+        assert_eq!(output.transactions.len(), 2);
+
+        let first = &output.transactions[0];
+        assert_eq!(first.date, "2024-01-02");
+        assert_eq!(first.amount, -42.5);
+        assert_eq!(first.description, "CB CARREFOUR PARIS");
+        assert_eq!(first.account, "FR7630004000031234567890143");
+        assert_eq!(first.line, 6);
+
+        // Structured :86: puts the counterparty before the remittance lines
+        let second = &output.transactions[1];
+        assert_eq!(second.amount, 2500.0);
+        assert_eq!(second.description, "ACME SAS SALAIRE JANVIER REF 1234");
+
+        let statement = output.statement.unwrap();
+        assert_eq!(statement.opening_balance, Some(1000.0));
+        assert_eq!(statement.closing_balance, Some(3457.5));
+        assert_eq!(statement.currency.as_deref(), Some("EUR"));
+        assert_eq!(statement.ledger_balance_date.as_deref(), Some("2024-01-31"));
+    }
+
+    #[test]
+    fn reports_a_balance_mismatch() {
+        let content = STATEMENT.replace(":62F:C240131EUR3457,50", ":62F:C240131EUR3500,00");
+        let output = parse(&content).unwrap();
+        assert_eq!(output.transactions.len(), 2);
+        assert_eq!(output.errors.len(), 1);
+        assert!(output.errors[0].starts_with("Ligne 2: Relevé incomplet"));
+    }
+
+    #[test]
+    fn reports_a_truncated_statement() {
+        let content: String = STATEMENT.lines().take(8).collect::<Vec<_>>().join("\n");
+        let output = parse(&content).unwrap();
+        assert_eq!(output.transactions.len(), 1);
+        assert_eq!(output.errors, vec!["Ligne 2: Relevé incomplet: solde de clôture :62F: manquant".to_string()]);
+    }
+
+    #[test]
+    fn checks_each_statement_against_its_own_balances() {
+        let content = ":20:A\n:25:ACC1\n:60F:C240101EUR100,00\n:61:240102D10,00NTRFNONREF\n:62F:C240102EUR90,00\n\
+                       :20:B\n:25:ACC2\n:60F:D240101EUR5,00\n:61:240103RD5,00NTRFNONREF\n:62F:C240103EUR10,00\n";
+        let output = parse(content).unwrap();
+        assert_eq!(output.transactions.len(), 2);
+        assert_eq!(output.transactions[0].account, "ACC1");
+        // A reversed debit is a credit
+        assert_eq!(output.transactions[1].amount, 5.0);
+        assert_eq!(output.transactions[1].account, "ACC2");
+        assert_eq!(output.errors, vec!["Ligne 6: Relevé incomplet: solde d'ouverture -5.00 + mouvements 5.00 = 0.00, solde de clôture 10.00".to_string()]);
+    }
+
+    #[test]
+    fn skips_question_marks_followed_by_accented_text() {
+        let content = ":20:A\n:25:ACC1\n:60F:C240101EUR100,00\n:61:240102D10,00NTRFNONREF\n\
+                       :86:166?20Quoi? été?32Café\n:62F:C240102EUR90,00\n";
+        let output = parse(content).unwrap();
+        assert!(output.errors.is_empty());
+        assert_eq!(output.transactions[0].description, "Café Quoi");
+    }
+
+    #[test]
+    fn rejects_files_without_statement_fields() {
+        assert!(parse("Date;Libellé;Montant\n").is_err());
+    }
+}
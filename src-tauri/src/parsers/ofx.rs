@@ -0,0 +1,298 @@
+use anyhow::{Result, anyhow};
+use crate::models::AmountLocale;
+use crate::utils::{parse_amount, parse_date};
+use super::{ParseOutput, ParsedTransaction, StatementInfo, DEFAULT_ACCOUNT, DEFAULT_CATEGORY};
+
+// A single element read from the OFX body. Leaf elements carry a value,
+// aggregates only open and close.
+enum Token<'a> {
+    Open { name: &'a str, value: Option<&'a str>, offset: usize },
+    Close { name: &'a str },
+}
+
+#[derive(Default)]
+struct PendingTransaction {
+    line: usize,
+    fitid: Option<String>,
+    date: Option<String>,
+    amount: Option<String>,
+    name: Option<String>,
+    memo: Option<String>,
+}
+
+// Parses OFX 1.x (SGML, unclosed leaf elements) and OFX 2.x (XML) statements.
+// QFX files are OFX with extra Intuit tags and go through the same path.
+pub fn parse(content: &str) -> Result<ParseOutput> {
+    let body_start = content.find("<OFX>")
+        .ok_or_else(|| anyhow!("Fichier OFX invalide: balise <OFX> introuvable"))?;
+
+    let mut output = ParseOutput::default();
+    let mut statement = StatementInfo::default();
+    let mut pending: Option<PendingTransaction> = None;
+    let mut stack: Vec<&str> = Vec::new();
+
+    for token in tokenize(content, body_start) {
+        match token {
+            Token::Open { name, value: None, offset } => {
+                if name == "STMTTRN" {
+                    pending = Some(PendingTransaction {
+                        line: line_number(content, offset),
+                        ..Default::default()
+                    });
+                }
+                stack.push(name);
+            }
+            Token::Open { name, value: Some(value), .. } => {
+                let value = decode_entities(value);
+                let parent = stack.last().copied().unwrap_or("");
+
+                if let Some(transaction) = pending.as_mut() {
+                    match name {
+                        "FITID" => transaction.fitid = Some(value),
+                        "DTPOSTED" => transaction.date = Some(value),
+                        "TRNAMT" => transaction.amount = Some(value),
+                        "NAME" | "PAYEE" => transaction.name = Some(value),
+                        "MEMO" => transaction.memo = Some(value),
+                        _ => {}
+                    }
+                    continue;
+                }
+
+                match (parent, name) {
+                    ("BANKACCTFROM" | "CCACCTFROM", "ACCTID") => statement.account_id = Some(value),
+                    ("BANKACCTFROM", "BANKID") => statement.bank_id = Some(value),
+                    ("BANKACCTFROM", "BRANCHID") => statement.branch_id = Some(value),
+                    ("BANKACCTFROM" | "CCACCTFROM", "ACCTTYPE") => statement.account_type = Some(value),
+                    (_, "CURDEF") => statement.currency = Some(value),
+                    ("LEDGERBAL", "BALAMT") => statement.ledger_balance = parse_amount(&value, &AmountLocale::default()).ok().map(|a| a.value()),
+                    ("LEDGERBAL", "DTASOF") => statement.ledger_balance_date = parse_date(&value).ok(),
+                    _ => {}
+                }
+            }
+            Token::Close { name } => {
+                // SGML files may omit closing tags of leaf elements, so unwind
+                // the stack up to the matching aggregate.
+                if let Some(position) = stack.iter().rposition(|open| *open == name) {
+                    stack.truncate(position);
+                }
+                if name == "STMTTRN" {
+                    if let Some(transaction) = pending.take() {
+                        let line = transaction.line;
+                        match build_transaction(transaction, &statement) {
+                            Ok(parsed) => output.transactions.push(parsed),
+                            Err(e) => output.push_error(line, e),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Transactions are listed before the account aggregate in some exports,
+    // so the account is only known once the whole body has been read.
+    if let Some(account_id) = &statement.account_id {
+        for transaction in &mut output.transactions {
+            transaction.account = account_id.clone();
+        }
+    }
+
+    output.statement = Some(statement);
+    Ok(output)
+}
+
+fn build_transaction(pending: PendingTransaction, statement: &StatementInfo) -> Result<ParsedTransaction> {
+    let date = pending.date.ok_or_else(|| anyhow!("DTPOSTED manquant"))?;
+    let date = parse_date(&date).map_err(|_| anyhow!("Format de date invalide"))?;
+
+    let amount = pending.amount.ok_or_else(|| anyhow!("TRNAMT manquant"))?;
+    // The OFX spec allows either '.' or ',' as the decimal separator
+    let amount = parse_amount(&amount, &AmountLocale::default())?.value();
+
+    let description = match (pending.name, pending.memo) {
+        (Some(name), Some(memo)) if !memo.is_empty() && memo != name => format!("{} - {}", name, memo),
+        (Some(name), _) => name,
+        (None, Some(memo)) => memo,
+        (None, None) => return Err(anyhow!("Description manquante (NAME/MEMO)")),
+    };
+
+    Ok(ParsedTransaction {
+        line: pending.line,
+        date,
+        description,
+        amount,
+        category: DEFAULT_CATEGORY.to_string(),
+        account: statement.account_id.clone().unwrap_or_else(|| DEFAULT_ACCOUNT.to_string()),
+        external_id: pending.fitid,
+        splits: Vec::new(),
+    })
+}
+
+fn tokenize(content: &str, start: usize) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut position = start;
+
+    while let Some(relative) = content[position..].find('<') {
+        let tag_start = position + relative;
+        let tag_end = match content[tag_start..].find('>') {
+            Some(end) => tag_start + end,
+            None => break,
+        };
+        let tag = content[tag_start + 1..tag_end].trim();
+        let value_end = content[tag_end + 1..].find('<')
+            .map(|end| tag_end + 1 + end)
+            .unwrap_or(content.len());
+        position = value_end;
+
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+        if let Some(name) = tag.strip_prefix('/') {
+            tokens.push(Token::Close { name: name.trim() });
+            continue;
+        }
+
+        let value = content[tag_end + 1..value_end].trim();
+        tokens.push(Token::Open {
+            name: tag,
+            value: if value.is_empty() { None } else { Some(value) },
+            offset: tag_start,
+        });
+    }
+
+    tokens
+}
+
+fn decode_entities(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+fn line_number(content: &str, offset: usize) -> usize {
+    content[..offset].matches('\n').count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SGML: &str = "OFXHEADER:100
+DATA:OFXSGML
+VERSION:102
+
+<OFX>
+<BANKMSGSRSV1>
+<STMTTRNRS>
+<STMTRS>
+<CURDEF>EUR
+<BANKACCTFROM>
+<BANKID>30004
+<ACCTID>FR7630004000031234567890143
+<ACCTTYPE>CHECKING
+</BANKACCTFROM>
+<BANKTRANLIST>
+<STMTTRN>
+<TRNTYPE>DEBIT
+<DTPOSTED>20240115120000.000[+1:CET]
+<TRNAMT>-42,50
+<FITID>0001
+<NAME>CB CARREFOUR
+<MEMO>PARIS
+</STMTTRN>
+<STMTTRN>
+<TRNTYPE>CREDIT
+<DTPOSTED>20240116
+<TRNAMT>1500.00
+<FITID>0002
+<NAME>VIR SALAIRE &amp; PRIMES
+</STMTTRN>
+</BANKTRANLIST>
+<LEDGERBAL>
+<BALAMT>1457.50
+<DTASOF>20240131
+</LEDGERBAL>
+</STMTRS>
+</STMTTRNRS>
+</BANKMSGSRSV1>
+</OFX>
+";
+
+    const XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<?OFX OFXHEADER="200" VERSION="220"?>
+<OFX>
+  <CREDITCARDMSGSRSV1>
+    <CCSTMTTRNRS>
+      <CCSTMTRS>
+        <CURDEF>EUR</CURDEF>
+        <BANKTRANLIST>
+          <STMTTRN>
+            <DTPOSTED>20240203</DTPOSTED>
+            <TRNAMT>-9.99</TRNAMT>
+            <FITID>A1</FITID>
+            <NAME>NETFLIX</NAME>
+            <MEMO>NETFLIX</MEMO>
+          </STMTTRN>
+        </BANKTRANLIST>
+        <CCACCTFROM>
+          <ACCTID>4970123412341234</ACCTID>
+        </CCACCTFROM>
+      </CCSTMTRS>
+    </CCSTMTTRNRS>
+  </CREDITCARDMSGSRSV1>
+</OFX>"#;
+
+    #[test]
+    fn parses_sgml_statements_with_unclosed_leaves() {
+        let output = parse(SGML).unwrap();
+        assert!(output.errors.is_empty());
+        assert_eq!(output.transactions.len(), 2);
+
+        let first = &output.transactions[0];
+        assert_eq!(first.date, "2024-01-15");
+        assert_eq!(first.amount, -42.5);
+        assert_eq!(first.description, "CB CARREFOUR - PARIS");
+        assert_eq!(first.external_id.as_deref(), Some("0001"));
+        assert_eq!(first.account, "FR7630004000031234567890143");
+        assert_eq!(first.line, 16);
+        assert_eq!(output.transactions[1].description, "VIR SALAIRE & PRIMES");
+
+        let statement = output.statement.unwrap();
+        assert_eq!(statement.currency.as_deref(), Some("EUR"));
+        assert_eq!(statement.bank_id.as_deref(), Some("30004"));
+        assert_eq!(statement.ledger_balance, Some(1457.5));
+        assert_eq!(statement.ledger_balance_date.as_deref(), Some("2024-01-31"));
+    }
+
+    #[test]
+    fn parses_xml_statements_with_the_account_after_the_transactions() {
+        let output = parse(XML).unwrap();
+        assert!(output.errors.is_empty());
+        assert_eq!(output.transactions.len(), 1);
+
+        let transaction = &output.transactions[0];
+        assert_eq!(transaction.date, "2024-02-03");
+        assert_eq!(transaction.amount, -9.99);
+        // A memo repeating the name is not appended
+        assert_eq!(transaction.description, "NETFLIX");
+        assert_eq!(transaction.account, "4970123412341234");
+    }
+
+    #[test]
+    fn reports_incomplete_transactions_without_failing_the_file() {
+        let content = "<OFX><STMTTRN><DTPOSTED>20240101<NAME>SANS MONTANT</STMTTRN>\
+                       <STMTTRN><DTPOSTED>20240102<TRNAMT>-1.00<NAME>OK</STMTTRN></OFX>";
+        let output = parse(content).unwrap();
+        assert_eq!(output.transactions.len(), 1);
+        assert_eq!(output.errors.len(), 1);
+        assert_eq!(output.transactions[0].account, DEFAULT_ACCOUNT);
+    }
+
+    #[test]
+    fn rejects_files_without_an_ofx_body() {
+        assert!(parse("OFXHEADER:100\nDATA:OFXSGML\n").is_err());
+    }
+}
@@ -0,0 +1,321 @@
+use anyhow::{Result, anyhow};
+use chrono::NaiveDate;
+use crate::models::{AmountLocale, DateOrder};
+use crate::utils::parse_amount;
+use super::{ParseOutput, ParsedSplit, ParsedTransaction, CATEGORY_PATH_SEPARATOR, DEFAULT_ACCOUNT, DEFAULT_CATEGORY, TRANSFER_CATEGORY};
+
+#[derive(Default)]
+struct PendingRecord {
+    line: usize,
+    date: Option<String>,
+    amount: Option<String>,
+    payee: Option<String>,
+    memo: Option<String>,
+    category: Option<String>,
+    number: Option<String>,
+    splits: Vec<PendingSplit>,
+}
+
+#[derive(Default)]
+struct PendingSplit {
+    category: Option<String>,
+    memo: Option<String>,
+    amount: Option<String>,
+}
+
+#[derive(PartialEq)]
+enum Section {
+    Transactions,
+    Account,
+    Ignored,
+}
+
+// Parses Quicken Interchange Format files. Only the banking sections
+// (!Type:Bank, !Type:CCard, !Type:Cash) produce transactions; investment,
+// category and memorized-transaction lists are skipped. The date order is
+// inferred from the file unless `date_order` is given.
+pub fn parse(content: &str, date_order: Option<DateOrder>) -> Result<ParseOutput> {
+    let day_first = match date_order {
+        Some(date_order) => date_order == DateOrder::DayFirst,
+        None => infer_day_first(content),
+    };
+    let mut output = ParseOutput::default();
+    let mut section = Section::Ignored;
+    let mut account = DEFAULT_ACCOUNT.to_string();
+    let mut pending_account: Option<String> = None;
+    let mut record = PendingRecord::default();
+    let mut seen_header = false;
+
+    for (index, raw_line) in content.lines().enumerate() {
+        let line_number = index + 1;
+        let line = raw_line.trim_end_matches('\r');
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('!') {
+            seen_header = true;
+            let header = header.trim();
+            section = if header.eq_ignore_ascii_case("Account") {
+                Section::Account
+            } else if let Some(kind) = header.strip_prefix("Type:") {
+                match kind.trim().to_ascii_lowercase().as_str() {
+                    "bank" | "ccard" | "cash" | "oth a" | "oth l" => Section::Transactions,
+                    _ => Section::Ignored,
+                }
+            } else if header.starts_with("Option:") || header.starts_with("Clear:") {
+                continue;
+            } else {
+                Section::Ignored
+            };
+            record = PendingRecord::default();
+            continue;
+        }
+
+        let (code, value) = line.split_at(line.chars().next().map(|c| c.len_utf8()).unwrap_or(0));
+        let value = value.trim().to_string();
+
+        match section {
+            Section::Ignored => {}
+            Section::Account => match code {
+                "N" => pending_account = Some(value),
+                "^" => {
+                    if let Some(name) = pending_account.take() {
+                        account = name;
+                    }
+                }
+                _ => {}
+            },
+            Section::Transactions => {
+                if record.line == 0 {
+                    record.line = line_number;
+                }
+                match code {
+                    "D" => record.date = Some(value),
+                    "T" | "U" => record.amount = Some(value),
+                    "P" => record.payee = Some(value),
+                    "M" => record.memo = Some(value),
+                    "L" => record.category = Some(value),
+                    "N" => record.number = Some(value),
+                    // Cleared status has no equivalent in our model
+                    "C" => {}
+                    "S" => record.splits.push(PendingSplit {
+                        category: Some(value),
+                        ..Default::default()
+                    }),
+                    "E" => current_split(&mut record).memo = Some(value),
+                    "$" => current_split(&mut record).amount = Some(value),
+                    "^" => {
+                        let finished = std::mem::take(&mut record);
+                        let line = finished.line;
+                        match build_transaction(finished, &account, day_first) {
+                            Ok(transaction) => output.transactions.push(transaction),
+                            Err(e) => output.push_error(line, e),
+                        }
+                    }
+                    // Address lines, reimbursable flags and investment fields are not imported
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    if !seen_header {
+        return Err(anyhow!("Fichier QIF invalide: en-tête !Type: introuvable"));
+    }
+    if record.line != 0 {
+        output.push_error(record.line, "Transaction non terminée (^ manquant)");
+    }
+
+    Ok(output)
+}
+
+fn current_split(record: &mut PendingRecord) -> &mut PendingSplit {
+    // E and $ lines may precede their S line in some exports
+    if record.splits.is_empty() {
+        record.splits.push(PendingSplit::default());
+    }
+    record.splits.last_mut().unwrap()
+}
+
+fn build_transaction(record: PendingRecord, account: &str, day_first: bool) -> Result<ParsedTransaction> {
+    let date = record.date.ok_or_else(|| anyhow!("Date manquante"))?;
+    let date = parse_qif_date(&date, day_first).map_err(|_| anyhow!("Format de date invalide"))?;
+
+    let amount = record.amount.ok_or_else(|| anyhow!("Montant manquant"))?;
+    // US exports write 1,234.56 and European ones 1 234,56
+    let amount = parse_amount(&amount, &AmountLocale::default())?.value();
+
+    let mut description = match (record.payee, record.memo) {
+        (Some(payee), Some(memo)) if !memo.is_empty() && memo != payee => format!("{} - {}", payee, memo),
+        (Some(payee), _) if !payee.is_empty() => payee,
+        (_, Some(memo)) if !memo.is_empty() => memo,
+        _ => return Err(anyhow!("Description manquante (P/M)")),
+    };
+    if let Some(number) = record.number.filter(|n| !n.is_empty()) {
+        description = format!("{} (n° {})", description, number);
+    }
+
+    let mut splits = Vec::new();
+    for split in record.splits {
+        let raw_amount = split.amount.ok_or_else(|| anyhow!("Montant de ventilation manquant"))?;
+        let split_amount = parse_amount(&raw_amount, &AmountLocale::default())?.value();
+        splits.push(ParsedSplit {
+            category: map_category(split.category.as_deref()),
+            memo: split.memo.unwrap_or_default(),
+            amount: split_amount,
+        });
+    }
+
+    // A split transaction usually carries no L line; when every split shares
+    // one category the parent takes it as well
+    let category = match record.category.as_deref() {
+        Some(category) if !category.is_empty() => map_category(Some(category)),
+        _ => match splits.first() {
+            Some(first) if splits.iter().all(|s| s.category == first.category) => first.category.clone(),
+            _ => DEFAULT_CATEGORY.to_string(),
+        },
+    };
+
+    Ok(ParsedTransaction {
+        line: record.line,
+        date,
+        description,
+        amount,
+        category,
+        account: account.to_string(),
+        external_id: None,
+        splits,
+    })
+}
+
+// "Food:Groceries/Class" becomes "Food > Groceries"; "[Savings]" is a
+// transfer to another Quicken account.
+fn map_category(raw: Option<&str>) -> String {
+    let raw = match raw.map(str::trim) {
+        Some(raw) if !raw.is_empty() => raw,
+        _ => return DEFAULT_CATEGORY.to_string(),
+    };
+    if raw.starts_with('[') {
+        return TRANSFER_CATEGORY.to_string();
+    }
+    let without_class = raw.split('/').next().unwrap_or(raw);
+    without_class
+        .split(':')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(CATEGORY_PATH_SEPARATOR)
+}
+
+// Quicken writes dates as 1/15/2024, 01/15/24, 1/15'24 or 1/ 5'04. The
+// apostrophe always marks a year in the 2000s; a bare two-digit year is
+// pivoted around 1970.
+fn parse_qif_date(raw: &str, day_first: bool) -> Result<String> {
+    let (first, second, year) = split_qif_date(raw).ok_or_else(|| anyhow!("Date QIF invalide: {}", raw))?;
+    let (day, month) = if day_first { (first, second) } else { (second, first) };
+
+    let date = NaiveDate::from_ymd_opt(year, month, day)
+        .ok_or_else(|| anyhow!("Date QIF invalide: {}", raw))?;
+    Ok(date.format("%Y-%m-%d").to_string())
+}
+
+fn split_qif_date(raw: &str) -> Option<(u32, u32, i32)> {
+    let raw = raw.replace(' ', "");
+    let apostrophe = raw.contains('\'');
+    let parts: Vec<&str> = raw.split(['/', '\'', '-', '.']).collect();
+    if parts.len() != 3 {
+        return None;
+    }
+
+    let first = parts[0].parse::<u32>().ok()?;
+    let second = parts[1].parse::<u32>().ok()?;
+    let year_part = parts[2];
+    let year = year_part.parse::<i32>().ok()?;
+    let year = match year_part.len() {
+        4 => year,
+        1 | 2 if apostrophe => 2000 + year,
+        1 | 2 if year >= 70 => 1900 + year,
+        1 | 2 => 2000 + year,
+        _ => return None,
+    };
+
+    Some((first, second, year))
+}
+
+// QIF has no day/month order marker: Quicken US writes month first, European
+// versions day first. Any component above 12 settles it for the whole file.
+fn infer_day_first(content: &str) -> bool {
+    let mut day_first = false;
+    for line in content.lines() {
+        if let Some(date) = line.strip_prefix('D') {
+            if let Some((first, second, _)) = split_qif_date(date.trim()) {
+                if first > 12 {
+                    day_first = true;
+                    break;
+                }
+                if second > 12 {
+                    break;
+                }
+            }
+        }
+    }
+    day_first
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_apostrophe_years_as_2000s() {
+        assert_eq!(parse_qif_date("1/15'24", false).unwrap(), "2024-01-15");
+        assert_eq!(parse_qif_date("1/ 5'04", false).unwrap(), "2004-01-05");
+        assert_eq!(parse_qif_date("12/31/99", false).unwrap(), "1999-12-31");
+        assert_eq!(parse_qif_date("01/15/2024", false).unwrap(), "2024-01-15");
+        assert!(parse_qif_date("13/45'24", false).is_err());
+    }
+
+    #[test]
+    fn infers_the_date_order_unless_given() {
+        let content = "!Type:Bank\nD03/04/2024\nT-10.00\nPBOULANGERIE\n^\nD25/04/2024\nT-5.00\nPCAFE\n^\n";
+        let inferred = parse(content, None).unwrap();
+        assert_eq!(inferred.transactions[0].date, "2024-04-03");
+
+        let ambiguous = "!Type:Bank\nD03/04/2024\nT-10.00\nPBOULANGERIE\n^\n";
+        assert_eq!(parse(ambiguous, None).unwrap().transactions[0].date, "2024-03-04");
+        assert_eq!(parse(ambiguous, Some(DateOrder::DayFirst)).unwrap().transactions[0].date, "2024-04-03");
+        assert_eq!(parse(ambiguous, Some(DateOrder::MonthFirst)).unwrap().transactions[0].date, "2024-03-04");
+    }
+
+    #[test]
+    fn parses_splits_categories_and_transfers() {
+        let content = "!Account\nNCompte joint\n^\n!Type:Bank\n\
+                       D1/15'24\nT-100.00\nPCARREFOUR\nN1234\n\
+                       SFood:Groceries/Home\nEFruits\n$-60.00\nSHousehold\n$-40.00\n^\n\
+                       D1/16'24\nT-200.00\nPEPARGNE\nL[Livret A]\n^\n";
+        let output = parse(content, None).unwrap();
+        assert!(output.errors.is_empty());
+        assert_eq!(output.transactions.len(), 2);
+
+        let split = &output.transactions[0];
+        assert_eq!(split.account, "Compte joint");
+        assert_eq!(split.description, "CARREFOUR (n° 1234)");
+        assert_eq!(split.category, DEFAULT_CATEGORY);
+        assert_eq!(split.splits.len(), 2);
+        assert_eq!(split.splits[0].category, format!("Food{}Groceries", CATEGORY_PATH_SEPARATOR));
+        assert_eq!(split.splits[0].memo, "Fruits");
+        assert_eq!(split.splits[0].amount, -60.0);
+
+        assert_eq!(output.transactions[1].category, TRANSFER_CATEGORY);
+    }
+
+    #[test]
+    fn reports_unterminated_records_and_missing_headers() {
+        let output = parse("!Type:Bank\nD1/15'24\nT-1.00\nPCAFE\n", None).unwrap();
+        assert!(output.transactions.is_empty());
+        assert_eq!(output.errors.len(), 1);
+
+        assert!(parse("D1/15'24\nT-1.00\n^\n", None).is_err());
+    }
+}
@@ -0,0 +1,110 @@
+use crate::duplicates::normalize_description;
+use crate::models::Payee;
+
+// Words banks put before the merchant name: card payments, direct debits,
+// transfers and their SEPA markers
+const PREFIX_WORDS: [&str; 20] = [
+    "CB", "CARTE", "PAIEMENT", "PAIE", "PAR", "ACHAT", "FACTURE", "PRLV", "PRELEVEMENT", "PRELEV",
+    "SEPA", "VIR", "VIREMENT", "VIRT", "INST", "RECU", "EMIS", "ECH", "DE", "DU",
+];
+
+// "CB CARREFOUR 12/03 PARIS 15" becomes "CARREFOUR PARIS": uppercased and
+// accent-free, without the banking prefixes, SEPA markers, dates, card and
+// store numbers. Empty when nothing is left.
+pub fn normalize_payee(description: &str) -> String {
+    let normalized = normalize_description(description);
+    let mut words: Vec<&str> = normalized.split_whitespace().collect();
+    let start = words.iter().position(|word| !PREFIX_WORDS.contains(word)).unwrap_or(words.len());
+    words.drain(..start);
+    words.retain(|word| *word != "SEPA");
+    words.join(" ")
+}
+
+// The payee whose longest alias the normalised description is or starts with
+pub fn find_payee<'a>(normalized: &str, payees: &'a [Payee]) -> Option<&'a Payee> {
+    payees
+        .iter()
+        .filter_map(|payee| {
+            payee.aliases
+                .iter()
+                .filter(|alias| matches_alias(normalized, alias))
+                .map(|alias| alias.len())
+                .max()
+                .map(|len| (len, payee))
+        })
+        .max_by_key(|(len, _)| *len)
+        .map(|(_, payee)| payee)
+}
+
+fn matches_alias(normalized: &str, alias: &str) -> bool {
+    !alias.is_empty()
+        && (normalized == alias
+            || normalized.strip_prefix(alias).map(|rest| rest.starts_with(' ')).unwrap_or(false))
+}
+
+// Display name for a payee created from a description: "CARREFOUR PARIS"
+// becomes "Carrefour Paris"
+pub fn display_name(normalized: &str) -> String {
+    normalized
+        .split_whitespace()
+        .map(|word| {
+            let lower = word.to_lowercase();
+            let mut chars = lower.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payee(id: &str, aliases: &[&str]) -> Payee {
+        Payee {
+            id: id.to_string(),
+            name: display_name(aliases[0]),
+            aliases: aliases.iter().map(|alias| alias.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn strips_banking_prefixes_and_references() {
+        assert_eq!(normalize_payee("CB CARREFOUR 12/03 PARIS 15"), "CARREFOUR PARIS");
+        assert_eq!(normalize_payee("PRLV SEPA EDF ÉLECTRICITÉ"), "EDF ELECTRICITE");
+        assert_eq!(normalize_payee("VIR INST RECU DE Jean Dupont"), "JEAN DUPONT");
+        assert_eq!(normalize_payee("Paiement par carte Amazon.fr"), "AMAZON FR");
+        assert_eq!(normalize_payee("CARTE 4970XXXX1234"), "");
+    }
+
+    #[test]
+    fn keeps_prefix_words_after_the_merchant_name() {
+        // Only leading prefixes are dropped; SEPA goes wherever it is
+        assert_eq!(normalize_payee("CB LE COMPTOIR DE PARIS"), "LE COMPTOIR DE PARIS");
+        assert_eq!(normalize_payee("FREE MOBILE SEPA"), "FREE MOBILE");
+    }
+
+    #[test]
+    fn finds_the_payee_with_the_longest_matching_alias() {
+        let payees = vec![
+            payee("carrefour", &["CARREFOUR"]),
+            payee("carrefour-city", &["CARREFOUR CITY"]),
+        ];
+        assert_eq!(find_payee("CARREFOUR", &payees).map(|p| p.id.as_str()), Some("carrefour"));
+        assert_eq!(find_payee("CARREFOUR PARIS", &payees).map(|p| p.id.as_str()), Some("carrefour"));
+        assert_eq!(find_payee("CARREFOUR CITY LYON", &payees).map(|p| p.id.as_str()), Some("carrefour-city"));
+        // An alias must end on a word boundary
+        assert!(find_payee("CARREFOURMARKET", &payees).is_none());
+        assert!(find_payee("", &[payee("empty", &[""])]).is_none());
+    }
+
+    #[test]
+    fn capitalises_display_names() {
+        assert_eq!(display_name("CARREFOUR PARIS"), "Carrefour Paris");
+        assert_eq!(display_name("EDF ELECTRICITE"), "Edf Electricite");
+        assert_eq!(display_name(""), "");
+    }
+}
@@ -0,0 +1,287 @@
+use anyhow::{Result, anyhow};
+use chrono::Datelike;
+use regex::{Regex, RegexBuilder};
+use crate::models::{Rule, RuleAction, RuleCondition, Transaction};
+use crate::parsers::TRANSFER_CATEGORY;
+use crate::utils::strip_accents;
+
+struct CompiledRule {
+    rule: Rule,
+    patterns: Vec<Regex>, // One per DescriptionMatches condition, in order
+}
+
+// The stored rules, sorted by priority with their regexes compiled once per run
+pub struct RuleEngine {
+    rules: Vec<CompiledRule>,
+}
+
+impl RuleEngine {
+    pub fn new(mut rules: Vec<Rule>) -> Result<Self> {
+        rules.sort_by_key(|rule| rule.priority);
+        let mut compiled = Vec::new();
+        for rule in rules {
+            let mut patterns = Vec::new();
+            for condition in &rule.conditions {
+                if let RuleCondition::DescriptionMatches { pattern } = condition {
+                    patterns.push(compile(pattern)?);
+                }
+            }
+            compiled.push(CompiledRule { rule, patterns });
+        }
+        Ok(RuleEngine { rules: compiled })
+    }
+
+    // Applies the first matching rule and returns it; the transaction records
+    // its ID
+    pub fn apply(&self, transaction: &mut Transaction) -> Option<&Rule> {
+        let compiled = self.rules.iter().find(|compiled| matches(compiled, transaction))?;
+        for action in &compiled.rule.actions {
+            match action {
+                RuleAction::SetCategory { category } => transaction.category = category.clone(),
+                // The bank's description stays as it is; see renamed_payee
+                RuleAction::RenamePayee { .. } => {}
+                RuleAction::AddTag { tag } => {
+                    if !transaction.tags.iter().any(|existing| existing.eq_ignore_ascii_case(tag)) {
+                        transaction.tags.push(tag.clone());
+                    }
+                }
+                RuleAction::MarkAsTransfer => transaction.category = TRANSFER_CATEGORY.to_string(),
+            }
+        }
+        transaction.rule_id = Some(compiled.rule.id.clone());
+        Some(&compiled.rule)
+    }
+}
+
+// The payee a rule files its transactions under, if it renames them; the
+// database maps the name onto a payee
+pub fn renamed_payee(rule: &Rule) -> Option<&str> {
+    rule.actions.iter().rev().find_map(|action| match action {
+        RuleAction::RenamePayee { payee } => Some(payee.trim()),
+        _ => None,
+    })
+}
+
+pub fn validate(rule: &Rule) -> Result<()> {
+    if rule.name.trim().is_empty() {
+        return Err(anyhow!("Le nom de la règle est obligatoire"));
+    }
+    if rule.conditions.is_empty() {
+        return Err(anyhow!("Une règle doit avoir au moins une condition"));
+    }
+    if rule.actions.is_empty() {
+        return Err(anyhow!("Une règle doit avoir au moins une action"));
+    }
+
+    for condition in &rule.conditions {
+        match condition {
+            RuleCondition::DescriptionContains { value } if value.trim().is_empty() => {
+                return Err(anyhow!("Le texte recherché est vide"));
+            }
+            RuleCondition::DescriptionMatches { pattern } => {
+                compile(pattern)?;
+            }
+            RuleCondition::AmountRange { min: Some(min), max: Some(max) } if min > max => {
+                return Err(anyhow!("Intervalle de montant invalide: {} > {}", min, max));
+            }
+            RuleCondition::DayOfMonth { from, to } if *from < 1 || *to > 31 || from > to => {
+                return Err(anyhow!("Jours du mois invalides: {} à {}", from, to));
+            }
+            _ => {}
+        }
+    }
+    for action in &rule.actions {
+        match action {
+            RuleAction::SetCategory { category } if category.trim().is_empty() => {
+                return Err(anyhow!("La catégorie de la règle est vide"));
+            }
+            RuleAction::RenamePayee { payee } if payee.trim().is_empty() => {
+                return Err(anyhow!("Le nom du bénéficiaire est vide"));
+            }
+            RuleAction::AddTag { tag } if tag.trim().is_empty() => {
+                return Err(anyhow!("L'étiquette est vide"));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn compile(pattern: &str) -> Result<Regex> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| anyhow!("Expression régulière invalide: {}", e))
+}
+
+fn matches(compiled: &CompiledRule, transaction: &Transaction) -> bool {
+    let mut patterns = compiled.patterns.iter();
+    compiled.rule.conditions.iter().all(|condition| match condition {
+        RuleCondition::DescriptionContains { value } => {
+            fold(&transaction.description).contains(&fold(value))
+        }
+        RuleCondition::DescriptionMatches { .. } => patterns
+            .next()
+            .map(|pattern| pattern.is_match(&transaction.description))
+            .unwrap_or(false),
+        RuleCondition::AmountRange { min, max } => {
+            min.map(|min| transaction.amount >= min).unwrap_or(true)
+                && max.map(|max| transaction.amount <= max).unwrap_or(true)
+        }
+        RuleCondition::Account { account } => transaction.account == *account,
+        RuleCondition::DayOfMonth { from, to } => {
+            match chrono::NaiveDate::parse_from_str(&transaction.date, "%Y-%m-%d") {
+                Ok(date) => (*from..=*to).contains(&date.day()),
+                Err(_) => false,
+            }
+        }
+    })
+}
+
+fn fold(value: &str) -> String {
+    strip_accents(&value.trim().to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(id: &str, priority: i32, conditions: Vec<RuleCondition>, actions: Vec<RuleAction>) -> Rule {
+        Rule { id: id.to_string(), name: id.to_string(), priority, conditions, actions }
+    }
+
+    fn set_category(category: &str) -> RuleAction {
+        RuleAction::SetCategory { category: category.to_string() }
+    }
+
+    fn contains(value: &str) -> RuleCondition {
+        RuleCondition::DescriptionContains { value: value.to_string() }
+    }
+
+    fn transaction(description: &str, amount: f64, date: &str) -> Transaction {
+        Transaction {
+            id: "t".to_string(),
+            description: description.to_string(),
+            amount,
+            date: date.to_string(),
+            category: "Non catégorisé".to_string(),
+            account: "courant".to_string(),
+            external_id: None,
+            transfer_id: None,
+            splits: Vec::new(),
+            tags: Vec::new(),
+            rule_id: None,
+            payee_id: None,
+        }
+    }
+
+    fn fires(condition: RuleCondition, transaction: &Transaction) -> bool {
+        let engine = RuleEngine::new(vec![rule("r", 0, vec![condition], vec![set_category("X")])]).unwrap();
+        engine.apply(&mut transaction.clone()).is_some()
+    }
+
+    #[test]
+    fn rejects_incomplete_or_inconsistent_rules() {
+        assert!(validate(&rule("r", 0, vec![contains("carrefour")], vec![set_category("Courses")])).is_ok());
+        assert!(validate(&rule(" ", 0, vec![contains("carrefour")], vec![set_category("Courses")])).is_err());
+        assert!(validate(&rule("r", 0, vec![], vec![set_category("Courses")])).is_err());
+        assert!(validate(&rule("r", 0, vec![contains("carrefour")], vec![])).is_err());
+        assert!(validate(&rule("r", 0, vec![contains("  ")], vec![set_category("Courses")])).is_err());
+
+        let bad_conditions = [
+            RuleCondition::DescriptionMatches { pattern: "(unclosed".to_string() },
+            RuleCondition::AmountRange { min: Some(10.0), max: Some(-10.0) },
+            RuleCondition::DayOfMonth { from: 0, to: 5 },
+            RuleCondition::DayOfMonth { from: 20, to: 10 },
+            RuleCondition::DayOfMonth { from: 1, to: 32 },
+        ];
+        for condition in bad_conditions {
+            assert!(validate(&rule("r", 0, vec![condition], vec![set_category("Courses")])).is_err());
+        }
+        let bad_actions = [
+            set_category(""),
+            RuleAction::RenamePayee { payee: " ".to_string() },
+            RuleAction::AddTag { tag: "".to_string() },
+        ];
+        for action in bad_actions {
+            assert!(validate(&rule("r", 0, vec![contains("carrefour")], vec![action])).is_err());
+        }
+    }
+
+    #[test]
+    fn matches_each_kind_of_condition() {
+        let coffee = transaction("CB CAFÉ DE LA GARE", -3.5, "2024-03-15");
+
+        // Case and accents are ignored both ways
+        assert!(fires(contains("cafe de la"), &coffee));
+        assert!(fires(contains("Café"), &transaction("CB CAFE", -3.5, "2024-03-15")));
+        assert!(!fires(contains("the"), &coffee));
+
+        assert!(fires(RuleCondition::DescriptionMatches { pattern: r"^cb\s+caf".to_string() }, &coffee));
+        assert!(!fires(RuleCondition::DescriptionMatches { pattern: "^gare".to_string() }, &coffee));
+
+        // Signed amount, bounds included, either bound optional
+        assert!(fires(RuleCondition::AmountRange { min: Some(-3.5), max: Some(0.0) }, &coffee));
+        assert!(fires(RuleCondition::AmountRange { min: None, max: Some(-1.0) }, &coffee));
+        assert!(!fires(RuleCondition::AmountRange { min: Some(0.0), max: None }, &coffee));
+
+        assert!(fires(RuleCondition::Account { account: "courant".to_string() }, &coffee));
+        assert!(!fires(RuleCondition::Account { account: "livret".to_string() }, &coffee));
+
+        assert!(fires(RuleCondition::DayOfMonth { from: 15, to: 15 }, &coffee));
+        assert!(!fires(RuleCondition::DayOfMonth { from: 1, to: 14 }, &coffee));
+        assert!(!fires(RuleCondition::DayOfMonth { from: 1, to: 31 }, &transaction("CB", -1.0, "15/03/2024")));
+    }
+
+    #[test]
+    fn requires_every_condition() {
+        let conditions = vec![contains("carrefour"), RuleCondition::AmountRange { min: None, max: Some(0.0) }];
+        let engine = RuleEngine::new(vec![rule("r", 0, conditions, vec![set_category("Courses")])]).unwrap();
+        assert!(engine.apply(&mut transaction("CB CARREFOUR", -20.0, "2024-03-15")).is_some());
+        assert!(engine.apply(&mut transaction("CARREFOUR REMBOURSEMENT", 20.0, "2024-03-15")).is_none());
+    }
+
+    #[test]
+    fn fires_the_first_matching_rule_by_priority_only() {
+        let engine = RuleEngine::new(vec![
+            rule("fallback", 10, vec![contains("cb")], vec![set_category("Divers")]),
+            rule("groceries", 1, vec![contains("carrefour")], vec![
+                set_category("Courses"),
+                RuleAction::AddTag { tag: "supermarché".to_string() },
+            ]),
+            rule("never", 0, vec![contains("auchan")], vec![set_category("Autre")]),
+        ]).unwrap();
+
+        let mut groceries = transaction("CB CARREFOUR", -20.0, "2024-03-15");
+        assert_eq!(engine.apply(&mut groceries).map(|rule| rule.id.as_str()), Some("groceries"));
+        assert_eq!(groceries.category, "Courses");
+        assert_eq!(groceries.tags, vec!["supermarché"]);
+        assert_eq!(groceries.rule_id.as_deref(), Some("groceries"));
+
+        // The lower priority rule also matched but never runs
+        let mut other = transaction("CB FNAC", -20.0, "2024-03-15");
+        assert_eq!(engine.apply(&mut other).map(|rule| rule.id.as_str()), Some("fallback"));
+        assert_eq!(other.category, "Divers");
+
+        let mut unmatched = transaction("PRLV EDF", -60.0, "2024-03-15");
+        assert!(engine.apply(&mut unmatched).is_none());
+        assert!(unmatched.rule_id.is_none());
+    }
+
+    #[test]
+    fn renaming_the_payee_leaves_the_description_alone() {
+        let rename = rule("r", 0, vec![contains("amzn")], vec![
+            RuleAction::RenamePayee { payee: " Amazon ".to_string() },
+            RuleAction::MarkAsTransfer,
+        ]);
+        assert_eq!(renamed_payee(&rename), Some("Amazon"));
+        assert_eq!(renamed_payee(&rule("r", 0, vec![contains("x")], vec![set_category("X")])), None);
+
+        let engine = RuleEngine::new(vec![rename]).unwrap();
+        let mut purchase = transaction("CB AMZN MKTP FR", -30.0, "2024-03-15");
+        engine.apply(&mut purchase).unwrap();
+        assert_eq!(purchase.description, "CB AMZN MKTP FR");
+        assert_eq!(purchase.category, TRANSFER_CATEGORY);
+    }
+}
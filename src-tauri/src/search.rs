@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+use crate::utils::strip_accents;
+
+// Shortest prefix indexed, so "ca" does not match half the table
+pub const MIN_PREFIX_LEN: usize = 3;
+// Longer words are only found by prefixes up to this length
+const MAX_PREFIX_LEN: usize = 20;
+
+// Lowercased, accent-free words of at least two characters
+pub fn tokenize(text: &str) -> Vec<String> {
+    strip_accents(&text.to_lowercase())
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|token| token.chars().count() >= 2)
+        .map(str::to_string)
+        .collect()
+}
+
+// Every term a description can be found by: its words, flagged as exact, and
+// their prefixes from MIN_PREFIX_LEN characters, flagged as partial. A prefix
+// that is also a whole word of the description stays exact.
+pub fn index_terms(text: &str) -> HashMap<String, bool> {
+    let mut terms = HashMap::new();
+    for token in tokenize(text) {
+        let chars: Vec<char> = token.chars().collect();
+        for len in MIN_PREFIX_LEN..chars.len().min(MAX_PREFIX_LEN + 1) {
+            terms.entry(chars[..len].iter().collect::<String>()).or_insert(false);
+        }
+        let whole: String = chars.iter().take(MAX_PREFIX_LEN).collect();
+        *terms.entry(whole).or_insert(false) |= chars.len() <= MAX_PREFIX_LEN;
+    }
+    terms
+}
+
+// Query words as looked up in the index: too-long words are cut to the
+// longest indexed prefix
+pub fn query_terms(query: &str) -> Vec<String> {
+    let mut terms: Vec<String> = tokenize(query)
+        .into_iter()
+        .map(|token| token.chars().take(MAX_PREFIX_LEN).collect())
+        .collect();
+    terms.sort();
+    terms.dedup();
+    terms
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenizes_into_lowercase_words_without_accents() {
+        assert_eq!(tokenize("Prélèvement EDF-Électricité n°5"), vec!["prelevement", "edf", "electricite"]);
+        assert!(tokenize("a - 1").is_empty());
+    }
+
+    #[test]
+    fn indexes_words_as_exact_and_their_prefixes_as_partial() {
+        let terms = index_terms("CB Carrefour");
+        assert_eq!(terms.get("cb"), Some(&true));
+        assert_eq!(terms.get("carrefour"), Some(&true));
+        assert_eq!(terms.get("car"), Some(&false));
+        assert_eq!(terms.get("carrefou"), Some(&false));
+        // Prefixes start at MIN_PREFIX_LEN characters
+        assert_eq!(terms.get("ca"), None);
+        assert_eq!(terms.len(), 1 + 1 + (9 - MIN_PREFIX_LEN));
+    }
+
+    #[test]
+    fn keeps_a_prefix_exact_when_it_is_also_a_word() {
+        assert_eq!(index_terms("car carrefour").get("car"), Some(&true));
+        assert_eq!(index_terms("carrefour car").get("car"), Some(&true));
+    }
+
+    #[test]
+    fn cuts_long_words_to_the_longest_indexed_prefix() {
+        let long = "anticonstitutionnellement";
+        let terms = index_terms(long);
+        let cut: String = long.chars().take(MAX_PREFIX_LEN).collect();
+        assert_eq!(terms.get(&cut), Some(&false));
+        assert_eq!(terms.get(long), None);
+
+        // A word as long as the cut stays exact whatever the order
+        assert_eq!(index_terms(&format!("{} {}", cut, long)).get(&cut), Some(&true));
+        assert_eq!(index_terms(&format!("{} {}", long, cut)).get(&cut), Some(&true));
+
+        assert_eq!(query_terms(long), vec![cut]);
+    }
+
+    #[test]
+    fn deduplicates_query_terms() {
+        assert_eq!(query_terms("Carrefour CARREFOUR cb"), vec!["carrefour", "cb"]);
+        assert!(query_terms("  ").is_empty());
+    }
+}
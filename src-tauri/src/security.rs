@@ -4,6 +4,7 @@ use aes_gcm::{Aes256Gcm, Key, Nonce, aead::{Aead, NewAead, generic_array::Generi
 use rand::RngCore;
 use base64::{Engine as _, engine::general_purpose};
 use sha2::{Sha256, Digest};
+use hmac::{Hmac, Mac};
 use anyhow::{Result, anyhow};
 
 pub struct SecurityManager {
@@ -78,6 +79,35 @@ impl SecurityManager {
         Ok(hex::encode(result))
     }
 
+    pub fn create_file_hash(&self, data: &[u8]) -> Result<String> {
+        let mut hasher = Sha256::new();
+        hasher.update(data);
+        Ok(hex::encode(hasher.finalize()))
+    }
+
+    // Derives a key for another purpose from the encryption key, so that key
+    // itself only ever serves AES. `label` names the purpose.
+    pub fn derive_subkey(&self, key: &[u8; 32], label: &str) -> Result<[u8; 32]> {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key)
+            .map_err(|e| anyhow!("Invalid HMAC key: {}", e))?;
+        mac.update(label.as_bytes());
+        let mut subkey = [0u8; 32];
+        subkey.copy_from_slice(&mac.finalize().into_bytes());
+        Ok(subkey)
+    }
+
+    // Deterministic keyed hash, so encrypted values can be matched in SQL without
+    // being decrypted. `domain` keeps indexes of different fields unrelated; `key`
+    // is the index key from derive_subkey, not the encryption key.
+    pub fn blind_index(&self, domain: &str, value: &str, key: &[u8; 32]) -> Result<String> {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key)
+            .map_err(|e| anyhow!("Invalid HMAC key: {}", e))?;
+        mac.update(domain.as_bytes());
+        mac.update(&[0]);
+        mac.update(value.as_bytes());
+        Ok(hex::encode(mac.finalize().into_bytes()))
+    }
+
     pub fn verify_password(&self, password: &str, hash: &str) -> Result<bool> {
         let parsed_hash = PasswordHash::new(hash)
             .map_err(|e| anyhow!("Failed to parse hash: {}", e))?;
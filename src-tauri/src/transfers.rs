@@ -0,0 +1,55 @@
+use crate::duplicates::normalize_description;
+use crate::parsers::TRANSFER_CATEGORY;
+
+// Days apart the two legs of an imported transfer may be booked
+pub const PAIRING_WINDOW_DAYS: i64 = 3;
+
+// Words banks use for transfers in statement descriptions
+const TRANSFER_WORDS: [&str; 6] = ["VIR", "VIREMENT", "VIRT", "VRT", "TRANSFER", "TRANSFERT"];
+
+// French banks label every SEPA credit transfer "VIR", so these words mark
+// one paid to or received from someone else: "VIR SALAIRE", "VIR LOYER"
+const THIRD_PARTY_WORDS: [&str; 12] = [
+    "SALAIRE", "PAIE", "LOYER", "FACTURE", "REMBOURSEMENT", "REMB", "ALLOCATION", "CAF", "PENSION",
+    "RETRAITE", "HONORAIRES", "COTISATION",
+];
+
+pub fn looks_like_transfer(description: &str, category: &str) -> bool {
+    if category == TRANSFER_CATEGORY {
+        return true;
+    }
+    let normalized = normalize_description(description);
+    let words: Vec<&str> = normalized.split_whitespace().collect();
+    words.iter().any(|word| TRANSFER_WORDS.contains(word))
+        && !words.iter().any(|word| THIRD_PARTY_WORDS.contains(word))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::DEFAULT_CATEGORY;
+
+    #[test]
+    fn recognises_transfers_between_own_accounts() {
+        assert!(looks_like_transfer("VIR INST EMIS VERS LIVRET A", DEFAULT_CATEGORY));
+        assert!(looks_like_transfer("Virement interne compte joint", DEFAULT_CATEGORY));
+        assert!(looks_like_transfer("VRT 0012345 EPARGNE", DEFAULT_CATEGORY));
+        assert!(looks_like_transfer("Transfer to savings", DEFAULT_CATEGORY));
+        // The category wins over the description
+        assert!(looks_like_transfer("CB CARREFOUR", TRANSFER_CATEGORY));
+    }
+
+    #[test]
+    fn rejects_transfers_to_or_from_someone_else() {
+        assert!(!looks_like_transfer("VIR SALAIRE ACME SAS", DEFAULT_CATEGORY));
+        assert!(!looks_like_transfer("VIR SEPA LOYER JANVIER", DEFAULT_CATEGORY));
+        assert!(!looks_like_transfer("VIREMENT REMBOURSEMENT SECU", DEFAULT_CATEGORY));
+    }
+
+    #[test]
+    fn ignores_transfer_words_inside_other_words() {
+        assert!(!looks_like_transfer("PRLV VIRGIN MOBILE", DEFAULT_CATEGORY));
+        assert!(!looks_like_transfer("CB CARREFOUR", DEFAULT_CATEGORY));
+        assert!(!looks_like_transfer("", DEFAULT_CATEGORY));
+    }
+}
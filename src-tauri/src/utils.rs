@@ -1,30 +1,777 @@
-use chrono::{DateTime, Utc, NaiveDateTime};
-use anyhow::Result;
+use chrono::{Datelike, NaiveDate};
+use anyhow::{Result, anyhow};
+use crate::models::{AmountLocale, DateOrder, FileFormat, FormatDetection};
+use crate::parsers::CATEGORY_PATH_SEPARATOR;
+
+const FRENCH_MONTHS: [(&str, u32); 24] = [
+    ("janvier", 1), ("janv", 1), ("jan", 1), ("fevrier", 2), ("fevr", 2), ("fev", 2),
+    ("mars", 3), ("mar", 3), ("avril", 4), ("avr", 4), ("mai", 5), ("juin", 6),
+    ("juillet", 7), ("juil", 7), ("aout", 8), ("septembre", 9), ("sept", 9), ("sep", 9),
+    ("octobre", 10), ("oct", 10), ("novembre", 11), ("nov", 11), ("decembre", 12), ("dec", 12),
+];
 
 pub fn parse_date(date_str: &str) -> Result<String> {
-    // Parse various date formats from imported files
-    Ok(date_str.to_string())
+    // French banks write day first unless told otherwise
+    parse_date_with_order(date_str, DateOrder::DayFirst)
+}
+
+// Normalises imported dates to ISO-8601 (YYYY-MM-DD). Accepts YYYY-MM-DD with an
+// optional time, YYYYMMDD and OFX stamps (20240115120000.000[-5:EST]),
+// DD/MM/YYYY, DD.MM.YYYY, DD-MM-YY and French month names ("15 janv. 2024").
+// `order` only matters for numeric dates whose year comes last.
+pub fn parse_date_with_order(date_str: &str, order: DateOrder) -> Result<String> {
+    let trimmed = date_str.trim();
+    let invalid = || anyhow!("Format de date invalide: {}", date_str);
+    if trimmed.is_empty() {
+        return Err(invalid());
+    }
+
+    let date = if trimmed.len() >= 8 && trimmed.as_bytes()[..8].iter().all(|b| b.is_ascii_digit()) {
+        // YYYYMMDD, possibly followed by an OFX time and timezone
+        let rest = &trimmed[8..];
+        if !rest.is_empty() && !rest.starts_with(|c: char| c.is_ascii_digit() || c == '.' || c == '[' || c == ' ') {
+            return Err(invalid());
+        }
+        NaiveDate::parse_from_str(&trimmed[..8], "%Y%m%d").ok()
+    } else {
+        parse_numeric_date(trimmed, order).or_else(|| parse_textual_date(trimmed))
+    };
+
+    date.map(|d| d.format("%Y-%m-%d").to_string()).ok_or_else(invalid)
+}
+
+fn parse_numeric_date(value: &str, order: DateOrder) -> Option<NaiveDate> {
+    // Drop a trailing time ("2024-01-15T10:00:00", "15/01/2024 10:00")
+    let date_part = value.split(['T', ' ']).next()?;
+    let parts: Vec<&str> = date_part.split(['/', '.', '-']).collect();
+    if parts.len() != 3 || parts.iter().any(|p| p.is_empty() || !p.chars().all(|c| c.is_ascii_digit())) {
+        return None;
+    }
+
+    if parts[0].len() == 4 {
+        let year = parts[0].parse().ok()?;
+        return NaiveDate::from_ymd_opt(year, parts[1].parse().ok()?, parts[2].parse().ok()?);
+    }
+
+    let first: u32 = parts[0].parse().ok()?;
+    let second: u32 = parts[1].parse().ok()?;
+    let year = expand_year(parts[2])?;
+    let (day, month) = match order {
+        DateOrder::DayFirst => (first, second),
+        DateOrder::MonthFirst => (second, first),
+    };
+    NaiveDate::from_ymd_opt(year, month, day)
+}
+
+// "15 janvier 2024", "1er mars 2024", "15-janv-24", "15 févr. 2024"
+fn parse_textual_date(value: &str) -> Option<NaiveDate> {
+    let normalised = strip_accents(&value.to_lowercase());
+    let parts: Vec<&str> = normalised
+        .split(|c: char| c.is_whitespace() || c == '-' || c == '/' || c == '.' || c == ',')
+        .filter(|p| !p.is_empty())
+        .collect();
+    if parts.len() < 3 {
+        return None;
+    }
+
+    let day: u32 = parts[0].trim_end_matches("er").parse().ok()?;
+    let month = FRENCH_MONTHS.iter().find(|(name, _)| *name == parts[1]).map(|(_, month)| *month)?;
+    let year = expand_year(parts[2])?;
+    NaiveDate::from_ymd_opt(year, month, day)
+}
+
+// Two-digit years pivot around 1970: 69 is 2069, 70 is 1970
+fn expand_year(value: &str) -> Option<i32> {
+    let year: i32 = value.parse().ok()?;
+    match value.len() {
+        4 => Some(year),
+        1 | 2 if year >= 70 => Some(1900 + year),
+        1 | 2 => Some(2000 + year),
+        _ => None,
+    }
+}
+
+pub const BUDGET_PERIODS: [&str; 4] = ["weekly", "monthly", "quarterly", "yearly"];
+
+// The budget period containing `day`, as its first day and the first day of
+// the next period. `start_day` is the weekday weekly periods start on
+// (1 = Monday) and the day of the month the others start on, moved to the
+// last day of shorter months. Quarters and years start in January.
+pub fn budget_period(period: &str, day: NaiveDate, start_day: u32) -> Option<(NaiveDate, NaiveDate)> {
+    let months = match period {
+        "weekly" => {
+            let start_weekday = start_day.clamp(1, 7) as i64 - 1;
+            let offset = (day.weekday().num_days_from_monday() as i64 - start_weekday).rem_euclid(7);
+            let start = day - chrono::Duration::days(offset);
+            return Some((start, start + chrono::Duration::days(7)));
+        }
+        "monthly" => 1,
+        "quarterly" => 3,
+        "yearly" => 12,
+        _ => return None,
+    };
+
+    // Months counted from year 0, so quarters and years line up with January
+    let month_index = day.year() * 12 + day.month0() as i32;
+    let mut start_index = month_index - month_index.rem_euclid(months);
+    let mut start = period_day(start_index, start_day)?;
+    if start > day {
+        start_index -= months;
+        start = period_day(start_index, start_day)?;
+    }
+    Some((start, period_day(start_index + months, start_day)?))
 }
 
-pub fn detect_file_format(content: &str) -> String {
-    if content.starts_with("OFXHEADER") {
-        "OFX".to_string()
-    } else if content.contains("!Type:") {
-        "QIF".to_string()
-    } else if content.contains(",") {
-        "CSV".to_string()
+fn period_day(month_index: i32, start_day: u32) -> Option<NaiveDate> {
+    let year = month_index.div_euclid(12);
+    let month = month_index.rem_euclid(12) as u32 + 1;
+    let next_month = if month == 12 {
+        NaiveDate::from_ymd_opt(year + 1, 1, 1)?
     } else {
-        "UNKNOWN".to_string()
+        NaiveDate::from_ymd_opt(year, month + 1, 1)?
+    };
+    let days_in_month = (next_month - chrono::Duration::days(1)).day();
+    NaiveDate::from_ymd_opt(year, month, start_day.clamp(1, days_in_month))
+}
+
+// Cuts "Logement > Électricité > Compteur" down to its first `depth` levels;
+// depth 1 gives the top-level category
+pub fn category_rollup(category: &str, depth: usize) -> String {
+    category
+        .split(CATEGORY_PATH_SEPARATOR)
+        .take(depth.max(1))
+        .collect::<Vec<_>>()
+        .join(CATEGORY_PATH_SEPARATOR)
+}
+
+// Whether `category` is `ancestor` or one of its sub-categories
+pub fn is_in_category(category: &str, ancestor: &str) -> bool {
+    rebase_category(category, ancestor, ancestor).is_some()
+}
+
+// Moves `category` from under `from` to under `to` when it is `from` itself or
+// one of its sub-categories. Matching ignores case like the category index.
+pub fn rebase_category(category: &str, from: &str, to: &str) -> Option<String> {
+    let category = category.trim();
+    let from = from.trim();
+    if category.to_lowercase() == from.to_lowercase() {
+        return Some(to.to_string());
+    }
+    let prefix = format!("{}{}", from, CATEGORY_PATH_SEPARATOR).to_lowercase();
+    if category.to_lowercase().starts_with(&prefix) {
+        // Lowercasing can change byte lengths, so the suffix is taken by levels
+        let depth = from.split(CATEGORY_PATH_SEPARATOR).count();
+        let rest: Vec<&str> = category.split(CATEGORY_PATH_SEPARATOR).skip(depth).collect();
+        return Some(format!("{}{}{}", to, CATEGORY_PATH_SEPARATOR, rest.join(CATEGORY_PATH_SEPARATOR)));
     }
+    None
 }
 
-pub fn sanitize_amount(amount_str: &str) -> Result<f64> {
-    let cleaned = amount_str
-        .replace(",", ".")
-        .replace(" ", "")
-        .replace("€", "")
-        .replace("$", "");
-    
-    cleaned.parse::<f64>()
-        .map_err(|e| anyhow::anyhow!("Failed to parse amount: {}", e))
+// IBANs and account numbers are compared without spaces or case: banks
+// print "FR76 3000 6000 ..." in one export and "FR7630006000..." in the next
+pub fn normalize_bank_identifier(value: &str) -> String {
+    value.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_uppercase()
+}
+
+pub fn strip_accents(value: &str) -> String {
+    value.chars().map(|c| match c {
+        'à' | 'â' | 'ä' => 'a',
+        'é' | 'è' | 'ê' | 'ë' => 'e',
+        'î' | 'ï' => 'i',
+        'ô' | 'ö' => 'o',
+        'ù' | 'û' | 'ü' => 'u',
+        'ç' => 'c',
+        other => other,
+    }).collect()
+}
+
+const CSV_DELIMITERS: [char; 4] = [',', ';', '\t', '|'];
+const SNIFF_LINES: usize = 20;
+
+// Sniffs the format of an imported file from its content rather than its
+// extension. The confidence is between 0 and 1; below 0.5 the caller should
+// ask the user instead of picking a parser.
+pub fn detect_file_format(bytes: &[u8]) -> FormatDetection {
+    let (text, encoding) = match decode_text(bytes, None) {
+        Ok(decoded) => decoded,
+        Err(_) => (String::from_utf8_lossy(bytes).into_owned(), "UTF-8".to_string()),
+    };
+    let trimmed = text.trim_start();
+    let detection = |format, confidence| FormatDetection {
+        format,
+        confidence,
+        delimiter: None,
+        encoding: encoding.clone(),
+        has_header: false,
+        headers: Vec::new(),
+    };
+
+    if trimmed.starts_with("OFXHEADER") {
+        return detection(FileFormat::Ofx, 0.99);
+    }
+
+    if trimmed.starts_with('<') {
+        // XML documents: OFX 2.x and ISO 20022 statements share the prolog,
+        // so look at the processing instructions and the root element
+        if trimmed.contains("<?OFX") || trimmed.contains("<OFX>") {
+            return detection(FileFormat::Ofx, 0.98);
+        }
+        if trimmed.contains("camt.053") {
+            return detection(FileFormat::Camt053, 0.99);
+        }
+        if trimmed.contains("BkToCstmrStmt") {
+            return detection(FileFormat::Camt053, 0.9);
+        }
+        return detection(FileFormat::Unknown, 0.0);
+    }
+
+    let first_line = trimmed.lines().next().unwrap_or("").trim();
+    if first_line.starts_with("!Type:") || first_line.starts_with("!Account") || first_line.starts_with("!Option") {
+        return detection(FileFormat::Qif, 0.98);
+    }
+    if text.contains("\n!Type:") {
+        return detection(FileFormat::Qif, 0.7);
+    }
+
+    let mt940_tags = [":20:", ":25:", ":60F:", ":61:", ":62F:"]
+        .iter()
+        .filter(|tag| text.lines().any(|line| line.starts_with(*tag)))
+        .count();
+    if mt940_tags >= 4 {
+        return detection(FileFormat::Mt940, 0.97);
+    }
+    if mt940_tags >= 2 && (first_line.starts_with(":20:") || first_line.starts_with("{1:")) {
+        return detection(FileFormat::Mt940, 0.6);
+    }
+
+    match sniff_csv(&text) {
+        Some((delimiter, consistency, rows)) => {
+            let has_header = looks_like_header(&rows);
+            FormatDetection {
+                format: FileFormat::Csv,
+                confidence: 0.9 * consistency,
+                delimiter: Some(delimiter),
+                encoding: encoding.clone(),
+                has_header,
+                headers: if has_header { rows[0].clone() } else { Vec::new() },
+            }
+        }
+        None => detection(FileFormat::Unknown, 0.0),
+    }
+}
+
+// Works out how an imported file is encoded, in order of reliability: byte
+// order mark, UTF-16 zero-byte pattern, valid UTF-8, the charset declared by
+// OFX headers or the XML prolog, then byte statistics. Returns the encoding
+// name and the length of the BOM to skip.
+pub fn detect_encoding(bytes: &[u8]) -> (&'static str, usize) {
+    if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        return ("UTF-8", 3);
+    }
+    if bytes.starts_with(&[0xFF, 0xFE]) {
+        return ("UTF-16LE", 2);
+    }
+    if bytes.starts_with(&[0xFE, 0xFF]) {
+        return ("UTF-16BE", 2);
+    }
+
+    // UTF-16 without BOM: ASCII text leaves every other byte at zero
+    let sample = &bytes[..bytes.len().min(4096)];
+    if sample.len() >= 4 {
+        let pairs = sample.len() / 2;
+        let zero_odd = sample.iter().skip(1).step_by(2).filter(|b| **b == 0).count();
+        let zero_even = sample.iter().step_by(2).filter(|b| **b == 0).count();
+        if zero_odd * 10 > pairs * 3 && zero_even * 10 < pairs {
+            return ("UTF-16LE", 0);
+        }
+        if zero_even * 10 > pairs * 3 && zero_odd * 10 < pairs {
+            return ("UTF-16BE", 0);
+        }
+    }
+
+    // Latin-1 text with accents is almost never valid UTF-8, so valid UTF-8
+    // wins over whatever the header claims
+    if std::str::from_utf8(bytes).is_ok() {
+        return ("UTF-8", 0);
+    }
+
+    if let Some(declared) = declared_charset(sample) {
+        return (declared, 0);
+    }
+
+    // 0x80-0x9F are control codes in ISO-8859-1 but printable in Windows-1252
+    // (€, œ, ’...), which is what French banks actually send
+    if bytes.iter().any(|b| (0x80..=0x9F).contains(b)) {
+        ("windows-1252", 0)
+    } else {
+        ("ISO-8859-1", 0)
+    }
+}
+
+// Reads "CHARSET:1252" from an OFX 1.x header or encoding="..." from an XML prolog
+fn declared_charset(sample: &[u8]) -> Option<&'static str> {
+    let header = String::from_utf8_lossy(sample).to_uppercase();
+
+    if let Some(position) = header.find("CHARSET:") {
+        let value: String = header[position + 8..]
+            .chars()
+            .take_while(|c| !c.is_whitespace())
+            .collect();
+        match value.as_str() {
+            "1252" | "WINDOWS-1252" => return Some("windows-1252"),
+            "ISO-8859-1" | "8859-1" | "LATIN1" => return Some("ISO-8859-1"),
+            "UTF-8" | "UTF8" => return Some("UTF-8"),
+            _ => {}
+        }
+    }
+
+    if let Some(position) = header.find("ENCODING=") {
+        let value: String = header[position + 9..]
+            .trim_start_matches(['"', '\''])
+            .chars()
+            .take_while(|c| *c != '"' && *c != '\'')
+            .collect();
+        return encoding_rs::Encoding::for_label(value.as_bytes()).map(|encoding| match encoding.name() {
+            "windows-1252" if value.contains("8859") => "ISO-8859-1",
+            name => name,
+        });
+    }
+
+    None
+}
+
+// Transcodes an imported file to UTF-8. `encoding` overrides detection, for
+// when the guess reported in the import result was wrong. Returns the text
+// and the name of the encoding that was used.
+pub fn decode_text(bytes: &[u8], encoding: Option<&str>) -> Result<(String, String)> {
+    let (detected, bom_length) = detect_encoding(bytes);
+    let name = match encoding {
+        Some(label) if !label.trim().is_empty() && !label.eq_ignore_ascii_case("auto") => label.trim(),
+        _ => detected,
+    };
+    let encoding = encoding_rs::Encoding::for_label(name.as_bytes())
+        .ok_or_else(|| anyhow!("Encodage inconnu: {}", name))?;
+
+    // A BOM only applies to the encoding it belongs to
+    let body = if name.eq_ignore_ascii_case(detected) { &bytes[bom_length..] } else { bytes };
+    let (text, _) = encoding.decode_without_bom_handling(body);
+
+    Ok((text.into_owned(), name.to_string()))
+}
+
+// Picks the delimiter that splits the first lines into the same number of
+// fields most consistently. Returns the delimiter, the share of lines that
+// agree and the split rows.
+fn sniff_csv(text: &str) -> Option<(char, f32, Vec<Vec<String>>)> {
+    let lines: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).take(SNIFF_LINES).collect();
+    if lines.is_empty() {
+        return None;
+    }
+
+    let mut best: Option<(char, f32, usize)> = None;
+    for delimiter in CSV_DELIMITERS {
+        let counts: Vec<usize> = lines.iter().map(|line| split_csv_line(line, delimiter).len()).collect();
+        let mode = most_common(&counts);
+        if mode < 2 {
+            continue;
+        }
+        let consistency = counts.iter().filter(|c| **c == mode).count() as f32 / counts.len() as f32;
+        let better = match best {
+            None => true,
+            Some((_, best_consistency, best_mode)) => {
+                consistency > best_consistency || (consistency == best_consistency && mode > best_mode)
+            }
+        };
+        if better {
+            best = Some((delimiter, consistency, mode));
+        }
+    }
+
+    let (delimiter, consistency, _) = best?;
+    let rows = lines.iter().map(|line| split_csv_line(line, delimiter)).collect();
+    Some((delimiter, consistency, rows))
+}
+
+fn split_csv_line(line: &str, delimiter: char) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    for c in line.chars() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            c if c == delimiter && !in_quotes => fields.push(std::mem::take(&mut current).trim().to_string()),
+            c => current.push(c),
+        }
+    }
+    fields.push(current.trim().to_string());
+    fields
+}
+
+fn most_common(values: &[usize]) -> usize {
+    let mut best = (0, 0);
+    for value in values {
+        let count = values.iter().filter(|v| *v == value).count();
+        if count > best.1 || (count == best.1 && *value > best.0) {
+            best = (*value, count);
+        }
+    }
+    best.0
+}
+
+// A header row has no amounts or dates while the rows below it do
+fn looks_like_header(rows: &[Vec<String>]) -> bool {
+    let is_data = |field: &String| {
+        let field = field.trim_matches('"');
+        !field.is_empty() && field.chars().filter(|c| c.is_ascii_digit()).count() * 2 >= field.chars().count()
+    };
+    match rows {
+        [first, rest @ ..] if !rest.is_empty() => {
+            !first.iter().any(is_data) && rest.iter().any(|row| row.iter().any(is_data))
+        }
+        _ => false,
+    }
+}
+
+const CURRENCY_SYMBOLS: [(&str, &str); 6] = [
+    ("€", "EUR"), ("$", "USD"), ("£", "GBP"), ("¥", "JPY"), ("₣", "CHF"), ("₹", "INR"),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedAmount {
+    pub magnitude: f64,
+    pub negative: bool,
+    pub currency: Option<String>, // ISO 4217 code when the raw text carried one
+}
+
+impl ParsedAmount {
+    pub fn value(&self) -> f64 {
+        if self.negative { -self.magnitude } else { self.magnitude }
+    }
+}
+
+// Parses amounts such as "1 234,56 €", "-1,234.56", "(12.50)", "12,50-",
+// "EUR 12.50", "12,50 CR" or "1'234.50 CHF". Separators come from `locale`;
+// when it leaves them unset, the last of ',' or '.' is taken as the decimal
+// separator unless it occurs more than once.
+pub fn parse_amount(amount_str: &str, locale: &AmountLocale) -> Result<ParsedAmount> {
+    let invalid = || anyhow!("Montant invalide: {}", amount_str);
+    let mut text: String = amount_str
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '\u{a0}' | '\u{202f}' | '\u{2009}' => ' ',
+            '\u{2212}' => '-',
+            other => other,
+        })
+        .collect();
+    let mut negative = false;
+    let mut currency = None;
+
+    if text.starts_with('(') && text.ends_with(')') {
+        negative = true;
+        text = text[1..text.len() - 1].trim().to_string();
+    }
+
+    // Credit/debit suffixes used by some bank exports
+    let upper = text.to_uppercase();
+    for (suffix, is_debit) in [("CR", false), ("DB", true), ("DR", true)] {
+        if let Some(stripped) = upper.strip_suffix(suffix) {
+            if stripped.ends_with(|c: char| c.is_ascii_digit() || c == ' ' || c == '.') {
+                negative = is_debit;
+                text = text[..stripped.len()].trim().to_string();
+                break;
+            }
+        }
+    }
+
+    for (symbol, code) in CURRENCY_SYMBOLS {
+        if text.contains(symbol) {
+            currency = Some(code.to_string());
+            text = text.replace(symbol, "");
+        }
+    }
+
+    // ISO codes written before or after the number ("EUR 12,50", "12.50USD")
+    let letters: String = text.chars().filter(|c| c.is_alphabetic()).collect();
+    if !letters.is_empty() {
+        if letters.len() != 3 || !letters.chars().all(|c| c.is_ascii_uppercase()) {
+            return Err(invalid());
+        }
+        let trimmed = text.trim();
+        if !(trimmed.starts_with(&letters) || trimmed.ends_with(&letters)) {
+            return Err(invalid());
+        }
+        text = text.replace(&letters, "");
+        currency = Some(letters);
+    }
+
+    let mut text: String = text.chars().filter(|c| !c.is_whitespace() && *c != '\'').collect();
+    if let Some(stripped) = text.strip_suffix('-') {
+        negative = !negative;
+        text = stripped.to_string();
+    } else if let Some(stripped) = text.strip_suffix('+') {
+        text = stripped.to_string();
+    }
+    if let Some(stripped) = text.strip_prefix('-') {
+        negative = !negative;
+        text = stripped.to_string();
+    } else if let Some(stripped) = text.strip_prefix('+') {
+        text = stripped.to_string();
+    }
+
+    let decimal = locale.decimal_separator.or_else(|| infer_decimal_separator(&text));
+    let chars: Vec<char> = text.chars().collect();
+    let mut normalised = String::with_capacity(chars.len());
+    for (index, c) in chars.iter().enumerate() {
+        match c {
+            '0'..='9' => normalised.push(*c),
+            c if Some(*c) == decimal => normalised.push('.'),
+            c if Some(*c) == locale.thousands_separator || *c == ',' || *c == '.' => {
+                // A thousands separator must be followed by exactly three digits,
+                // otherwise "12,50" read with a '.' decimal would become 1250
+                let group = chars.iter().skip(index + 1).take_while(|c| c.is_ascii_digit()).count();
+                if group != 3 {
+                    return Err(invalid());
+                }
+            }
+            _ => return Err(invalid()),
+        }
+    }
+
+    if normalised.is_empty() || normalised.matches('.').count() > 1 || normalised == "." {
+        return Err(invalid());
+    }
+    let magnitude = normalised.parse::<f64>().map_err(|_| invalid())?;
+
+    Ok(ParsedAmount {
+        magnitude,
+        negative: negative && magnitude != 0.0,
+        currency,
+    })
+}
+
+fn infer_decimal_separator(text: &str) -> Option<char> {
+    let last = text.rfind([',', '.'])?;
+    let separator = text[last..].chars().next()?;
+    if text.matches(separator).count() > 1 {
+        // "1.234.567" or "1,234,567": the separator groups thousands
+        return None;
+    }
+    Some(separator)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_numeric_dates_in_the_requested_order() {
+        assert_eq!(parse_date("15/01/2024").unwrap(), "2024-01-15");
+        assert_eq!(parse_date("05.02.2024").unwrap(), "2024-02-05");
+        assert_eq!(parse_date("05-02-24").unwrap(), "2024-02-05");
+        assert_eq!(parse_date_with_order("02/05/2024", DateOrder::MonthFirst).unwrap(), "2024-02-05");
+        assert_eq!(parse_date_with_order("01/15/2024", DateOrder::MonthFirst).unwrap(), "2024-01-15");
+        // Day first cannot read a thirteenth month
+        assert!(parse_date("01/15/2024").is_err());
+        assert!(parse_date("31/02/2024").is_err());
+    }
+
+    #[test]
+    fn parses_iso_and_ofx_dates_whatever_the_order() {
+        assert_eq!(parse_date_with_order("2024-01-15", DateOrder::MonthFirst).unwrap(), "2024-01-15");
+        assert_eq!(parse_date("2024-01-15T10:30:00").unwrap(), "2024-01-15");
+        assert_eq!(parse_date("20240115").unwrap(), "2024-01-15");
+        assert_eq!(parse_date("20240115120000.000[-5:EST]").unwrap(), "2024-01-15");
+        assert!(parse_date("20240115abc").is_err());
+        assert!(parse_date("").is_err());
+    }
+
+    #[test]
+    fn parses_french_month_names() {
+        assert_eq!(parse_date("15 janv. 2024").unwrap(), "2024-01-15");
+        assert_eq!(parse_date("1er mars 2024").unwrap(), "2024-03-01");
+        assert_eq!(parse_date("15 février 2024").unwrap(), "2024-02-15");
+        assert_eq!(parse_date("15 févr. 2024").unwrap(), "2024-02-15");
+        assert_eq!(parse_date("3-AOÛT-24").unwrap(), "2024-08-03");
+        assert!(parse_date("15 brumaire 2024").is_err());
+    }
+
+    #[test]
+    fn pivots_two_digit_years_around_1970() {
+        assert_eq!(parse_date("01/01/69").unwrap(), "2069-01-01");
+        assert_eq!(parse_date("01/01/70").unwrap(), "1970-01-01");
+    }
+
+    fn amount(text: &str) -> f64 {
+        parse_amount(text, &AmountLocale::default()).unwrap().value()
+    }
+
+    #[test]
+    fn infers_the_decimal_separator() {
+        assert_eq!(amount("1.234,56"), 1234.56);
+        assert_eq!(amount("1,234.56"), 1234.56);
+        assert_eq!(amount("1 234,56"), 1234.56);
+        assert_eq!(amount("1\u{a0}234,56"), 1234.56);
+        assert_eq!(amount("1'234.50"), 1234.5);
+        assert_eq!(amount("1.234.567"), 1234567.0);
+        assert_eq!(amount("12"), 12.0);
+    }
+
+    #[test]
+    fn follows_an_explicit_locale() {
+        let dot = AmountLocale { decimal_separator: Some('.'), thousands_separator: Some(',') };
+        assert_eq!(parse_amount("1,234", &dot).unwrap().value(), 1234.0);
+        // A grouping separator followed by two digits is a misread decimal
+        assert!(parse_amount("12,50", &dot).is_err());
+
+        let comma = AmountLocale { decimal_separator: Some(','), thousands_separator: Some('.') };
+        assert_eq!(parse_amount("1.234", &comma).unwrap().value(), 1234.0);
+        assert_eq!(parse_amount("1,234", &comma).unwrap().value(), 1.234);
+    }
+
+    #[test]
+    fn reads_signs_parentheses_and_credit_debit_suffixes() {
+        assert_eq!(amount("-12,50"), -12.5);
+        assert_eq!(amount("\u{2212}12,50"), -12.5);
+        assert_eq!(amount("+12,50"), 12.5);
+        assert_eq!(amount("12,50-"), -12.5);
+        assert_eq!(amount("(12,50)"), -12.5);
+        assert_eq!(amount("12,50 CR"), 12.5);
+        assert_eq!(amount("12,50 DB"), -12.5);
+        assert_eq!(amount("12.50DR"), -12.5);
+        assert!(!parse_amount("-0,00", &AmountLocale::default()).unwrap().negative);
+    }
+
+    #[test]
+    fn detects_the_currency() {
+        let locale = AmountLocale::default();
+        let parsed = parse_amount("1 234,56 €", &locale).unwrap();
+        assert_eq!(parsed.value(), 1234.56);
+        assert_eq!(parsed.currency.as_deref(), Some("EUR"));
+        assert_eq!(parse_amount("EUR 12,50", &locale).unwrap().currency.as_deref(), Some("EUR"));
+        assert_eq!(parse_amount("12.50USD", &locale).unwrap().currency.as_deref(), Some("USD"));
+        assert_eq!(parse_amount("-£3.20", &locale).unwrap().value(), -3.2);
+        assert_eq!(parse_amount("12,50", &locale).unwrap().currency, None);
+    }
+
+    #[test]
+    fn rejects_text_that_is_not_an_amount() {
+        let locale = AmountLocale::default();
+        assert!(parse_amount("", &locale).is_err());
+        assert!(parse_amount("abc", &locale).is_err());
+        assert!(parse_amount("12,50 euros", &locale).is_err());
+        assert!(parse_amount("12 EUR 50", &locale).is_err());
+        assert!(parse_amount("1,2,3", &locale).is_err());
+    }
+
+    #[test]
+    fn detects_statement_formats_from_their_content() {
+        let ofx = detect_file_format(b"OFXHEADER:100\nDATA:OFXSGML\n\n<OFX>");
+        assert_eq!(ofx.format, FileFormat::Ofx);
+        assert!(ofx.confidence > 0.9);
+        let ofx_xml = detect_file_format(b"<?xml version=\"1.0\"?>\n<?OFX OFXHEADER=\"200\"?>\n<OFX></OFX>");
+        assert_eq!(ofx_xml.format, FileFormat::Ofx);
+
+        let camt = detect_file_format(b"<?xml version=\"1.0\"?>\n<Document xmlns=\"urn:iso:std:iso:20022:tech:xsd:camt.053.001.02\"><BkToCstmrStmt/></Document>");
+        assert_eq!(camt.format, FileFormat::Camt053);
+        assert_eq!(detect_file_format(b"<html></html>").format, FileFormat::Unknown);
+
+        assert_eq!(detect_file_format(b"!Type:Bank\nD15/01/2024\nT-42.50\n^\n").format, FileFormat::Qif);
+
+        let mt940 = detect_file_format(b":20:STMT\n:25:FR76\n:60F:C240101EUR1,00\n:61:240102D1,00NTRF\n:62F:C240102EUR0,00\n");
+        assert_eq!(mt940.format, FileFormat::Mt940);
+        assert!(mt940.confidence > 0.9);
+    }
+
+    #[test]
+    fn sniffs_the_csv_delimiter_and_header() {
+        let detection = detect_file_format(b"Date;Libell\xe9;Montant\n15/01/2024;CB CARREFOUR;-42,50\n16/01/2024;SALAIRE;2500,00\n");
+        assert_eq!(detection.format, FileFormat::Csv);
+        assert_eq!(detection.delimiter, Some(';'));
+        assert_eq!(detection.encoding, "ISO-8859-1");
+        assert!(detection.has_header);
+        assert_eq!(detection.headers, vec!["Date", "Libellé", "Montant"]);
+        assert!((detection.confidence - 0.9).abs() < 1e-6);
+
+        // Commas inside quotes do not split fields
+        let detection = detect_file_format(b"2024-01-15,\"CB CARREFOUR, PARIS\",-42.50\n2024-01-16,SALAIRE,2500.00\n");
+        assert_eq!(detection.delimiter, Some(','));
+        assert!(!detection.has_header);
+        assert!(detection.headers.is_empty());
+    }
+
+    #[test]
+    fn gives_up_on_unrecognised_files() {
+        let detection = detect_file_format(b"just some notes\nwith no structure\n");
+        assert_eq!(detection.format, FileFormat::Unknown);
+        assert_eq!(detection.confidence, 0.0);
+        assert_eq!(detect_file_format(b"").format, FileFormat::Unknown);
+    }
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    fn period(kind: &str, day: &str, start_day: u32) -> (NaiveDate, NaiveDate) {
+        budget_period(kind, date(day), start_day).unwrap()
+    }
+
+    #[test]
+    fn moves_a_late_start_day_to_the_end_of_short_months() {
+        assert_eq!(period("monthly", "2024-02-15", 31), (date("2024-01-31"), date("2024-02-29")));
+        assert_eq!(period("monthly", "2024-02-29", 31), (date("2024-02-29"), date("2024-03-31")));
+        assert_eq!(period("monthly", "2024-03-30", 31), (date("2024-02-29"), date("2024-03-31")));
+        assert_eq!(period("monthly", "2023-02-28", 31), (date("2023-02-28"), date("2023-03-31")));
+        assert_eq!(period("monthly", "2023-02-27", 30), (date("2023-01-30"), date("2023-02-28")));
+        assert_eq!(period("monthly", "2024-12-31", 31), (date("2024-12-31"), date("2025-01-31")));
+    }
+
+    #[test]
+    fn starts_monthly_periods_on_the_requested_day() {
+        assert_eq!(period("monthly", "2024-01-17", 1), (date("2024-01-01"), date("2024-02-01")));
+        assert_eq!(period("monthly", "2024-01-04", 5), (date("2023-12-05"), date("2024-01-05")));
+        assert_eq!(period("monthly", "2024-01-05", 5), (date("2024-01-05"), date("2024-02-05")));
+    }
+
+    #[test]
+    fn aligns_weeks_quarters_and_years() {
+        // 2024-01-17 is a Wednesday
+        assert_eq!(period("weekly", "2024-01-17", 1), (date("2024-01-15"), date("2024-01-22")));
+        assert_eq!(period("weekly", "2024-01-17", 7), (date("2024-01-14"), date("2024-01-21")));
+        assert_eq!(period("quarterly", "2024-05-10", 1), (date("2024-04-01"), date("2024-07-01")));
+        assert_eq!(period("quarterly", "2024-04-10", 15), (date("2024-01-15"), date("2024-04-15")));
+        assert_eq!(period("yearly", "2024-06-01", 1), (date("2024-01-01"), date("2025-01-01")));
+        assert!(budget_period("daily", date("2024-06-01"), 1).is_none());
+    }
+
+    #[test]
+    fn rolls_categories_up_to_a_depth() {
+        assert_eq!(category_rollup("Logement > Électricité > Compteur", 1), "Logement");
+        assert_eq!(category_rollup("Logement > Électricité > Compteur", 2), "Logement > Électricité");
+        assert_eq!(category_rollup("Logement > Électricité", 5), "Logement > Électricité");
+        // Depth 0 still keeps the top level
+        assert_eq!(category_rollup("Logement > Électricité", 0), "Logement");
+    }
+
+    #[test]
+    fn rebases_a_category_and_its_children_only() {
+        assert_eq!(rebase_category("Alimentation", "Alimentation", "Maison").as_deref(), Some("Maison"));
+        assert_eq!(
+            rebase_category("Alimentation > Courses > Bio", "Alimentation", "Maison > Repas").as_deref(),
+            Some("Maison > Repas > Courses > Bio")
+        );
+        // Case and surrounding spaces are ignored, the rest of the path keeps its case
+        assert_eq!(rebase_category(" ÉNERGIE > Gaz ", "énergie", "Logement").as_deref(), Some("Logement > Gaz"));
+        assert_eq!(rebase_category("Alimentation2", "Alimentation", "Maison"), None);
+        assert_eq!(rebase_category("Loisirs > Alimentation", "Alimentation", "Maison"), None);
+        assert_eq!(rebase_category("Alimentation", "Alimentation > Courses", "Maison"), None);
+    }
+
+    #[test]
+    fn tells_whether_a_category_is_under_another() {
+        assert!(is_in_category("Alimentation", "Alimentation"));
+        assert!(is_in_category("alimentation > Courses", "Alimentation"));
+        assert!(!is_in_category("Alimentation", "Alimentation > Courses"));
+        assert!(!is_in_category("Alimentation2", "Alimentation"));
+    }
 }